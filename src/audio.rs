@@ -0,0 +1,265 @@
+use crate::Direction;
+use crate::events::GameEvent;
+use crate::settings::AudioSettings;
+
+// The music speeds up a step every few levels once the game gets going
+const MUSIC_SPEED_UP_LEVEL: u32 = 10;
+const LEVELS_PER_TEMPO: u32 = 5;
+// every speed the music can play at, the backends make a copy of it for each
+pub const MUSIC_TEMPOS: [f32; 3] = [1.0, 1.25, 1.5];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sound {
+    Move,
+    Rotate,
+    Lock,
+    Single,
+    Double,
+    Triple,
+    Tetris,
+    TSpin,
+    LevelUp,
+    Hold,
+    GameOver,
+}
+impl Sound {
+    pub const ALL: [Sound; 11] = [
+        Sound::Move,
+        Sound::Rotate,
+        Sound::Lock,
+        Sound::Single,
+        Sound::Double,
+        Sound::Triple,
+        Sound::Tetris,
+        Sound::TSpin,
+        Sound::LevelUp,
+        Sound::Hold,
+        Sound::GameOver,
+    ];
+
+    #[must_use]
+    pub fn for_lines(lines: usize) -> Option<Sound> {
+        match lines {
+            0 => None,
+            1 => Some(Sound::Single),
+            2 => Some(Sound::Double),
+            3 => Some(Sound::Triple),
+            _ => Some(Sound::Tetris),
+        }
+    }
+}
+
+// Something that can make a noise. The game only ever talks to this so it can be
+// played with or without a sound card, volumes run from 0.0 to 1.0
+pub trait AudioBackend {
+    fn play_sound(&mut self, sound: Sound, volume: f32);
+    // start the music looping or change how it's playing. Tempo is one of MUSIC_TEMPOS
+    fn play_music(&mut self, volume: f32, tempo: f32);
+    fn stop_music(&mut self);
+}
+impl AudioBackend for Box<dyn AudioBackend> {
+    fn play_sound(&mut self, sound: Sound, volume: f32) {
+        (**self).play_sound(sound, volume);
+    }
+    fn play_music(&mut self, volume: f32, tempo: f32) {
+        (**self).play_music(volume, tempo);
+    }
+    fn stop_music(&mut self) {
+        (**self).stop_music();
+    }
+}
+
+// Plays nothing but remembers what it was asked to, for tests & for running
+// without sound
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NullAudio {
+    pub played: Vec<(Sound, f32)>,
+    pub music: Option<(f32, f32)>, // volume & tempo while the music is on
+}
+impl AudioBackend for NullAudio {
+    fn play_sound(&mut self, sound: Sound, volume: f32) {
+        self.played.push((sound, volume));
+    }
+    fn play_music(&mut self, volume: f32, tempo: f32) {
+        self.music = Some((volume, tempo));
+    }
+    fn stop_music(&mut self) {
+        self.music = None;
+    }
+}
+
+// How fast the music plays at a level
+#[must_use]
+pub fn music_tempo(level: u32) -> f32 {
+    if level < MUSIC_SPEED_UP_LEVEL {
+        return MUSIC_TEMPOS[0];
+    }
+    let step = 1 + (level - MUSIC_SPEED_UP_LEVEL) / LEVELS_PER_TEMPO;
+    MUSIC_TEMPOS[(step as usize).min(MUSIC_TEMPOS.len() - 1)]
+}
+
+// The sounds for one update's events, each at most once however many times it happened
+#[must_use]
+pub fn sounds_for(events: &[GameEvent]) -> Vec<Sound> {
+    let mut ret_sounds = Vec::new();
+    for event in events {
+        let sound = match event {
+            // soft dropping moves down every few frames, it would only be noise
+            GameEvent::PieceMoved(Direction::Left | Direction::Right) => Some(Sound::Move),
+            GameEvent::PieceRotated => Some(Sound::Rotate),
+            GameEvent::PieceHeld(_) => Some(Sound::Hold),
+            GameEvent::PieceLocked { t_spin: true, .. } => Some(Sound::TSpin),
+            GameEvent::PieceLocked { .. } => Some(Sound::Lock),
+            GameEvent::LinesCleared(rows) => Sound::for_lines(rows.len()),
+            GameEvent::LevelUp(_) | GameEvent::Completed => Some(Sound::LevelUp),
+            GameEvent::GameOver => Some(Sound::GameOver),
+            _ => None,
+        };
+        if let Some(sound) = sound
+            && !ret_sounds.contains(&sound)
+        {
+            ret_sounds.push(sound);
+        }
+    }
+    ret_sounds
+}
+
+// Turns what happens in the game into sounds & keeps the music going while there's
+// a game on screen. Like the effects it only listens to the game's events
+pub struct Audio<B: AudioBackend = Box<dyn AudioBackend>> {
+    backend: B,
+    music: Option<(f32, f32)>, // what the backend was last told to play
+}
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new(Box::new(NullAudio::default()))
+    }
+}
+impl<B: AudioBackend> Audio<B> {
+    #[must_use]
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend,
+            music: None,
+        }
+    }
+    #[must_use]
+    pub fn get_backend(&self) -> &B {
+        &self.backend
+    }
+    pub fn handle_events(&mut self, events: &[GameEvent], settings: &AudioSettings) {
+        let volume = settings.master_volume * settings.effects_volume;
+        for sound in sounds_for(events) {
+            if volume > 0.0 {
+                self.backend.play_sound(sound, volume);
+            }
+            if sound == Sound::GameOver {
+                self.stop_music();
+            }
+        }
+    }
+    // Called every frame with the level of the game on screen, or None when there
+    // isn't one, so the music follows pausing, the volume sliders & the level
+    pub fn update_music(&mut self, level: Option<u32>, settings: &AudioSettings) {
+        let volume = settings.master_volume * settings.music_volume;
+        match level {
+            Some(level) if volume > 0.0 => {
+                let music = (volume, music_tempo(level));
+                if self.music != Some(music) {
+                    self.backend.play_music(music.0, music.1);
+                    self.music = Some(music);
+                }
+            }
+            _ => self.stop_music(),
+        }
+    }
+    pub fn stop_music(&mut self) {
+        if self.music.take().is_some() {
+            self.backend.stop_music();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::ICoordinate;
+    use crate::tetromino::TetrominoShape;
+
+    fn locked(t_spin: bool) -> GameEvent {
+        GameEvent::PieceLocked {
+            shape: TetrominoShape::T,
+            finesse_faults: 0,
+            cells: vec![ICoordinate::new(4, 15)],
+            t_spin,
+        }
+    }
+    #[test]
+    fn test_events_make_sounds_once() {
+        let events = [
+            GameEvent::PieceMoved(Direction::Left),
+            GameEvent::PieceMoved(Direction::Left),
+            GameEvent::PieceMoved(Direction::Down),
+            GameEvent::PieceRotated,
+            locked(false),
+            GameEvent::LinesCleared(vec![14, 15]),
+            GameEvent::LevelUp(2),
+        ];
+        assert_eq!(
+            sounds_for(&events),
+            vec![
+                Sound::Move,
+                Sound::Rotate,
+                Sound::Lock,
+                Sound::Double,
+                Sound::LevelUp
+            ]
+        );
+        let t_spin = [locked(true), GameEvent::LinesCleared(vec![12, 13, 14, 15])];
+        assert_eq!(sounds_for(&t_spin), vec![Sound::TSpin, Sound::Tetris]);
+    }
+    #[test]
+    fn test_volumes_multiply() {
+        let mut audio = Audio::new(NullAudio::default());
+        let settings = AudioSettings {
+            master_volume: 0.5,
+            effects_volume: 0.5,
+            music_volume: 1.0,
+        };
+        audio.handle_events(&[GameEvent::PieceHeld(TetrominoShape::I)], &settings);
+        assert_eq!(audio.get_backend().played, vec![(Sound::Hold, 0.25)]);
+        audio.update_music(Some(1), &settings);
+        assert_eq!(audio.get_backend().music, Some((0.5, 1.0)));
+        // nothing is played at all with the effects turned right down
+        let quiet = AudioSettings {
+            effects_volume: 0.0,
+            ..settings
+        };
+        audio.handle_events(&[GameEvent::PieceRotated], &quiet);
+        assert_eq!(audio.get_backend().played.len(), 1);
+    }
+    #[test]
+    fn test_music_follows_the_game() {
+        let mut audio = Audio::new(NullAudio::default());
+        let settings = AudioSettings::default();
+        audio.update_music(Some(3), &settings);
+        assert_eq!(audio.get_backend().music, Some((0.7, 1.0)));
+        audio.update_music(Some(12), &settings);
+        assert_eq!(audio.get_backend().music, Some((0.7, 1.25)));
+        // paused
+        audio.update_music(None, &settings);
+        assert_eq!(audio.get_backend().music, None);
+        audio.update_music(Some(12), &settings);
+        audio.handle_events(&[GameEvent::GameOver], &settings);
+        assert_eq!(audio.get_backend().music, None);
+        assert_eq!(audio.get_backend().played, vec![(Sound::GameOver, 1.0)]);
+    }
+    #[test]
+    fn test_music_speeds_up_at_high_levels() {
+        assert!((music_tempo(0) - 1.0).abs() < f32::EPSILON);
+        assert!((music_tempo(9) - 1.0).abs() < f32::EPSILON);
+        assert!((music_tempo(10) - 1.25).abs() < f32::EPSILON);
+        assert!((music_tempo(15) - 1.5).abs() < f32::EPSILON);
+        assert!((music_tempo(99) - 1.5).abs() < f32::EPSILON);
+    }
+}
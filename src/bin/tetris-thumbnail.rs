@@ -0,0 +1,102 @@
+// Draws a replay's board as a small PNG without opening a window, for showing next
+// to replays in lists & on leaderboards:
+//
+//   tetris-thumbnail <replay file> <png file> [--tick N] [--scale N] [--theme NAME]
+//
+// The picture is of the end of the game unless --tick picks an earlier moment.
+// --scale is how many pixels each square of the board takes, 8 unless given.
+// --theme is any built in theme or one from the themes directory, Classic unless given
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use rust_tetris::constants::{BOARD_HEIGHT, BOARD_WIDTH};
+use rust_tetris::render::draw_playfield;
+use rust_tetris::replay::{Playback, Replay};
+use rust_tetris::software_renderer::SoftwareRenderer;
+use rust_tetris::theme::Theme;
+
+const USAGE: &str =
+    "usage: tetris-thumbnail <replay file> <png file> [--tick N] [--scale N] [--theme NAME]";
+const DEFAULT_SCALE: u32 = 8;
+
+struct Arguments {
+    replay: PathBuf,
+    output: PathBuf,
+    tick: Option<u64>,
+    scale: u32,
+    theme: Option<String>,
+}
+
+fn parse_number<T: FromStr>(argument: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{argument} needs a number, not {value}"))
+}
+
+fn parse_arguments(mut arguments: impl Iterator<Item = String>) -> Result<Arguments, String> {
+    let mut paths = Vec::new();
+    let (mut tick, mut scale, mut theme) = (None, None, None);
+    while let Some(argument) = arguments.next() {
+        if !argument.starts_with("--") {
+            paths.push(PathBuf::from(argument));
+            continue;
+        }
+        let value = arguments
+            .next()
+            .ok_or_else(|| format!("{argument} needs a value"))?;
+        match argument.as_str() {
+            "--tick" => tick = Some(parse_number(&argument, &value)?),
+            "--scale" => scale = Some(parse_number::<u32>(&argument, &value)?.clamp(1, 64)),
+            "--theme" => theme = Some(value),
+            _ => return Err(format!("unexpected argument {argument}")),
+        }
+    }
+    let [replay, output] = <[PathBuf; 2]>::try_from(paths)
+        .map_err(|_| "needs a replay file and a png file".to_string())?;
+    Ok(Arguments {
+        replay,
+        output,
+        tick,
+        scale: scale.unwrap_or(DEFAULT_SCALE),
+        theme,
+    })
+}
+
+fn main() -> ExitCode {
+    let arguments = match parse_arguments(std::env::args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(error) => {
+            eprintln!("{error}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let replay = match Replay::load(&arguments.replay) {
+        Ok(replay) => replay,
+        Err(error) => {
+            eprintln!("{}: {error}", arguments.replay.display());
+            return ExitCode::from(2);
+        }
+    };
+
+    let length = replay.get_length();
+    let mut playback = Playback::new(replay);
+    playback.seek(arguments.tick.unwrap_or(length).min(length));
+
+    let themes = Theme::load_all(Theme::default_directory().as_deref());
+    let name = arguments.theme.as_deref().unwrap_or("Classic");
+    let Some(theme) = themes.iter().find(|theme| theme.name == name) else {
+        eprintln!("there's no theme called {name}");
+        return ExitCode::from(2);
+    };
+
+    let scale = arguments.scale;
+    let mut renderer = SoftwareRenderer::new(BOARD_WIDTH * scale, BOARD_HEIGHT * scale);
+    let game = playback.get_game();
+    draw_playfield(&mut renderer, game, (0.0, 0.0), scale as f32, theme);
+    if let Err(error) = renderer.save_png(&arguments.output) {
+        eprintln!("{}: {error}", arguments.output.display());
+        return ExitCode::from(1);
+    }
+    ExitCode::SUCCESS
+}
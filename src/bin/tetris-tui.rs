@@ -0,0 +1,284 @@
+// Plays the game in a terminal, for playing over SSH and trying out gameplay without
+// a window. Uses the same core as the windowed game, so settings, replays and scoring
+// all behave the same:
+//
+//   tetris-tui
+//
+// Arrows move & soft drop, space hard drops, up/X rotate, Z rotates back, C holds,
+// P or escape pauses, R starts again once the game is over and Q quits
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+
+use rust_tetris::constants::{BOARD_HEIGHT, BOARD_WIDTH, NEXT_QUEUE_SIZE};
+use rust_tetris::coordinate::UCoordinate;
+use rust_tetris::game::{Game, GameMode, Ruleset};
+use rust_tetris::input::{Action, ActionState};
+use rust_tetris::replay::Replay;
+use rust_tetris::rng::Rng;
+use rust_tetris::settings::Settings;
+use rust_tetris::tetromino::TetrominoShape;
+
+// how long to wait for keys between frames, about 60 frames a second
+const FRAME_TIME: Duration = Duration::from_millis(16);
+// where the stats are drawn, in terminal columns. Each board square is two columns
+// wide so it comes out roughly square
+const PANEL_COLUMN: u16 = BOARD_WIDTH as u16 * 2 + 3;
+
+fn terminal_colour(colour: u32) -> Color {
+    match colour {
+        0 => Color::Cyan,
+        1 => Color::DarkBlue,
+        2 => Color::Rgb {
+            r: 255,
+            g: 161,
+            b: 0,
+        },
+        3 => Color::Green,
+        4 => Color::Red,
+        5 => Color::Yellow,
+        6 => Color::Magenta,
+        8 => Color::White,
+        _ => Color::DarkGrey,
+    }
+}
+
+fn action_for_key(code: KeyCode) -> Option<Action> {
+    match code {
+        KeyCode::Left | KeyCode::Char('a') => Some(Action::MoveLeft),
+        KeyCode::Right | KeyCode::Char('d') => Some(Action::MoveRight),
+        KeyCode::Down | KeyCode::Char('s') => Some(Action::SoftDrop),
+        KeyCode::Char(' ') => Some(Action::HardDrop),
+        KeyCode::Up | KeyCode::Char('x' | 'w') => Some(Action::Rotate),
+        KeyCode::Char('z') => Some(Action::RotateCounterClockwise),
+        KeyCode::Char('c') => Some(Action::Hold),
+        KeyCode::Char('u') | KeyCode::Backspace => Some(Action::Undo),
+        KeyCode::Char('p') | KeyCode::Esc => Some(Action::Pause),
+        KeyCode::Char('r') | KeyCode::Enter => Some(Action::Confirm),
+        KeyCode::Char('q') => Some(Action::Back),
+        _ => None,
+    }
+}
+
+// Most terminals only send key presses, with the keyboard's own repeat while a key is
+// held, and never say when it was let go. Each of those is treated as a fresh press,
+// so holding a key moves at the keyboard's repeat rate rather than the game's.
+// Terminals that can report releases are asked to, and then keys are held properly
+struct TerminalInput {
+    reports_releases: bool,
+    held: ActionState,
+}
+impl TerminalInput {
+    fn new(reports_releases: bool) -> Self {
+        Self {
+            reports_releases,
+            held: ActionState::new(),
+        }
+    }
+    fn release(&mut self, action: Action) {
+        let mut ret_held = ActionState::new();
+        for other in Action::ALL {
+            if other != action && self.held.is_down(other) {
+                ret_held.set_down(other);
+            }
+        }
+        self.held = ret_held;
+    }
+    fn handle_key(&mut self, key: KeyEvent, actions: &mut ActionState) {
+        let Some(action) = action_for_key(key.code) else {
+            return;
+        };
+        match key.kind {
+            KeyEventKind::Press => {
+                actions.set_pressed(action);
+                if self.reports_releases {
+                    self.held.set_down(action);
+                }
+            }
+            // the game does its own repeating when it knows a key is held
+            KeyEventKind::Repeat if !self.reports_releases => actions.set_pressed(action),
+            KeyEventKind::Repeat => {}
+            KeyEventKind::Release => self.release(action),
+        }
+    }
+    // everything that happened over one frame, waiting at most the frame time
+    fn poll(&mut self, timeout: Duration) -> io::Result<Option<ActionState>> {
+        let started = Instant::now();
+        let mut ret_actions = self.held;
+        while let Some(remaining) = timeout.checked_sub(started.elapsed()) {
+            if !event::poll(remaining)? {
+                break;
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    return Ok(None);
+                }
+                self.handle_key(key, &mut ret_actions);
+            }
+        }
+        Ok(Some(ret_actions))
+    }
+}
+
+fn new_game(settings: &Settings) -> Game {
+    Game::new(
+        GameMode::Classic,
+        Ruleset::from_settings(settings),
+        Rng::random_seed(),
+    )
+}
+
+fn save_replay(game: &Game) {
+    if game.get_statistics().get_pieces() == 0 {
+        return;
+    }
+    if let Some(directory) = Replay::default_directory() {
+        let path = directory.join(Replay::file_name(game.get_mode()));
+        // there's nowhere to show an error once the terminal is back to normal
+        let _ = game.get_replay().save(&path);
+    }
+}
+
+fn draw_square(out: &mut Stdout, x: u32, y: u32, colour: u32) -> io::Result<()> {
+    queue!(
+        out,
+        cursor::MoveTo(x as u16 * 2, y as u16),
+        SetBackgroundColor(terminal_colour(colour)),
+        Print("  "),
+        ResetColor
+    )
+}
+
+fn draw_line(out: &mut Stdout, row: u16, text: &str) -> io::Result<()> {
+    queue!(
+        out,
+        cursor::MoveTo(PANEL_COLUMN, row),
+        terminal::Clear(ClearType::UntilNewLine),
+        Print(text)
+    )
+}
+
+fn draw_shape_letters(out: &mut Stdout, shapes: &[TetrominoShape]) -> io::Result<()> {
+    for shape in shapes {
+        queue!(
+            out,
+            SetForegroundColor(terminal_colour(shape.index() as u32)),
+            Print(format!("{} ", shape.letter())),
+            ResetColor
+        )?;
+    }
+    Ok(())
+}
+
+fn draw(out: &mut Stdout, game: &Game, paused: bool) -> io::Result<()> {
+    let board = game.get_board();
+    for y in 0..BOARD_HEIGHT {
+        for x in 0..BOARD_WIDTH {
+            match board.get_board_character_at_coordinate(&UCoordinate::new(x, y)) {
+                ' ' => queue!(out, cursor::MoveTo(x as u16 * 2, y as u16), Print("  "))?,
+                square => draw_square(out, x, y, square.to_digit(10).unwrap_or(9))?,
+            }
+        }
+    }
+    if !game.is_game_over() && !game.is_between_pieces() {
+        let tetromino = game.get_current_tetromino();
+        for cell in tetromino.get_cells() {
+            if cell.x >= 0 && cell.y >= 0 {
+                draw_square(out, cell.x as u32, cell.y as u32, tetromino.get_colour())?;
+            }
+        }
+    }
+
+    let statistics = game.get_statistics();
+    draw_line(out, 0, &format!("Score: {}", game.get_score()))?;
+    draw_line(out, 1, &format!("Lines: {}", game.get_lines()))?;
+    draw_line(out, 2, &format!("Level: {}", game.get_level()))?;
+    draw_line(out, 3, &format!("Time:  {:.1}s", game.get_time()))?;
+    draw_line(
+        out,
+        4,
+        &format!("PPS:   {:.2}", statistics.pieces_per_second()),
+    )?;
+    draw_line(
+        out,
+        5,
+        &format!("APM:   {:.1}", statistics.attack_per_minute()),
+    )?;
+    draw_line(out, 6, "Next:  ")?;
+    draw_shape_letters(out, &game.get_next(NEXT_QUEUE_SIZE))?;
+    draw_line(out, 7, "Hold:  ")?;
+    draw_shape_letters(out, game.get_held().as_slice())?;
+    let status = if game.is_game_over() {
+        "GAME OVER - R to play again, Q to quit"
+    } else if paused {
+        "PAUSED - P to carry on, Q to quit"
+    } else {
+        ""
+    };
+    draw_line(out, 9, status)?;
+    out.flush()
+}
+
+fn run(out: &mut Stdout, settings: &Settings, reports_releases: bool) -> io::Result<()> {
+    let mut input = TerminalInput::new(reports_releases);
+    let mut game = new_game(settings);
+    let mut paused = false;
+    let mut last_frame = Instant::now();
+    while let Some(actions) = input.poll(FRAME_TIME)? {
+        let delta_time = last_frame.elapsed().as_secs_f64();
+        last_frame = Instant::now();
+        if actions.is_pressed(Action::Back) {
+            break;
+        }
+        if game.is_game_over() {
+            if actions.is_pressed(Action::Confirm) {
+                save_replay(&game);
+                game = new_game(settings);
+            }
+        } else if actions.is_pressed(Action::Pause) {
+            paused = !paused;
+        } else if !paused {
+            game.update(&actions, delta_time);
+        }
+        draw(out, &game, paused)?;
+    }
+    save_replay(&game);
+    Ok(())
+}
+
+fn main() -> io::Result<()> {
+    let settings = Settings::load().unwrap_or_default();
+    let mut out = io::stdout();
+
+    terminal::enable_raw_mode()?;
+    let reports_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    execute!(
+        out,
+        EnterAlternateScreen,
+        cursor::Hide,
+        Clear(ClearType::All)
+    )?;
+    if reports_releases {
+        execute!(
+            out,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+
+    let result = run(&mut out, &settings, reports_releases);
+
+    // put the terminal back however the game ended
+    if reports_releases {
+        let _ = execute!(out, PopKeyboardEnhancementFlags);
+    }
+    let _ = execute!(out, ResetColor, cursor::Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+    result
+}
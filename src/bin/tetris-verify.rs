@@ -0,0 +1,97 @@
+// Replays a saved game with no window and checks it ends the way it says it does.
+// Used to check leaderboard submissions offline:
+//
+//   tetris-verify <replay file> [--score N] [--lines N] [--level N]
+//
+// The claimed result comes from the replay itself, any of the flags override it.
+// Exits 0 if everything matches, 1 on a mismatch and 2 if the replay can't be read
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use rust_tetris::constants::TICKS_PER_SECOND;
+use rust_tetris::replay::{Replay, ReplayResult};
+
+const USAGE: &str = "usage: tetris-verify <replay file> [--score N] [--lines N] [--level N]";
+
+struct Arguments {
+    path: PathBuf,
+    score: Option<u32>,
+    lines: Option<u32>,
+    level: Option<u32>,
+}
+
+fn parse_arguments(mut arguments: impl Iterator<Item = String>) -> Result<Arguments, String> {
+    let mut path = None;
+    let (mut score, mut lines, mut level) = (None, None, None);
+    while let Some(argument) = arguments.next() {
+        let target = match argument.as_str() {
+            "--score" => &mut score,
+            "--lines" => &mut lines,
+            "--level" => &mut level,
+            _ if path.is_none() && !argument.starts_with("--") => {
+                path = Some(PathBuf::from(argument));
+                continue;
+            }
+            _ => return Err(format!("unexpected argument {argument}")),
+        };
+        let value = arguments
+            .next()
+            .ok_or_else(|| format!("{argument} needs a value"))?;
+        *target = Some(
+            value
+                .parse()
+                .map_err(|_| format!("{argument} needs a number, not {value}"))?,
+        );
+    }
+    Ok(Arguments {
+        path: path.ok_or("no replay file given")?,
+        score,
+        lines,
+        level,
+    })
+}
+
+fn print_result(result: &ReplayResult) {
+    let seconds = result.ticks as f64 / f64::from(TICKS_PER_SECOND);
+    println!("ticks: {} ({seconds:.3}s)", result.ticks);
+    println!("score: {}", result.score);
+    println!("lines: {}", result.lines);
+    println!("level: {}", result.level);
+    println!("board hash: {:016x}", result.board_hash);
+}
+
+fn main() -> ExitCode {
+    let arguments = match parse_arguments(std::env::args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(error) => {
+            eprintln!("{error}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let replay = match Replay::load(&arguments.path) {
+        Ok(replay) => replay,
+        Err(error) => {
+            eprintln!("{}: {error}", arguments.path.display());
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut claimed = replay.result.clone();
+    claimed.score = arguments.score.unwrap_or(claimed.score);
+    claimed.lines = arguments.lines.unwrap_or(claimed.lines);
+    claimed.level = arguments.level.unwrap_or(claimed.level);
+
+    let actual = replay.simulate();
+    println!("mode: {}", replay.mode.name());
+    print_result(&actual);
+
+    let differences = claimed.differences(&actual);
+    if differences.is_empty() {
+        println!("OK");
+        return ExitCode::SUCCESS;
+    }
+    for (field, claimed_value, actual_value) in differences {
+        eprintln!("MISMATCH {field}: claimed {claimed_value}, replayed {actual_value}");
+    }
+    ExitCode::from(1)
+}
@@ -1,12 +1,51 @@
+use std::fmt;
+use std::str::FromStr;
+
 use crate::Direction;
 use crate::constants::{BOARD_HEIGHT, BOARD_WIDTH, TETROMINO_SIZE};
 use crate::coordinate::UCoordinate;
-use crate::tetromino::Tetromino;
+use crate::tetromino::{Tetromino, TetrominoShape};
 
+// What's wrong with a board, or a position, written out as text
+#[derive(Debug, PartialEq)]
+pub enum BoardTextError {
+    TooManyRows,
+    RowWidth(usize),
+    Square(char),
+    Piece(char),
+    Field(String),
+}
+impl fmt::Display for BoardTextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardTextError::TooManyRows => {
+                write!(f, "there are more than {} rows", BOARD_HEIGHT - 1)
+            }
+            BoardTextError::RowWidth(row) => {
+                write!(f, "row {row} should be {} squares wide", BOARD_WIDTH - 2)
+            }
+            BoardTextError::Square(square) => write!(
+                f,
+                "'{square}' is not a square, use _ for empty, G for garbage or a piece letter"
+            ),
+            BoardTextError::Piece(letter) => write!(f, "there is no {letter} piece"),
+            BoardTextError::Field(field) => write!(f, "don't know what {field} is"),
+        }
+    }
+}
+impl std::error::Error for BoardTextError {}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Board {
     board: [char; (BOARD_HEIGHT * BOARD_WIDTH) as usize],
 }
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl Board {
+    #[must_use]
     pub fn new() -> Board {
         let mut s = Self {
             board: [' '; (BOARD_HEIGHT * BOARD_WIDTH) as usize],
@@ -21,19 +60,20 @@ impl Board {
         for y in 0..BOARD_HEIGHT {
             for x in 0..BOARD_WIDTH {
                 if (x == 0 || x == BOARD_WIDTH - 1) || y == BOARD_HEIGHT - 1 {
-                    self.board[self.convert_xy_to_array_position(&UCoordinate::new(x, y))] = '9';
+                    self.board[Self::convert_xy_to_array_position(&UCoordinate::new(x, y))] = '9';
                 }
             }
         }
     }
-    fn convert_xy_to_array_position(&self, coordinate: &UCoordinate) -> usize {
+    fn convert_xy_to_array_position(coordinate: &UCoordinate) -> usize {
         // this allows us to take an X,Y and flatten it out onto the board 1D array
         (coordinate.x + coordinate.y * BOARD_WIDTH) as usize
     }
 
+    #[must_use]
     pub fn can_piece_move(&self, tetromino: Tetromino, direction: Direction) -> bool {
         // check if the piece can move into it's new area.
-        let mut temp_tetromino = tetromino.clone();
+        let mut temp_tetromino = tetromino;
         match direction {
             Direction::Left => {
                 temp_tetromino.move_left();
@@ -47,46 +87,59 @@ impl Board {
         }
         for y in 0..TETROMINO_SIZE {
             for x in 0..TETROMINO_SIZE {
-                if temp_tetromino.get_val_at_xy(&UCoordinate::new(x, y)) == 'X' {
-                    if self.board[self.convert_xy_to_array_position(&UCoordinate::new(
+                if temp_tetromino.get_val_at_xy(&UCoordinate::new(x, y)) == 'X'
+                    && self.board[Self::convert_xy_to_array_position(&UCoordinate::new(
                         (x as i32 + temp_tetromino.get_coordinates().x) as u32,
                         (y as i32 + temp_tetromino.get_coordinates().y) as u32,
                     ))] != ' '
-                    {
-                        return false;
-                    }
+                {
+                    return false;
                 }
             }
         }
         true
     }
+    // whether the piece fits where it is without overlapping anything
+    #[must_use]
+    pub fn does_piece_fit(&self, tetromino: Tetromino) -> bool {
+        tetromino.get_cells().iter().all(|cell| {
+            cell.x >= 0
+                && cell.y >= 0
+                && cell.x < BOARD_WIDTH as i32
+                && cell.y < BOARD_HEIGHT as i32
+                && self.board[Self::convert_xy_to_array_position(&UCoordinate::new(
+                    cell.x as u32,
+                    cell.y as u32,
+                ))] == ' '
+        })
+    }
+    #[must_use]
     pub fn can_piece_rotate(&self, tetromino: Tetromino) -> bool {
-        let mut temp_tetromino = tetromino.clone();
+        let mut temp_tetromino = tetromino;
         temp_tetromino.rotate();
         for y in 0..TETROMINO_SIZE {
             for x in 0..TETROMINO_SIZE {
-                if temp_tetromino.get_val_at_xy(&UCoordinate::new(x, y)) == 'X' {
-                    if self.board[self.convert_xy_to_array_position(&UCoordinate::new(
+                if temp_tetromino.get_val_at_xy(&UCoordinate::new(x, y)) == 'X'
+                    && self.board[Self::convert_xy_to_array_position(&UCoordinate::new(
                         (x as i32 + temp_tetromino.get_coordinates().x) as u32,
                         (y as i32 + temp_tetromino.get_coordinates().y) as u32,
                     ))] != ' '
-                    {
-                        return false;
-                    }
+                {
+                    return false;
                 }
             }
-        }     
+        }
         true
     }
-    
-    
+
+    #[must_use]
     pub fn get_filled_lines(&self) -> Vec<u32> {
         // get the filled lines of the board - used for the flash & score
         let mut ret_filled_lines: Vec<u32> = Vec::new();
         for y in 0..BOARD_HEIGHT - 1 {
             let mut has_a_gap: bool = false;
             for x in 0..BOARD_WIDTH {
-                if self.board[self.convert_xy_to_array_position(&UCoordinate::new(x, y))] == ' ' {
+                if self.board[Self::convert_xy_to_array_position(&UCoordinate::new(x, y))] == ' ' {
                     has_a_gap = true;
                 }
             }
@@ -100,7 +153,7 @@ impl Board {
         for y in 0..TETROMINO_SIZE {
             for x in 0..TETROMINO_SIZE {
                 if tetromino.get_val_at_xy(&UCoordinate::new(x, y)) == 'X' {
-                    self.board[self.convert_xy_to_array_position(&UCoordinate::new(
+                    self.board[Self::convert_xy_to_array_position(&UCoordinate::new(
                         (tetromino.get_coordinates().x + x as i32) as u32,
                         (tetromino.get_coordinates().y + y as i32) as u32,
                     ))] = char::from_digit(tetromino.get_colour(), 10).unwrap();
@@ -115,8 +168,8 @@ impl Board {
         for line in self.get_filled_lines() {
             for y in (1..=line).rev() {
                 for x in 0..BOARD_WIDTH {
-                    self.board[self.convert_xy_to_array_position(&UCoordinate::new(x, y))] =
-                        self.board[self.convert_xy_to_array_position(&UCoordinate::new(x, y - 1))];
+                    self.board[Self::convert_xy_to_array_position(&UCoordinate::new(x, y))] =
+                        self.board[Self::convert_xy_to_array_position(&UCoordinate::new(x, y - 1))];
                 }
             }
         }
@@ -130,12 +183,177 @@ impl Board {
         if !self.get_filled_lines().is_empty() {
             for line in self.get_filled_lines() {
                 for x in 1..BOARD_WIDTH - 1 {
-                    self.board[self.convert_xy_to_array_position(&UCoordinate::new(x, line))] = '8';
+                    self.board[Self::convert_xy_to_array_position(&UCoordinate::new(x, line))] =
+                        '8';
+                }
+            }
+        }
+    }
+    // Push everything up and fill in rows of garbage along the bottom, each with a gap at
+    // hole_column. Garbage is drawn like the walls. Gives true if anything was pushed off
+    // the top, which tops the game out
+    pub fn insert_garbage_rows(&mut self, count: u32, hole_column: u32) -> bool {
+        let floor = BOARD_HEIGHT - 1;
+        let count = count.min(floor);
+        let mut topped_out = false;
+        for y in 0..count {
+            for x in 1..BOARD_WIDTH - 1 {
+                if self.board[Self::convert_xy_to_array_position(&UCoordinate::new(x, y))] != ' ' {
+                    topped_out = true;
+                }
+            }
+        }
+        for y in 0..floor {
+            for x in 1..BOARD_WIDTH - 1 {
+                self.board[Self::convert_xy_to_array_position(&UCoordinate::new(x, y))] =
+                    if y + count < floor {
+                        self.board
+                            [Self::convert_xy_to_array_position(&UCoordinate::new(x, y + count))]
+                    } else if x == hole_column {
+                        ' '
+                    } else {
+                        '9'
+                    };
+            }
+        }
+        topped_out
+    }
+    // how many rows still have garbage in them
+    #[must_use]
+    pub fn get_garbage_rows(&self) -> u32 {
+        let mut ret_garbage_rows = 0;
+        for y in 0..BOARD_HEIGHT - 1 {
+            if (1..BOARD_WIDTH - 1).any(|x| {
+                self.board[Self::convert_xy_to_array_position(&UCoordinate::new(x, y))] == '9'
+            }) {
+                ret_garbage_rows += 1;
+            }
+        }
+        ret_garbage_rows
+    }
+    // The board as one string per row, top first, using the same characters it is stored as
+    #[must_use]
+    pub fn get_rows(&self) -> Vec<String> {
+        self.board
+            .chunks(BOARD_WIDTH as usize)
+            .map(|row| row.iter().collect())
+            .collect()
+    }
+    // The opposite of get_rows. Gives None if the rows are the wrong size, use characters
+    // that can't be on a board, or leave a gap in the walls or floor - a piece could fall
+    // out through one and off the edge of the array
+    #[must_use]
+    pub fn from_rows(rows: &[String]) -> Option<Board> {
+        if rows.len() != BOARD_HEIGHT as usize {
+            return None;
+        }
+        let mut ret_board = Board::new();
+        for (y, row) in rows.iter().enumerate() {
+            let squares: Vec<char> = row.chars().collect();
+            if squares.len() != BOARD_WIDTH as usize {
+                return None;
+            }
+            let is_edge = |x: usize| {
+                x == 0 || x == BOARD_WIDTH as usize - 1 || y == BOARD_HEIGHT as usize - 1
+            };
+            if !squares.iter().enumerate().all(|(x, square)| {
+                if is_edge(x) {
+                    *square == '9'
+                } else {
+                    matches!(square, ' ' | '0'..='9')
                 }
+            }) {
+                return None;
+            }
+            for (x, square) in squares.into_iter().enumerate() {
+                ret_board.board
+                    [Self::convert_xy_to_array_position(&UCoordinate::new(x as u32, y as u32))] =
+                    square;
             }
         }
+        Some(ret_board)
+    }
+    // A fingerprint of every square on the board (FNV-1a), so two boards can be
+    // compared without printing them both out
+    #[must_use]
+    pub fn get_hash(&self) -> u64 {
+        let mut ret_hash: u64 = 0xCBF2_9CE4_8422_2325;
+        for square in self.board {
+            ret_hash ^= u64::from(square);
+            ret_hash = ret_hash.wrapping_mul(0x0100_0000_01B3);
+        }
+        ret_hash
     }
+    #[must_use]
     pub fn get_board_character_at_coordinate(&self, coordinate: &UCoordinate) -> char {
-        self.board[self.convert_xy_to_array_position(coordinate)]
+        self.board[Self::convert_xy_to_array_position(coordinate)]
+    }
+}
+// The inside of the board as text, one line per row from the top of the stack down
+// to the floor. Empty squares are _, garbage is G and anything a piece left behind is
+// that piece's letter. Rows about to be cleared come out as garbage
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let floor = BOARD_HEIGHT - 1;
+        let top = (0..floor)
+            .find(|y| {
+                (1..BOARD_WIDTH - 1).any(|x| {
+                    self.board[Self::convert_xy_to_array_position(&UCoordinate::new(x, *y))] != ' '
+                })
+            })
+            .unwrap_or(floor);
+        for y in top..floor {
+            if y > top {
+                writeln!(f)?;
+            }
+            for x in 1..BOARD_WIDTH - 1 {
+                let square =
+                    self.board[Self::convert_xy_to_array_position(&UCoordinate::new(x, y))];
+                let letter = match square.to_digit(10) {
+                    None => '_',
+                    Some(colour) => TetrominoShape::ALL
+                        .get(colour as usize)
+                        .map_or('G', |shape| shape.letter()),
+                };
+                write!(f, "{letter}")?;
+            }
+        }
+        Ok(())
+    }
+}
+// The opposite of Display. The rows sit on the floor so only the stack needs writing
+// out, and blank lines are skipped
+impl FromStr for Board {
+    type Err = BoardTextError;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let floor = BOARD_HEIGHT as usize - 1;
+        if lines.len() > floor {
+            return Err(BoardTextError::TooManyRows);
+        }
+        let mut ret_board = Board::new();
+        for (index, line) in lines.iter().enumerate() {
+            if line.chars().count() != BOARD_WIDTH as usize - 2 {
+                return Err(BoardTextError::RowWidth(index + 1));
+            }
+            let y = (floor - lines.len() + index) as u32;
+            for (x, letter) in line.chars().enumerate() {
+                let square = match letter {
+                    '_' => ' ',
+                    'G' | 'g' => '9',
+                    _ => TetrominoShape::from_letter(letter)
+                        .and_then(|shape| char::from_digit(shape.index() as u32, 10))
+                        .ok_or(BoardTextError::Square(letter))?,
+                };
+                ret_board.board
+                    [Self::convert_xy_to_array_position(&UCoordinate::new(x as u32 + 1, y))] =
+                    square;
+            }
+        }
+        Ok(ret_board)
     }
 }
@@ -1,16 +1,40 @@
 // Height & width of the game board
 pub const BOARD_HEIGHT: u32 = 18;
 pub const BOARD_WIDTH: u32 = 12;
-pub const DRAW_SCALE: f32 = 30.0; // Scales the rectangles from 1:1 to 1:30
+pub const DRAW_SCALE: f32 = 60.0; // The biggest a square is drawn, smaller if the window is
 pub const TETROMINO_SIZE: u32 = 4; // Tetrominos are squares so the size is the width & height
 pub const SPEED: f64 = 0.5; // speed at which the game runs. 
 //Needed as processors run at different speeds
+pub const SPEED_INCREASE: f64 = 0.01; // how much quicker the game gets as the score goes up
 pub const SHOW_FILLED_LINES_TIME: f64 = 0.3; // How long to show the filled lines for.
 // Processors run at different speeds so this
 // makes sure it's the same for everyone
 pub const SCORE_INCREMENT: u32 = 25; // Amount to increase score each time a block lands 
 pub const SCORE_COMPLETED_LINES_INCREMENT: u32 = 100; // Amount to increase score by when
 // a full line is achieved
+pub const LINES_PER_LEVEL: u32 = 10; // how many lines need clearing to go up a level
+pub const SPRINT_LINES: u32 = 40; // how many lines a sprint is
+pub const TIME_LIMIT: f64 = 120.0; // how long an ultra game lasts, in seconds
+pub const MARATHON_LINES: u32 = 150; // the end of level 15, when a marathon is won
+pub const MAX_START_LEVEL: u32 = 15;
+pub const DIG_ROWS: u32 = 10; // how many rows of garbage a dig starts with
+pub const MAX_DIG_ROWS: u32 = 12; // leaving room at the top for the pieces
+pub const FINESSE_PIECES: u32 = 100; // how many clean pieces finish the finesse trainer
+
+// High scores - how many are kept for each game mode & how long a name can be
+pub const HIGH_SCORE_TABLE_SIZE: usize = 10;
+pub const MAX_NAME_LENGTH: usize = 10;
+
+// Handling - how long a direction has to be held before it starts repeating (DAS)
+// and then how often it repeats (ARR). Soft drop repeats at its own rate
+pub const DELAYED_AUTO_SHIFT: f64 = 0.167;
+pub const AUTO_REPEAT_RATE: f64 = 0.033;
+pub const SOFT_DROP_SPEED: f64 = 0.05;
+
+// The game moves on in fixed steps so the same inputs always give the same game
+pub const TICKS_PER_SECOND: u32 = 60;
+pub const MAX_TICKS_PER_UPDATE: u32 = 120; // don't try to catch up more than this after a stall
+pub const NEXT_QUEUE_SIZE: usize = 5; // how many upcoming pieces are shown
 
 // Sat the starting tetromino X & Y
 pub const TETROMINO_START_X: i32 = 5;
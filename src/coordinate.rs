@@ -9,7 +9,7 @@ impl UCoordinate {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ICoordinate {
     pub x: i32,
     pub y: i32,
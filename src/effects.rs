@@ -0,0 +1,347 @@
+use serde::{Deserialize, Serialize};
+
+use crate::constants::BOARD_WIDTH;
+use crate::coordinate::ICoordinate;
+use crate::events::GameEvent;
+use crate::input::Action;
+use crate::rng::Rng;
+use crate::settings::VisualSettings;
+use crate::tetromino::TetrominoShape;
+
+// All times are in seconds, distances in board squares
+const LOCK_FLASH_TIME: f64 = 0.15;
+const PARTICLE_TIME: f64 = 0.8;
+const PARTICLES_PER_SQUARE: u32 = 3;
+const GRAVITY: f32 = 30.0;
+const SHAKE_TIME: f64 = 0.3;
+const SHAKE_PER_LINE: f32 = 0.08;
+const SHAKE_HARD_DROP: f32 = 0.06;
+
+// How the filled rows go while they wait to be cleared
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineClearEffect {
+    Flash, // just the flash colour, like the original game
+    #[default]
+    Dissolve, // the squares vanish a few at a time
+    Slide, // the row slides away into the right hand wall
+}
+impl LineClearEffect {
+    pub const ALL: [LineClearEffect; 3] = [
+        LineClearEffect::Flash,
+        LineClearEffect::Dissolve,
+        LineClearEffect::Slide,
+    ];
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            LineClearEffect::Flash => "Flash",
+            LineClearEffect::Dissolve => "Dissolve",
+            LineClearEffect::Slide => "Slide",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particle {
+    pub x: f32,
+    pub y: f32,
+    velocity_x: f32,
+    velocity_y: f32,
+    pub square: u32, // coloured like this board square
+    age: f64,
+}
+impl Particle {
+    // how far through its life it is, from 0.0 to 1.0
+    #[must_use]
+    pub fn get_progress(&self) -> f32 {
+        (self.age / PARTICLE_TIME) as f32
+    }
+}
+
+// The filled rows being animated, all cleared together
+#[derive(Clone, Debug, PartialEq)]
+pub struct RowClear {
+    pub rows: Vec<u32>,
+    pub effect: LineClearEffect,
+    age: f64,
+    duration: f64, // how long the game shows the rows for before removing them
+}
+impl RowClear {
+    #[must_use]
+    pub fn get_progress(&self) -> f32 {
+        (self.age / self.duration).min(1.0) as f32
+    }
+}
+
+// Everything drawn on top of the board that the game itself doesn't care about.
+// It only reacts to the game's events so a game plays the same with it or without it,
+// and each effect can be turned off in the settings
+#[derive(Clone, Debug)]
+pub struct Effects {
+    row_clear: Option<RowClear>,
+    lock_flash: Vec<ICoordinate>,
+    lock_flash_age: f64,
+    particles: Vec<Particle>,
+    shake: f32,
+    shake_age: f64,
+    rng: Rng,
+}
+impl Default for Effects {
+    fn default() -> Self {
+        Self {
+            row_clear: None,
+            lock_flash: Vec::new(),
+            lock_flash_age: 0.0,
+            particles: Vec::new(),
+            shake: 0.0,
+            shake_age: 0.0,
+            rng: Rng::new(Rng::random_seed()),
+        }
+    }
+}
+impl Effects {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // clear_time is how long the game leaves filled rows before removing them
+    pub fn handle_events(
+        &mut self,
+        events: &[GameEvent],
+        settings: &VisualSettings,
+        clear_time: f64,
+    ) {
+        let hard_dropped = events.contains(&GameEvent::KeyPressed(Action::HardDrop));
+        for event in events {
+            match event {
+                GameEvent::PieceLocked {
+                    shape,
+                    cells,
+                    t_spin,
+                    ..
+                } => {
+                    if settings.lock_flash {
+                        self.lock_flash.clone_from(cells);
+                        self.lock_flash_age = 0.0;
+                    }
+                    if *t_spin && settings.particles {
+                        for cell in cells {
+                            self.burst(cell.x as f32, cell.y as f32, shape.index() as u32);
+                        }
+                    }
+                    if hard_dropped && settings.screen_shake {
+                        self.start_shake(SHAKE_HARD_DROP);
+                    }
+                }
+                GameEvent::LinesCleared(rows) => {
+                    if settings.line_clear_effect != LineClearEffect::Flash && clear_time > 0.0 {
+                        self.row_clear = Some(RowClear {
+                            rows: rows.clone(),
+                            effect: settings.line_clear_effect,
+                            age: 0.0,
+                            duration: clear_time,
+                        });
+                    }
+                    if rows.len() == 4 && settings.particles {
+                        for row in rows {
+                            for x in 1..BOARD_WIDTH - 1 {
+                                // every colour of piece for a tetris
+                                let square = self.rng.gen_range(TetrominoShape::ALL.len() as u32);
+                                self.burst(x as f32, *row as f32, square);
+                            }
+                        }
+                    }
+                    if settings.screen_shake {
+                        self.start_shake(SHAKE_PER_LINE * rows.len() as f32);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    fn burst(&mut self, x: f32, y: f32, square: u32) {
+        for _ in 0..PARTICLES_PER_SQUARE {
+            let spread = |rng: &mut Rng, range: u32| rng.gen_range(range * 100) as f32 / 100.0;
+            self.particles.push(Particle {
+                x: x + 0.5,
+                y: y + 0.5,
+                velocity_x: spread(&mut self.rng, 12) - 6.0,
+                velocity_y: -4.0 - spread(&mut self.rng, 10),
+                square,
+                age: 0.0,
+            });
+        }
+    }
+    fn start_shake(&mut self, amount: f32) {
+        // a second shake before the first dies down makes it bigger rather than restarting it
+        self.shake = self.get_shake_strength() + amount;
+        self.shake_age = 0.0;
+    }
+    fn get_shake_strength(&self) -> f32 {
+        self.shake * (1.0 - (self.shake_age / SHAKE_TIME).min(1.0)) as f32
+    }
+    pub fn update(&mut self, delta_time: f64) {
+        if let Some(row_clear) = &mut self.row_clear {
+            row_clear.age += delta_time;
+            if row_clear.age >= row_clear.duration {
+                self.row_clear = None;
+            }
+        }
+        self.lock_flash_age += delta_time;
+        if self.lock_flash_age >= LOCK_FLASH_TIME {
+            self.lock_flash.clear();
+        }
+        let seconds = delta_time as f32;
+        for particle in &mut self.particles {
+            particle.age += delta_time;
+            particle.velocity_y += GRAVITY * seconds;
+            particle.x += particle.velocity_x * seconds;
+            particle.y += particle.velocity_y * seconds;
+        }
+        self.particles
+            .retain(|particle| particle.age < PARTICLE_TIME);
+        self.shake_age += delta_time;
+        if self.shake_age >= SHAKE_TIME {
+            self.shake = 0.0;
+        }
+    }
+    // drop everything, for when a new game starts
+    pub fn clear(&mut self) {
+        *self = Self {
+            rng: self.rng,
+            ..Self::default()
+        };
+    }
+    #[must_use]
+    pub fn is_idle(&self) -> bool {
+        self.row_clear.is_none()
+            && self.lock_flash.is_empty()
+            && self.particles.is_empty()
+            && self.shake <= 0.0
+    }
+    #[must_use]
+    pub fn get_row_clear(&self) -> Option<&RowClear> {
+        self.row_clear.as_ref()
+    }
+    // the squares of the piece that just locked & how bright they are, from 1.0 down to 0.0
+    #[must_use]
+    pub fn get_lock_flash(&self) -> (&[ICoordinate], f32) {
+        let brightness = 1.0 - (self.lock_flash_age / LOCK_FLASH_TIME).min(1.0);
+        (&self.lock_flash, brightness as f32)
+    }
+    #[must_use]
+    pub fn get_particles(&self) -> &[Particle] {
+        &self.particles
+    }
+    // how far to move the board this frame, in squares. Shakes back & forth, dying away
+    #[must_use]
+    pub fn get_shake_offset(&self) -> (f32, f32) {
+        let strength = self.get_shake_strength();
+        let time = self.shake_age as f32;
+        (
+            strength * (time * 71.0).sin(),
+            strength * (time * 53.0).cos(),
+        )
+    }
+}
+
+// Whether a square of a dissolving row has gone yet. Each square goes at its own
+// moment so the row breaks up rather than fading all at once
+#[must_use]
+pub fn dissolved(row: u32, column: u32, progress: f32) -> bool {
+    let mut rng = Rng::new(u64::from(row * BOARD_WIDTH + column));
+    rng.gen_range(1000) as f32 / 1000.0 < progress
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> VisualSettings {
+        VisualSettings {
+            particles: true,
+            lock_flash: true,
+            screen_shake: true,
+            line_clear_effect: LineClearEffect::Dissolve,
+            ..VisualSettings::default()
+        }
+    }
+    fn tetris() -> Vec<GameEvent> {
+        vec![
+            GameEvent::KeyPressed(Action::HardDrop),
+            GameEvent::PieceLocked {
+                shape: TetrominoShape::I,
+                finesse_faults: 0,
+                cells: (13..17).map(|y| ICoordinate::new(10, y)).collect(),
+                t_spin: false,
+            },
+            GameEvent::LinesCleared(vec![13, 14, 15, 16]),
+        ]
+    }
+    #[test]
+    fn test_tetris_starts_everything() {
+        let mut effects = Effects::new();
+        effects.handle_events(&tetris(), &settings(), 0.3);
+        assert_eq!(effects.get_row_clear().unwrap().rows, vec![13, 14, 15, 16]);
+        assert_eq!(effects.get_lock_flash().0.len(), 4);
+        let squares = 4 * (BOARD_WIDTH - 2) * PARTICLES_PER_SQUARE;
+        assert_eq!(effects.get_particles().len(), squares as usize);
+        effects.update(0.01);
+        assert!(effects.get_shake_offset() != (0.0, 0.0));
+        // particles are thrown upwards before they fall
+        assert!(
+            effects
+                .get_particles()
+                .iter()
+                .all(|particle| particle.y < 16.5)
+        );
+    }
+    #[test]
+    fn test_effects_finish() {
+        let mut effects = Effects::new();
+        effects.handle_events(&tetris(), &settings(), 0.3);
+        let progress = effects.get_row_clear().unwrap().get_progress();
+        effects.update(0.15);
+        assert!(effects.get_row_clear().unwrap().get_progress() > progress);
+        for _ in 0..60 {
+            effects.update(1.0 / 60.0);
+        }
+        assert!(effects.is_idle());
+        assert_eq!(effects.get_shake_offset(), (0.0, 0.0));
+    }
+    #[test]
+    fn test_settings_turn_effects_off() {
+        let mut effects = Effects::new();
+        let off = VisualSettings {
+            particles: false,
+            lock_flash: false,
+            screen_shake: false,
+            line_clear_effect: LineClearEffect::Flash,
+            ..VisualSettings::default()
+        };
+        effects.handle_events(&tetris(), &off, 0.3);
+        assert!(effects.is_idle());
+        // a T-spin bursts too, but only when particles are on
+        let t_spin = [GameEvent::PieceLocked {
+            shape: TetrominoShape::T,
+            finesse_faults: 0,
+            cells: vec![ICoordinate::new(4, 15)],
+            t_spin: true,
+        }];
+        effects.handle_events(&t_spin, &off, 0.3);
+        assert!(effects.get_particles().is_empty());
+        effects.handle_events(&t_spin, &settings(), 0.3);
+        assert_eq!(effects.get_particles().len(), PARTICLES_PER_SQUARE as usize);
+        assert_eq!(effects.get_particles()[0].square, 6);
+    }
+    #[test]
+    fn test_dissolve_covers_the_row() {
+        let columns = 1..BOARD_WIDTH - 1;
+        assert!(columns.clone().all(|column| !dissolved(4, column, 0.0)));
+        assert!(columns.clone().all(|column| dissolved(4, column, 1.0)));
+        let halfway = columns.filter(|column| dissolved(4, *column, 0.5)).count();
+        assert!(halfway > 0 && halfway < (BOARD_WIDTH - 2) as usize);
+    }
+}
@@ -0,0 +1,38 @@
+use crate::Direction;
+use crate::coordinate::ICoordinate;
+use crate::input::Action;
+use crate::tetromino::TetrominoShape;
+
+// Everything interesting that happens during a game. The game collects the events
+// from each update so the HUD, effects etc can react without the game knowing about them
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    PieceSpawned(TetrominoShape),
+    // a gameplay button went down: moves, drops, rotations & hold
+    KeyPressed(Action),
+    // moved by the player rather than by gravity
+    PieceMoved(Direction),
+    PieceRotated,
+    PieceHeld(TetrominoShape),
+    PieceLocked {
+        shape: TetrominoShape,
+        finesse_faults: u32,
+        cells: Vec<ICoordinate>, // the board squares it locked into
+        t_spin: bool,
+    },
+    // the finesse trainer took a piece back, it took this many presses more than it needed
+    FinesseFault {
+        shape: TetrominoShape,
+        faults: u32,
+    },
+    // opener practice: a piece locked somewhere the opener doesn't have it
+    OffTemplate(TetrominoShape),
+    // opener practice: the last piece was taken back off the board
+    PlacementUndone(TetrominoShape),
+    // the rows that were filled, top first
+    LinesCleared(Vec<u32>),
+    LevelUp(u32),
+    GameOver,
+    // the mode's goal was reached, like the 40 lines of a sprint
+    Completed,
+}
@@ -0,0 +1,517 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::constants::{BOARD_HEIGHT, BOARD_WIDTH, FINESSE_PIECES, TETROMINO_SIZE};
+use crate::coordinate::{ICoordinate, UCoordinate};
+use crate::tetromino::{Tetromino, TetrominoShape};
+
+// One step on the way from where a piece spawns to where it's dropped. Holding a
+// direction until the piece stops (DAS) counts as a single press, like a tap. Falling
+// a row is free, gravity or soft drop will get it there without a press
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FinesseInput {
+    Left,
+    Right,
+    DasLeft,
+    DasRight,
+    Rotate,
+    RotateCounterClockwise,
+    SoftDrop,
+}
+impl FinesseInput {
+    const ALL: [FinesseInput; 7] = [
+        FinesseInput::Left,
+        FinesseInput::Right,
+        FinesseInput::DasLeft,
+        FinesseInput::DasRight,
+        FinesseInput::Rotate,
+        FinesseInput::RotateCounterClockwise,
+        FinesseInput::SoftDrop,
+    ];
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            FinesseInput::Left => "Left",
+            FinesseInput::Right => "Right",
+            FinesseInput::DasLeft => "DAS left",
+            FinesseInput::DasRight => "DAS right",
+            FinesseInput::Rotate => "Rotate",
+            FinesseInput::RotateCounterClockwise => "Rotate CCW",
+            FinesseInput::SoftDrop => "Soft drop",
+        }
+    }
+    // how many button presses it takes
+    #[must_use]
+    pub fn presses(self) -> u32 {
+        u32::from(self != FinesseInput::SoftDrop)
+    }
+    // Where it takes the piece, or None if it can't go anywhere. Nothing a piece can do
+    // in the open air above the stack is any different a row lower, so up there a soft
+    // drop falls until it's near enough to the stack for it to matter
+    fn apply(self, board: &Board, shapes: &Shapes, stack_top: i32, spot: Spot) -> Option<Spot> {
+        let (step, repeat) = match self {
+            FinesseInput::Left => (-1, false),
+            FinesseInput::Right => (1, false),
+            FinesseInput::DasLeft => (-1, true),
+            FinesseInput::DasRight => (1, true),
+            FinesseInput::SoftDrop => {
+                let mut ret_spot = spot.moved(0, 1);
+                if !shapes.fits(board, ret_spot) {
+                    return None;
+                }
+                while ret_spot.is_above(stack_top) && shapes.fits(board, ret_spot.moved(0, 1)) {
+                    ret_spot = ret_spot.moved(0, 1);
+                }
+                return Some(ret_spot);
+            }
+            FinesseInput::Rotate | FinesseInput::RotateCounterClockwise => {
+                let turn = if self == FinesseInput::Rotate { 1 } else { 3 };
+                let ret_spot = Spot {
+                    rotation: (spot.rotation + turn) % 4,
+                    ..spot
+                };
+                return shapes.fits(board, ret_spot).then_some(ret_spot);
+            }
+        };
+        let mut moves = 0;
+        while shapes.fits(board, spot.moved(step * (moves + 1), 0)) {
+            moves += 1;
+            if !repeat {
+                break;
+            }
+        }
+        // a single square is a tap, not worth holding for
+        if moves == 0 || (repeat && moves < 2) {
+            return None;
+        }
+        Some(spot.moved(step * moves, 0))
+    }
+}
+
+// The fewest presses that get a piece from where it spawns to where it was dropped on
+// this board, before it locked. Sliding under an overhang takes falling first, so the
+// search goes through every spot the piece could be in, not just drops from the top.
+// Of the ways that take the fewest presses, the one falling the least is given.
+// Pieces that couldn't have got there give None
+#[must_use]
+pub fn minimal_path(board: &Board, placed: &Tetromino) -> Option<Vec<FinesseInput>> {
+    let target = sorted(&placed.get_cells());
+    let shapes = Shapes::of(placed.get_shape());
+    let start = Spot::of(&Tetromino::new(placed.get_shape()));
+    if !shapes.fits(board, start) {
+        return None;
+    }
+    let stack_top = stack_top(board);
+    // cheapest first: (presses, rows fallen) to reach each spot, and how it was reached
+    let mut queue = BinaryHeap::from([Reverse((0, 0, start))]);
+    let mut reached: HashMap<Spot, (u32, u32)> = HashMap::from([(start, (0, 0))]);
+    let mut came_from: HashMap<Spot, (Spot, FinesseInput)> = HashMap::new();
+    while let Some(Reverse((presses, fallen, spot))) = queue.pop() {
+        if reached.get(&spot) != Some(&(presses, fallen)) {
+            continue;
+        }
+        // dropped from here, it would land on the target
+        let mut landed = spot;
+        while shapes.fits(board, landed.moved(0, 1)) {
+            landed = landed.moved(0, 1);
+        }
+        if sorted(&shapes.cells(landed)) == target {
+            let mut ret_path = Vec::new();
+            let mut at = spot;
+            while let Some((from, input)) = came_from.get(&at) {
+                // falling through the open air is still a row at a time
+                let times = if *input == FinesseInput::SoftDrop {
+                    at.y - from.y
+                } else {
+                    1
+                };
+                ret_path.extend(std::iter::repeat_n(*input, times as usize));
+                at = *from;
+            }
+            ret_path.reverse();
+            return Some(ret_path);
+        }
+        for input in FinesseInput::ALL {
+            let Some(next) = input.apply(board, &shapes, stack_top, spot) else {
+                continue;
+            };
+            let cost = (
+                presses + input.presses(),
+                fallen + (next.y - spot.y) as u32,
+            );
+            if reached.get(&next).is_some_and(|best| *best <= cost) {
+                continue;
+            }
+            reached.insert(next, cost);
+            came_from.insert(next, (spot, input));
+            queue.push(Reverse((cost.0, cost.1, next)));
+        }
+    }
+    None
+}
+// the highest row with anything in it between the walls, or the floor if there's nothing
+fn stack_top(board: &Board) -> i32 {
+    (0..BOARD_HEIGHT - 1)
+        .find(|y| {
+            (1..BOARD_WIDTH - 1).any(|x| {
+                board.get_board_character_at_coordinate(&UCoordinate::new(x, *y)) != ' '
+            })
+        })
+        .unwrap_or(BOARD_HEIGHT - 1) as i32
+}
+
+// The fewest presses for a piece dropped from above as if the board was empty, without
+// searching. It's what the finesse faults in the stats are counted against outside the
+// trainer, since it's cheap enough for every piece. Holding a direction to slide into the
+// wall counts as a single press, so far columns are cheaper to reach by going to the wall
+// and tapping back. Pieces that have been tucked under something give None
+#[must_use]
+pub fn minimal_inputs(board: &Board, placed: &Tetromino) -> Option<u32> {
+    let covered = placed.get_cells().iter().any(|cell| {
+        (0..cell.y).any(|y| {
+            board.get_board_character_at_coordinate(&UCoordinate::new(cell.x as u32, y as u32))
+                != ' '
+        })
+    });
+    if covered {
+        return None;
+    }
+    let target = columns_and_shape(&placed.get_cells());
+    let mut ret_inputs: Option<u32> = None;
+    // sliding into a wall between turns leaves the piece somewhere else than turning first
+    for (turned, slide) in (0..4).flat_map(|turned| {
+        [Ordering::Equal, Ordering::Less, Ordering::Greater].map(|slide| (turned, slide))
+    }) {
+        let mut tetromino = Tetromino::new(placed.get_shape());
+        for _ in 0..turned {
+            tetromino.rotate();
+        }
+        let (furthest_left, furthest_right) = wall_shifts(&tetromino.get_cells());
+        let shift = match slide {
+            Ordering::Less => furthest_left,
+            Ordering::Greater => furthest_right,
+            Ordering::Equal => 0,
+        };
+        let coordinates = tetromino.get_coordinates();
+        tetromino.set_coordinates(ICoordinate::new(coordinates.x + shift, coordinates.y));
+        for rotations in 0..4 {
+            let inputs = turns(turned) + u32::from(slide != Ordering::Equal) + turns(rotations);
+            if let Some(moves) = moves_to(&tetromino.get_cells(), &target) {
+                let inputs = inputs + moves;
+                ret_inputs = Some(ret_inputs.map_or(inputs, |best| best.min(inputs)));
+            }
+            tetromino.rotate();
+        }
+    }
+    ret_inputs
+}
+// turning three times one way is once the other way
+fn turns(rotations: u32) -> u32 {
+    rotations.min(4 - rotations)
+}
+// how far the squares can go before they hit each wall, which is negative for the left
+fn wall_shifts(cells: &[ICoordinate]) -> (i32, i32) {
+    let left = cells.iter().map(|cell| cell.x).min().unwrap_or(0);
+    let right = cells.iter().map(|cell| cell.x).max().unwrap_or(0);
+    (1 - left, (BOARD_WIDTH as i32 - 2) - right)
+}
+// the presses to move the squares sideways over the target, if they can get there
+fn moves_to(cells: &[ICoordinate], target: &[(i32, i32)]) -> Option<u32> {
+    let (furthest_left, furthest_right) = wall_shifts(cells);
+    // a piece turned against the wall can stick out of the board
+    if furthest_left > 0 || furthest_right < 0 {
+        return None;
+    }
+    (furthest_left..=furthest_right).find_map(|shift| {
+        let shifted: Vec<ICoordinate> = cells
+            .iter()
+            .map(|cell| ICoordinate::new(cell.x + shift, cell.y))
+            .collect();
+        if columns_and_shape(&shifted) != target {
+            return None;
+        }
+        // either tap the whole way or slide into the wall and tap back
+        Some(match shift.cmp(&0) {
+            Ordering::Less => shift
+                .unsigned_abs()
+                .min(1 + (shift - furthest_left).unsigned_abs()),
+            Ordering::Greater => shift
+                .unsigned_abs()
+                .min(1 + (furthest_right - shift).unsigned_abs()),
+            Ordering::Equal => 0,
+        })
+    })
+}
+
+// The squares a piece covers with the height taken out, so pieces that only differ in
+// how far they have dropped compare the same. Symmetrical pieces in different rotations
+// that cover the same squares compare the same too
+fn columns_and_shape(cells: &[ICoordinate]) -> Vec<(i32, i32)> {
+    let top = cells.iter().map(|cell| cell.y).min().unwrap_or(0);
+    let mut ret_cells: Vec<(i32, i32)> = cells.iter().map(|cell| (cell.x, cell.y - top)).collect();
+    ret_cells.sort_unstable();
+    ret_cells
+}
+
+#[must_use]
+pub fn presses(path: &[FinesseInput]) -> u32 {
+    path.iter().map(|input| input.presses()).sum()
+}
+
+// A path written out for the player, with falling a row at a time run together
+#[must_use]
+pub fn describe(path: &[FinesseInput]) -> String {
+    let mut names: Vec<&str> = Vec::new();
+    for (index, input) in path.iter().enumerate() {
+        if *input != FinesseInput::SoftDrop || index == 0 || path[index - 1] != *input {
+            names.push(input.name());
+        }
+    }
+    if names.is_empty() {
+        return String::from("Drop");
+    }
+    names.join(", ")
+}
+
+// where a piece is and which way round, to tell the spots in the search apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Spot {
+    x: i32,
+    y: i32,
+    rotation: u8,
+}
+impl Spot {
+    fn of(tetromino: &Tetromino) -> Self {
+        Self {
+            x: tetromino.get_coordinates().x,
+            y: tetromino.get_coordinates().y,
+            rotation: tetromino.get_rotation() as u8,
+        }
+    }
+    fn moved(self, x: i32, y: i32) -> Self {
+        Self {
+            x: self.x + x,
+            y: self.y + y,
+            ..self
+        }
+    }
+    // whether the whole square the piece turns in is clear of the stack
+    fn is_above(self, stack_top: i32) -> bool {
+        self.y + TETROMINO_SIZE as i32 <= stack_top
+    }
+}
+
+// The squares a piece covers in each rotation, from where it is. They're worked out
+// once for a search, working them out from the piece at every spot is slow
+struct Shapes([Vec<ICoordinate>; 4]);
+impl Shapes {
+    fn of(shape: TetrominoShape) -> Self {
+        let mut tetromino = Tetromino::new(shape);
+        tetromino.set_coordinates(ICoordinate::new(0, 0));
+        Self(std::array::from_fn(|_| {
+            let cells = tetromino.get_cells();
+            tetromino.rotate();
+            cells
+        }))
+    }
+    fn cells(&self, spot: Spot) -> Vec<ICoordinate> {
+        self.0[spot.rotation as usize]
+            .iter()
+            .map(|cell| ICoordinate::new(cell.x + spot.x, cell.y + spot.y))
+            .collect()
+    }
+    // whether the squares are all on the board and empty
+    fn fits(&self, board: &Board, spot: Spot) -> bool {
+        self.0[spot.rotation as usize].iter().all(|cell| {
+            let (x, y) = (cell.x + spot.x, cell.y + spot.y);
+            x >= 0
+                && y >= 0
+                && x < BOARD_WIDTH as i32
+                && y < BOARD_HEIGHT as i32
+                && board.get_board_character_at_coordinate(&UCoordinate::new(x as u32, y as u32))
+                    == ' '
+        })
+    }
+}
+
+// Symmetrical pieces in different rotations can cover the same squares, so pieces
+// are compared by the squares they cover
+fn sorted(cells: &[ICoordinate]) -> Vec<(i32, i32)> {
+    let mut ret_cells: Vec<(i32, i32)> = cells.iter().map(|cell| (cell.x, cell.y)).collect();
+    ret_cells.sort_unstable();
+    ret_cells
+}
+
+// A piece that took more presses than it needed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FinesseFault {
+    pub shape: TetrominoShape,
+    pub presses: u32,
+    pub path: Vec<FinesseInput>, // what it could have been done with
+}
+
+// How the Finesse trainer is going. A piece placed with more presses than it needed
+// is a fault and gets taken back to try again, only clean pieces count
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FinesseState {
+    placed: u32,
+    faults: u32,
+    last_fault: Option<FinesseFault>,
+}
+impl FinesseState {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    #[must_use]
+    pub fn get_pieces_left(&self) -> u32 {
+        FINESSE_PIECES.saturating_sub(self.placed)
+    }
+    #[must_use]
+    pub fn get_faults(&self) -> u32 {
+        self.faults
+    }
+    // the last piece that was taken back, until a clean one goes down
+    #[must_use]
+    pub fn get_last_fault(&self) -> Option<&FinesseFault> {
+        self.last_fault.as_ref()
+    }
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.get_pieces_left() == 0
+    }
+    pub fn piece_placed(&mut self) {
+        self.placed += 1;
+        self.last_fault = None;
+    }
+    pub fn fault(&mut self, fault: FinesseFault) {
+        self.faults += 1;
+        self.last_fault = Some(fault);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // pieces off the side of the board are left where they are
+    fn drop(board: &Board, tetromino: Tetromino) -> Tetromino {
+        let mut ret_tetromino = tetromino;
+        loop {
+            let mut below = ret_tetromino;
+            below.move_down();
+            if !board.does_piece_fit(below) {
+                return ret_tetromino;
+            }
+            ret_tetromino = below;
+        }
+    }
+    fn landed(board: &Board, shape: TetrominoShape, shift: i32, rotations: u32) -> Tetromino {
+        let mut tetromino = Tetromino::new(shape);
+        for _ in 0..rotations {
+            tetromino.rotate();
+        }
+        let coordinates = tetromino.get_coordinates();
+        tetromino.set_coordinates(ICoordinate::new(coordinates.x + shift, coordinates.y));
+        drop(board, tetromino)
+    }
+    #[test]
+    fn test_dropping_straight_down_is_free() {
+        let board = Board::new();
+        let tetromino = landed(&board, TetrominoShape::T, 0, 0);
+        assert_eq!(minimal_path(&board, &tetromino), Some(vec![]));
+        assert_eq!(describe(&[]), "Drop");
+    }
+    #[test]
+    fn test_taps_and_wall_slides() {
+        let board = Board::new();
+        assert_eq!(
+            minimal_inputs(&board, &landed(&board, TetrominoShape::O, 1, 0)),
+            Some(1)
+        );
+        // the O spawns with its left edge in column 6 so the left wall is five taps away,
+        // one long press does the same job
+        let wall = landed(&board, TetrominoShape::O, -5, 0);
+        assert_eq!(
+            minimal_path(&board, &wall),
+            Some(vec![FinesseInput::DasLeft])
+        );
+        // one back from the wall is a slide and a tap
+        let path = minimal_path(&board, &landed(&board, TetrominoShape::O, -4, 0)).unwrap();
+        assert_eq!(presses(&path), 2);
+    }
+    #[test]
+    fn test_empty_board_agrees_with_the_search() {
+        let board = Board::new();
+        for shape in TetrominoShape::ALL {
+            for rotations in 0..4 {
+                for shift in -6..=6 {
+                    let placed = landed(&board, shape, shift, rotations);
+                    if !board.does_piece_fit(placed) {
+                        continue;
+                    }
+                    let path = minimal_path(&board, &placed).unwrap();
+                    assert_eq!(
+                        minimal_inputs(&board, &placed),
+                        Some(presses(&path)),
+                        "{shape:?} {rotations} {shift}"
+                    );
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_symmetrical_rotations() {
+        // the O looks the same whichever way round it is so rotating it is never needed
+        let board = Board::new();
+        let o = landed(&board, TetrominoShape::O, -1, 1);
+        assert_eq!(minimal_path(&board, &o), Some(vec![FinesseInput::Left]));
+        let t = landed(&board, TetrominoShape::T, 0, 1);
+        assert_eq!(minimal_inputs(&board, &t), Some(1));
+    }
+    #[test]
+    fn test_tucks_and_the_unreachable() {
+        // an overhang on the right with room under it for the O to slide into
+        let board: Board = "_______GGG\n__________\n__________\nGG_GGGGGGG"
+            .parse()
+            .unwrap();
+        let mut tucked = Tetromino::new(TetrominoShape::O);
+        let spawn = tucked.get_coordinates();
+        tucked.set_coordinates(ICoordinate::new(spawn.x + 3, 13));
+        assert!(board.does_piece_fit(tucked));
+        assert_eq!(drop(&board, tucked).get_cells(), tucked.get_cells());
+        let path = minimal_path(&board, &tucked).unwrap();
+        assert_eq!(presses(&path), 1);
+        assert_eq!(describe(&path), "Soft drop, DAS right");
+        // which only the search can tell
+        assert_eq!(minimal_inputs(&board, &tucked), None);
+        // a pocket that's shut in can't be reached at all
+        let board: Board = "GGGG______\n____G_____\n____G_____".parse().unwrap();
+        let mut shut_in = Tetromino::new(TetrominoShape::O);
+        shut_in.set_coordinates(ICoordinate::new(0, 14));
+        assert!(board.does_piece_fit(shut_in));
+        assert_eq!(minimal_path(&board, &shut_in), None);
+    }
+    #[test]
+    fn test_trainer_counts_clean_pieces() {
+        let mut trainer = FinesseState::new();
+        trainer.fault(FinesseFault {
+            shape: TetrominoShape::T,
+            presses: 3,
+            path: vec![FinesseInput::Rotate],
+        });
+        assert_eq!(trainer.get_faults(), 1);
+        assert_eq!(trainer.get_pieces_left(), FINESSE_PIECES);
+        assert!(trainer.get_last_fault().is_some());
+        for _ in 0..FINESSE_PIECES {
+            assert!(!trainer.is_finished());
+            trainer.piece_placed();
+        }
+        assert!(trainer.is_finished());
+        assert_eq!(trainer.get_last_fault(), None);
+    }
+}
@@ -0,0 +1,677 @@
+use std::fmt;
+
+use crate::board::Board;
+use crate::constants::{BOARD_HEIGHT, BOARD_WIDTH};
+use crate::coordinate::{ICoordinate, UCoordinate};
+use crate::tetromino::{Tetromino, TetrominoShape};
+
+// Fumen (fumen.zui.jp, and the tetris-fumen library most tools use) is how board
+// diagrams get shared. A fumen is a list of pages, each a field, an optional piece
+// and a comment, written as "v115@" then base 64 data. This reads and writes v115.
+//
+// The fumen field is 10 wide and 23 tall with a row of garbage waiting underneath,
+// numbered from the bottom. Only the bottom rows fit on our board so a field with
+// anything higher up, or a piece up there, can't be read
+const ENCODE_TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const VERSION: &str = "115@";
+const FIELD_WIDTH: i32 = 10;
+const FIELD_TOP: i32 = 23;
+const FIELD_BLOCKS: u32 = ((FIELD_TOP + 1) * FIELD_WIDTH) as u32; // with the garbage row
+const PLAYFIELD_HEIGHT: i32 = BOARD_HEIGHT as i32 - 1;
+const GRAY: u8 = 8;
+// comments are escaped the way javascript's escape() does it, then packed 4 to 5 digits
+const COMMENT_CHARACTERS: u32 = 96;
+const MAX_COMMENT_LENGTH: usize = 4095;
+// and split up with a ? every so often, the way fumen.zui.jp does it
+const FIRST_LINE_LENGTH: usize = 42;
+const LINE_LENGTH: usize = 47;
+
+#[derive(Debug, PartialEq)]
+pub enum FumenError {
+    Version,
+    Character(char),
+    Truncated,
+    Field,
+    TooTall,
+    PieceOffBoard,
+    Comment,
+}
+impl fmt::Display for FumenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FumenError::Version => write!(f, "only v115 fumens can be read"),
+            FumenError::Character(character) => write!(f, "'{character}' can't be in a fumen"),
+            FumenError::Truncated => write!(f, "the fumen stops part way through a page"),
+            FumenError::Field => write!(f, "a field can't be read"),
+            FumenError::TooTall => write!(
+                f,
+                "the field is taller than the board's {PLAYFIELD_HEIGHT} rows"
+            ),
+            FumenError::PieceOffBoard => write!(f, "a piece is off the board"),
+            FumenError::Comment => write!(f, "a comment can't be read"),
+        }
+    }
+}
+impl std::error::Error for FumenError {}
+
+// One page of a fumen. The board is what's on the page before the piece goes down,
+// locking puts the piece into the next page's board (and clears any lines it fills)
+#[derive(Clone, Debug, PartialEq)]
+pub struct FumenPage {
+    pub board: Board,
+    pub piece: Option<Tetromino>,
+    pub comment: String,
+    pub lock: bool,
+}
+impl FumenPage {
+    #[must_use]
+    pub fn new(board: Board, piece: Option<Tetromino>) -> Self {
+        Self {
+            board,
+            piece,
+            comment: String::new(),
+            lock: true,
+        }
+    }
+}
+
+// whether some text looks like a fumen, or a link to one, rather than anything else
+#[must_use]
+pub fn is_fumen(text: &str) -> bool {
+    find_data(text).is_some()
+}
+
+pub fn decode(text: &str) -> Result<Vec<FumenPage>, FumenError> {
+    let data = find_data(text).ok_or(FumenError::Version)?;
+    let mut values = Values::read(data)?;
+    let mut ret_pages = Vec::new();
+    let mut field = Field::new();
+    let mut comment = String::new();
+    let mut repeats = 0;
+    while !values.is_empty() {
+        // a page that doesn't change the field says how many after it don't either
+        if repeats > 0 {
+            repeats -= 1;
+        } else if !field.decode_changes(&mut values)? {
+            repeats = values.poll(1)?;
+        }
+        let action = Action::decode(values.poll(3)?);
+        if action.comment {
+            comment = decode_comment(&mut values)?;
+        }
+        ret_pages.push(FumenPage {
+            board: field.to_board()?,
+            piece: action.piece.map(Placement::to_tetromino).transpose()?,
+            comment: comment.clone(),
+            lock: action.lock,
+        });
+        if action.lock {
+            field.lock(&action);
+        }
+    }
+    if ret_pages.is_empty() {
+        return Err(FumenError::Truncated);
+    }
+    Ok(ret_pages)
+}
+
+#[must_use]
+pub fn encode(pages: &[FumenPage]) -> String {
+    let mut values: Vec<u32> = Vec::new();
+    let mut field = Field::new();
+    let mut comment = "";
+    // where the count of pages not changing the field is, while there's a run of them
+    let mut repeat_index: Option<usize> = None;
+    for (index, page) in pages.iter().enumerate() {
+        let current = Field::from_board(&page.board);
+        let changes = field.encode_changes(&current);
+        match (changes, repeat_index) {
+            (None, Some(repeat)) if values[repeat] < 63 => values[repeat] += 1,
+            (None, _) => {
+                values.extend(Field::UNCHANGED);
+                values.push(0);
+                repeat_index = Some(values.len() - 1);
+            }
+            (Some(changes), _) => {
+                values.extend(changes);
+                repeat_index = None;
+            }
+        }
+        let action = Action {
+            piece: page
+                .piece
+                .and_then(|piece| Placement::from_tetromino(&piece)),
+            colour: index == 0,
+            comment: page.comment != comment,
+            lock: page.lock,
+        };
+        push_values(&mut values, action.encode(), 3);
+        if action.comment {
+            encode_comment(&mut values, &page.comment);
+            comment = &page.comment;
+        }
+        field = current;
+        if action.lock {
+            field.lock(&action);
+        }
+    }
+    let data: String = values
+        .into_iter()
+        .map(|value| char::from(ENCODE_TABLE[value as usize]))
+        .collect();
+    // break it up the same way the editor does so the two can be compared
+    let mut ret_fumen = format!("v{VERSION}");
+    let (first, rest) = data.split_at(data.len().min(FIRST_LINE_LENGTH));
+    ret_fumen.push_str(first);
+    for line in rest.as_bytes().chunks(LINE_LENGTH) {
+        ret_fumen.push('?');
+        ret_fumen.push_str(std::str::from_utf8(line).unwrap_or_default());
+    }
+    ret_fumen
+}
+
+// the data after the version, from a bare fumen or anywhere in a link to one
+fn find_data(text: &str) -> Option<&str> {
+    let start = text.find(VERSION)?;
+    let prefix = text[..start].chars().last()?;
+    matches!(prefix, 'v' | 'm' | 'd' | 'D').then(|| text[start + VERSION.len()..].trim())
+}
+
+// The data as base 64 digits, least significant first
+struct Values {
+    digits: Vec<u32>,
+    position: usize,
+}
+impl Values {
+    fn read(data: &str) -> Result<Self, FumenError> {
+        let digits = data
+            .chars()
+            .filter(|character| *character != '?')
+            .map(|character| {
+                ENCODE_TABLE
+                    .iter()
+                    .position(|digit| char::from(*digit) == character)
+                    .map(|digit| digit as u32)
+                    .ok_or(FumenError::Character(character))
+            })
+            .collect::<Result<Vec<u32>, FumenError>>()?;
+        Ok(Self {
+            digits,
+            position: 0,
+        })
+    }
+    fn is_empty(&self) -> bool {
+        self.position >= self.digits.len()
+    }
+    fn poll(&mut self, count: usize) -> Result<u32, FumenError> {
+        let digits = self
+            .digits
+            .get(self.position..self.position + count)
+            .ok_or(FumenError::Truncated)?;
+        self.position += count;
+        Ok(digits
+            .iter()
+            .rev()
+            .fold(0, |value, digit| value * 64 + digit))
+    }
+}
+fn push_values(values: &mut Vec<u32>, mut value: u32, count: usize) {
+    for _ in 0..count {
+        values.push(value % 64);
+        value /= 64;
+    }
+}
+
+// The fumen field, top row first with the garbage row last, each square a fumen
+// piece number (0 for empty, 8 for gray)
+struct Field {
+    squares: [u8; FIELD_BLOCKS as usize],
+}
+impl Field {
+    // "vh", every square unchanged
+    const UNCHANGED: [u32; 2] = [47, 33];
+
+    fn new() -> Self {
+        Self {
+            squares: [0; FIELD_BLOCKS as usize],
+        }
+    }
+    // y counts up from the bottom of the field, the garbage row is -1
+    fn index(x: i32, y: i32) -> usize {
+        ((FIELD_TOP - 1 - y) * FIELD_WIDTH + x) as usize
+    }
+    fn from_board(board: &Board) -> Self {
+        let mut ret_field = Self::new();
+        for y in 0..PLAYFIELD_HEIGHT {
+            for x in 0..FIELD_WIDTH {
+                let square = board.get_board_character_at_coordinate(&UCoordinate::new(
+                    x as u32 + 1,
+                    (PLAYFIELD_HEIGHT - 1 - y) as u32,
+                ));
+                ret_field.squares[Self::index(x, y)] = match square.to_digit(10) {
+                    None => 0,
+                    Some(colour) => TetrominoShape::ALL
+                        .get(colour as usize)
+                        .map_or(GRAY, |shape| piece_number(*shape)),
+                };
+            }
+        }
+        ret_field
+    }
+    fn to_board(&self) -> Result<Board, FumenError> {
+        let row = |y: i32| (0..FIELD_WIDTH).map(move |x| self.squares[Self::index(x, y)]);
+        if (PLAYFIELD_HEIGHT..FIELD_TOP).any(|y| row(y).any(|square| square != 0)) {
+            return Err(FumenError::TooTall);
+        }
+        let rows: Vec<String> = (0..PLAYFIELD_HEIGHT)
+            .rev()
+            .map(|y| {
+                row(y)
+                    .map(|square| match shape_of(square) {
+                        Some(shape) => shape.letter(),
+                        None if square == 0 => '_',
+                        None => 'G',
+                    })
+                    .collect()
+            })
+            .collect();
+        rows.join("\n").parse().map_err(|_| FumenError::Field)
+    }
+    // Reads the squares that differ from this field, as runs of the same difference.
+    // Gives false if nothing changed
+    fn decode_changes(&mut self, values: &mut Values) -> Result<bool, FumenError> {
+        let mut index = 0;
+        let mut changed = true;
+        while index < FIELD_BLOCKS {
+            let run = values.poll(2)?;
+            let difference = run / FIELD_BLOCKS;
+            let count = run % FIELD_BLOCKS + 1;
+            if difference == 8 && count == FIELD_BLOCKS {
+                changed = false;
+            }
+            if index + count > FIELD_BLOCKS || difference > 16 {
+                return Err(FumenError::Field);
+            }
+            for square in &mut self.squares[index as usize..(index + count) as usize] {
+                let value = u32::from(*square) + difference;
+                *square = value
+                    .checked_sub(8)
+                    .filter(|value| *value <= 8)
+                    .ok_or(FumenError::Field)? as u8;
+            }
+            index += count;
+        }
+        Ok(changed)
+    }
+    // the opposite of decode_changes, None if nothing changed
+    fn encode_changes(&self, current: &Field) -> Option<Vec<u32>> {
+        let mut ret_values = Vec::new();
+        let differences = self
+            .squares
+            .iter()
+            .zip(current.squares.iter())
+            .map(|(before, after)| u32::from(*after) + 8 - u32::from(*before));
+        let mut run: Option<(u32, u32)> = None;
+        for difference in differences {
+            run = match run {
+                Some((same, count)) if same == difference => Some((same, count + 1)),
+                Some((same, count)) => {
+                    push_values(&mut ret_values, same * FIELD_BLOCKS + count - 1, 2);
+                    Some((difference, 1))
+                }
+                None => Some((difference, 1)),
+            };
+        }
+        if let Some((same, count)) = run {
+            if same == 8 && count == FIELD_BLOCKS {
+                return None;
+            }
+            push_values(&mut ret_values, same * FIELD_BLOCKS + count - 1, 2);
+        }
+        Some(ret_values)
+    }
+    // what happens to the field between pages when a page locks
+    fn lock(&mut self, action: &Action) {
+        if let Some(piece) = action.piece {
+            for (x, y) in piece.cells() {
+                if (0..FIELD_WIDTH).contains(&x) && (-1..FIELD_TOP).contains(&y) {
+                    self.squares[Self::index(x, y)] = piece.number;
+                }
+            }
+        }
+        // filled lines drop out and everything above comes down
+        let mut rows: Vec<[u8; FIELD_WIDTH as usize]> = (0..FIELD_TOP)
+            .map(|y| {
+                let start = Self::index(0, y);
+                self.squares[start..start + FIELD_WIDTH as usize]
+                    .try_into()
+                    .unwrap_or_default()
+            })
+            .filter(|row: &[u8; FIELD_WIDTH as usize]| row.contains(&0))
+            .collect();
+        rows.resize(FIELD_TOP as usize, [0; FIELD_WIDTH as usize]);
+        for (y, row) in rows.iter().enumerate() {
+            let start = Self::index(0, y as i32);
+            self.squares[start..start + FIELD_WIDTH as usize].copy_from_slice(row);
+        }
+    }
+}
+
+// fumen's own numbers for the pieces
+fn piece_number(shape: TetrominoShape) -> u8 {
+    match shape {
+        TetrominoShape::I => 1,
+        TetrominoShape::L => 2,
+        TetrominoShape::O => 3,
+        TetrominoShape::Z => 4,
+        TetrominoShape::T => 5,
+        TetrominoShape::J => 6,
+        TetrominoShape::S => 7,
+    }
+}
+fn shape_of(number: u8) -> Option<TetrominoShape> {
+    TetrominoShape::ALL
+        .into_iter()
+        .find(|shape| piece_number(*shape) == number)
+}
+
+// A piece the way fumen places it: by the square it turns around, with the rotation
+// numbered 0 for upside down, 1 for clockwise, 2 for how it spawns, 3 for anticlockwise
+#[derive(Clone, Copy)]
+struct Placement {
+    number: u8,
+    rotation: u32,
+    x: i32,
+    y: i32,
+}
+impl Placement {
+    const SPAWN: u32 = 2;
+
+    // the squares it covers, y counting up
+    fn cells(self) -> [(i32, i32); 4] {
+        let spawn = match shape_of(self.number) {
+            Some(TetrominoShape::I) => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+            Some(TetrominoShape::T) => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+            Some(TetrominoShape::O) => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            Some(TetrominoShape::L) => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+            Some(TetrominoShape::J) => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+            Some(TetrominoShape::S) => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+            Some(TetrominoShape::Z) | None => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+        };
+        spawn.map(|(x, y)| {
+            let (x, y) = match self.rotation {
+                0 => (-x, -y),
+                1 => (y, -x),
+                3 => (-y, x),
+                _ => (x, y),
+            };
+            (self.x + x, self.y + y)
+        })
+    }
+    // fumen keeps some pieces a square away from where they turn, this is how far
+    fn stored_offset(self) -> (i32, i32) {
+        match (shape_of(self.number), self.rotation) {
+            (Some(TetrominoShape::O), 3) => (1, -1),
+            (Some(TetrominoShape::O | TetrominoShape::I), 0) | (Some(TetrominoShape::Z), 3) => {
+                (1, 0)
+            }
+            (Some(TetrominoShape::O | TetrominoShape::S | TetrominoShape::Z), 2)
+            | (Some(TetrominoShape::I), 3) => (0, -1),
+            (Some(TetrominoShape::S), 1) => (-1, 0),
+            _ => (0, 0),
+        }
+    }
+    fn to_tetromino(self) -> Result<Tetromino, FumenError> {
+        let shape = shape_of(self.number).ok_or(FumenError::PieceOffBoard)?;
+        let target: Vec<ICoordinate> = self
+            .cells()
+            .iter()
+            .map(|(x, y)| ICoordinate::new(x + 1, PLAYFIELD_HEIGHT - 1 - y))
+            .collect();
+        if target.iter().any(|cell| {
+            cell.x < 1
+                || cell.x > BOARD_WIDTH as i32 - 2
+                || cell.y < 0
+                || cell.y >= PLAYFIELD_HEIGHT
+        }) {
+            return Err(FumenError::PieceOffBoard);
+        }
+        // our pieces turn inside a box rather than around a square, so find whichever
+        // of our rotations covers the same squares
+        Tetromino::covering(shape, &target).ok_or(FumenError::PieceOffBoard)
+    }
+    // the opposite of to_tetromino, None if the piece isn't on the board
+    fn from_tetromino(tetromino: &Tetromino) -> Option<Self> {
+        let mut target: Vec<(i32, i32)> = tetromino
+            .get_cells()
+            .iter()
+            .map(|cell| (cell.x - 1, PLAYFIELD_HEIGHT - 1 - cell.y))
+            .collect();
+        target.sort_unstable();
+        let number = piece_number(tetromino.get_shape());
+        // the way it spawns if that fits, the symmetrical pieces cover the same squares
+        // more than one way round
+        [Self::SPAWN, 1, 0, 3].into_iter().find_map(|rotation| {
+            let mut placement = Self {
+                number,
+                rotation,
+                x: 0,
+                y: 0,
+            };
+            let mut cells = placement.cells();
+            cells.sort_unstable();
+            placement.x = target[0].0 - cells[0].0;
+            placement.y = target[0].1 - cells[0].1;
+            let mut cells = placement.cells();
+            cells.sort_unstable();
+            (cells[..] == target[..]).then_some(placement)
+        })
+    }
+}
+
+// What a page does besides its field, packed into three digits
+struct Action {
+    piece: Option<Placement>,
+    colour: bool,
+    comment: bool,
+    lock: bool,
+}
+impl Action {
+    fn decode(mut value: u32) -> Self {
+        let mut take = |count: u32| {
+            let ret_part = value % count;
+            value /= count;
+            ret_part
+        };
+        let number = take(8) as u8;
+        let rotation = take(4);
+        let position = take(FIELD_BLOCKS) as i32;
+        // rising garbage and mirroring are for the editor, they aren't needed here
+        let _rise = take(2);
+        let _mirror = take(2);
+        let colour = take(2) == 1;
+        let comment = take(2) == 1;
+        let lock = take(2) == 0;
+        let piece = (number != 0).then(|| {
+            let mut placement = Placement {
+                number,
+                rotation,
+                x: position % FIELD_WIDTH,
+                y: FIELD_TOP - 1 - position / FIELD_WIDTH,
+            };
+            let (x, y) = placement.stored_offset();
+            placement.x += x;
+            placement.y += y;
+            placement
+        });
+        Self {
+            piece,
+            colour,
+            comment,
+            lock,
+        }
+    }
+    fn encode(&self) -> u32 {
+        let (number, rotation, position) = match self.piece {
+            Some(piece) => {
+                let (x, y) = piece.stored_offset();
+                let position = (FIELD_TOP - 1 - (piece.y - y)) * FIELD_WIDTH + piece.x - x;
+                (u32::from(piece.number), piece.rotation, position as u32)
+            }
+            None => (0, 0, 0),
+        };
+        let flags =
+            u32::from(self.colour) + 2 * u32::from(self.comment) + 4 * u32::from(!self.lock);
+        number + 8 * (rotation + 4 * (position + FIELD_BLOCKS * 4 * flags))
+    }
+}
+
+fn decode_comment(values: &mut Values) -> Result<String, FumenError> {
+    let length = values.poll(2)? as usize;
+    let mut escaped = String::new();
+    while escaped.len() < length {
+        let mut value = values.poll(5)?;
+        for _ in 0..4 {
+            if escaped.len() < length {
+                escaped.push(char::from((value % COMMENT_CHARACTERS) as u8 + b' '));
+            }
+            value /= COMMENT_CHARACTERS;
+        }
+    }
+    unescape(&escaped)
+}
+fn encode_comment(values: &mut Vec<u32>, comment: &str) {
+    let mut escaped = escape(comment);
+    escaped.truncate(MAX_COMMENT_LENGTH);
+    push_values(values, escaped.len() as u32, 2);
+    for chunk in escaped.as_bytes().chunks(4) {
+        let value = chunk.iter().rev().fold(0, |value, character| {
+            value * COMMENT_CHARACTERS + u32::from(character - b' ')
+        });
+        push_values(values, value, 5);
+    }
+}
+// javascript's escape(), which leaves letters, digits and @*_+-./ alone and writes
+// anything else as %XX or %uXXXX
+fn escape(text: &str) -> String {
+    text.encode_utf16()
+        .map(|unit| match char::from_u32(u32::from(unit)) {
+            Some(character)
+                if character.is_ascii_alphanumeric() || "@*_+-./".contains(character) =>
+            {
+                character.to_string()
+            }
+            _ if unit < 256 => format!("%{unit:02X}"),
+            _ => format!("%u{unit:04X}"),
+        })
+        .collect()
+}
+fn unescape(escaped: &str) -> Result<String, FumenError> {
+    let mut units = Vec::new();
+    let mut rest = escaped;
+    while let Some(character) = rest.chars().next() {
+        let (unit, length) = match rest.strip_prefix("%u") {
+            Some(hex) => (hex.get(..4), 6),
+            None if character == '%' => (rest.get(1..3), 3),
+            None => (None, 0),
+        };
+        match unit {
+            Some(hex) => {
+                units.push(u16::from_str_radix(hex, 16).map_err(|_| FumenError::Comment)?);
+                rest = &rest[length..];
+            }
+            None if character == '%' => return Err(FumenError::Comment),
+            None => {
+                units.push(character as u16);
+                rest = &rest[1..];
+            }
+        }
+    }
+    Ok(String::from_utf16_lossy(&units))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_fumen() {
+        let pages = decode("v115@vhAAgH").unwrap();
+        assert_eq!(pages, vec![FumenPage::new(Board::new(), None)]);
+        assert_eq!(encode(&pages), "v115@vhAAgH");
+        // from a link too
+        assert!(decode("https://fumen.zui.jp/?v115@vhAAgH").is_ok());
+        assert_eq!(decode("v110@vhAAgH"), Err(FumenError::Version));
+        assert_eq!(decode("v115@vh!AgH"), Err(FumenError::Character('!')));
+        assert_eq!(decode("v115@vhAAg"), Err(FumenError::Truncated));
+    }
+    #[test]
+    fn test_field() {
+        // 22 empty rows, then gray and L along the bottom and an empty garbage row
+        let pages = decode("v115@bhD8hlD8JeAgH").unwrap();
+        assert_eq!(pages[0].board.to_string(), "GGGGLLGGGG");
+        assert_eq!(encode(&pages), "v115@bhD8hlD8JeAgH");
+    }
+    #[test]
+    fn test_pieces_round_trip() {
+        let board: Board = "___TT_____\nGGGG_GGGGG".parse().unwrap();
+        for shape in TetrominoShape::ALL {
+            let mut tetromino = Tetromino::new(shape);
+            for _ in 0..4 {
+                tetromino.rotate();
+                let page = FumenPage::new(board.clone(), Some(tetromino));
+                let pages = decode(&encode(&[page])).unwrap();
+                let decoded = pages[0].piece.unwrap();
+                assert_eq!(decoded.get_shape(), shape);
+                assert_eq!(decoded.get_cells(), tetromino.get_cells());
+                assert_eq!(pages[0].board, board);
+            }
+        }
+    }
+    #[test]
+    fn test_pages_and_comments() {
+        let board: Board = "GGGGG_GGGG".parse().unwrap();
+        let mut i = Tetromino::new(TetrominoShape::I);
+        i.set_coordinates(ICoordinate::new(4, 13));
+        let mut pages = vec![FumenPage::new(board, Some(i))];
+        pages[0].comment = String::from("Put the I in the gap — 100%");
+        // the I locks and clears the line, so the next page's field is already there,
+        // as is the one after that
+        let cleared: Board = "_____I____\n_____I____\n_____I____".parse().unwrap();
+        for _ in 0..2 {
+            let mut page = FumenPage::new(cleared.clone(), None);
+            page.comment.clone_from(&pages[0].comment);
+            page.lock = false;
+            pages.push(page);
+        }
+        pages.push(FumenPage::new(Board::new(), None));
+        let fumen = encode(&pages);
+        assert_eq!(fumen.matches("vh").count(), 1);
+        let decoded = decode(&fumen).unwrap();
+        assert_eq!(decoded, pages);
+        assert_eq!(encode(&decoded), fumen);
+    }
+    #[test]
+    fn test_escaping_comments() {
+        assert_eq!(escape("a b"), "a%20b");
+        assert_eq!(unescape(&escape("a b+é€")).unwrap(), "a b+é€");
+        assert_eq!(unescape("100%"), Err(FumenError::Comment));
+    }
+    #[test]
+    fn test_too_tall() {
+        let mut board = String::from("G_________\n");
+        board.push_str(&"__________\n".repeat(PLAYFIELD_HEIGHT as usize - 1));
+        let fumen = encode(&[FumenPage::new(board.parse().unwrap(), None)]);
+        assert!(decode(&fumen).is_ok());
+        // a row higher than the board goes
+        let mut values = Vec::new();
+        push_values(&mut values, 8 * FIELD_BLOCKS + 9, 2);
+        push_values(&mut values, 16 * FIELD_BLOCKS, 2);
+        push_values(&mut values, 8 * FIELD_BLOCKS + FIELD_BLOCKS - 12, 2);
+        let data: String = values
+            .into_iter()
+            .map(|value| char::from(ENCODE_TABLE[value as usize]))
+            .collect();
+        assert_eq!(decode(&format!("v115@{data}AgH")), Err(FumenError::TooTall));
+    }
+}
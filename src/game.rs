@@ -0,0 +1,1570 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Direction;
+use crate::board::Board;
+use crate::constants::{
+    BOARD_HEIGHT, BOARD_WIDTH, LINES_PER_LEVEL, MARATHON_LINES, MAX_TICKS_PER_UPDATE,
+    NEXT_QUEUE_SIZE, SPRINT_LINES, TICKS_PER_SECOND,
+};
+use crate::coordinate::{ICoordinate, UCoordinate};
+use crate::events::GameEvent;
+use crate::finesse::{self, FinesseFault, FinesseState};
+use crate::fumen::{self, FumenPage};
+use crate::gamepad::DEFAULT_DEADZONE;
+use crate::handling::PieceHandling;
+use crate::input::{Action, ActionState};
+use crate::master::{self, MasterState, Wait};
+use crate::opener::{Opener, OpenerState};
+use crate::position::Position;
+use crate::puzzle::{Puzzle, PuzzleState};
+use crate::replay::{
+    Replay, ReplayInput, ReplayResult, ReplaySetup, actions_from_bits, actions_to_bits,
+};
+use crate::rng::{Bag, Rng};
+use crate::save::{SAVE_VERSION, SaveError, SavedGame, SavedTetromino};
+use crate::settings::{HandlingSettings, RulesSettings, Settings};
+use crate::stats::Statistics;
+use crate::tetromino::{Tetromino, TetrominoShape};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    Classic,
+    Sprint,
+    Ultra,
+    Marathon,
+    Endless,
+    Dig,
+    Master,
+    Puzzle,
+    Finesse,
+    Opener,
+}
+// How a mode's high score table is ordered
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ranking {
+    Score, // highest first
+    Time,  // quickest first, and only games that reached the goal
+}
+impl GameMode {
+    pub const ALL: [GameMode; 10] = [
+        GameMode::Classic,
+        GameMode::Sprint,
+        GameMode::Ultra,
+        GameMode::Marathon,
+        GameMode::Endless,
+        GameMode::Dig,
+        GameMode::Master,
+        GameMode::Puzzle,
+        GameMode::Finesse,
+        GameMode::Opener,
+    ];
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Classic => "Classic",
+            GameMode::Sprint => "Sprint",
+            GameMode::Ultra => "Ultra",
+            GameMode::Marathon => "Marathon",
+            GameMode::Endless => "Endless",
+            GameMode::Dig => "Dig",
+            GameMode::Master => "Master",
+            GameMode::Puzzle => "Puzzle",
+            GameMode::Finesse => "Finesse",
+            GameMode::Opener => "Opener",
+        }
+    }
+    // the number a mode is saved as in replays, which must never change
+    #[must_use]
+    pub fn id(self) -> u8 {
+        match self {
+            GameMode::Classic => 0,
+            GameMode::Sprint => 1,
+            GameMode::Ultra => 2,
+            GameMode::Marathon => 3,
+            GameMode::Endless => 4,
+            GameMode::Dig => 5,
+            GameMode::Master => 6,
+            GameMode::Puzzle => 7,
+            GameMode::Finesse => 8,
+            GameMode::Opener => 9,
+        }
+    }
+    #[must_use]
+    pub fn from_id(id: u8) -> Option<GameMode> {
+        GameMode::ALL.into_iter().find(|mode| mode.id() == id)
+    }
+    #[must_use]
+    pub fn description(self) -> &'static str {
+        match self {
+            GameMode::Classic => "Play until the stack reaches the top",
+            GameMode::Sprint => "Clear 40 lines as fast as you can",
+            GameMode::Ultra => "Score as much as you can before the time runs out",
+            GameMode::Marathon => "Get through 15 levels, faster every 10 lines",
+            GameMode::Endless => "Marathon with no end",
+            GameMode::Dig => "Dig through the garbage as fast as you can",
+            GameMode::Master => "Reach level 999 at 20G for the best grade",
+            GameMode::Puzzle => "Reach the goal with the pieces you are given",
+            GameMode::Finesse => "Place 100 pieces with as few presses as each one takes",
+            GameMode::Opener => "Practise building an opener, piece by piece",
+        }
+    }
+    #[must_use]
+    pub fn ranking(self) -> Ranking {
+        match self {
+            GameMode::Classic
+            | GameMode::Ultra
+            | GameMode::Marathon
+            | GameMode::Endless
+            | GameMode::Master
+            | GameMode::Puzzle => Ranking::Score,
+            GameMode::Sprint | GameMode::Dig | GameMode::Finesse | GameMode::Opener => {
+                Ranking::Time
+            }
+        }
+    }
+    // what the results screen says when the goal is reached
+    #[must_use]
+    pub fn complete_title(self) -> &'static str {
+        match self {
+            GameMode::Marathon => "Victory!",
+            GameMode::Ultra => "Time's up!",
+            GameMode::Dig => "Dug out!",
+            GameMode::Master => "Level 999!",
+            GameMode::Puzzle => "Solved!",
+            GameMode::Finesse => "Clean!",
+            GameMode::Opener => "Built!",
+            GameMode::Classic | GameMode::Sprint | GameMode::Endless => "Complete!",
+        }
+    }
+    // a puzzle is solved or it isn't, there's nothing to rank, and an opener is practice
+    #[must_use]
+    pub fn has_high_scores(self) -> bool {
+        !matches!(self, GameMode::Puzzle | GameMode::Opener)
+    }
+    // the modes with a high score table, in the order they're shown
+    #[must_use]
+    pub fn with_high_scores() -> Vec<GameMode> {
+        GameMode::ALL
+            .into_iter()
+            .filter(|mode| mode.has_high_scores())
+            .collect()
+    }
+    // Marathon rules: the game starts at the chosen level and the level sets the speed,
+    // rather than the speed going up with the score
+    #[must_use]
+    pub fn has_levels(self) -> bool {
+        matches!(self, GameMode::Marathon | GameMode::Endless)
+    }
+    // how many lines finish the game, for modes that have a line goal
+    #[must_use]
+    pub fn goal_lines(self) -> Option<u32> {
+        match self {
+            GameMode::Classic
+            | GameMode::Ultra
+            | GameMode::Endless
+            | GameMode::Dig
+            | GameMode::Master
+            | GameMode::Puzzle
+            | GameMode::Finesse
+            | GameMode::Opener => None,
+            GameMode::Sprint => Some(SPRINT_LINES),
+            GameMode::Marathon => Some(MARATHON_LINES),
+        }
+    }
+}
+
+// Everything that changes how a game plays out. It is fixed when the game starts,
+// so changing the settings part way through a game only affects the next one
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Ruleset {
+    pub rules: RulesSettings,
+    pub handling: HandlingSettings,
+}
+impl Ruleset {
+    #[must_use]
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            rules: settings.rules.clone(),
+            // the gamepad deadzone doesn't change how a game plays out so it isn't kept
+            handling: HandlingSettings {
+                gamepad_deadzone: DEFAULT_DEADZONE,
+                ..settings.handling.clone()
+            },
+        }
+    }
+}
+
+// How a game finished
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ending {
+    ToppedOut, // there was no room for the next piece
+    Completed, // the mode's goal was reached
+}
+
+// How the game was just before a piece locked in opener practice, to go back to
+#[derive(Clone, Debug)]
+struct Undo {
+    board: Board,
+    bag: Bag,
+    shape: TetrominoShape,
+    held: Option<TetrominoShape>,
+    opener: OpenerState,
+    hold_used: bool,
+    score: u32,
+    lines: u32,
+    speed: f64,
+    filled_lines_shown_at: Option<f64>,
+    statistics: Statistics,
+}
+
+// Turns real frame times into a whole number of fixed length ticks,
+// carrying whatever is left over into the next frame
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TickClock {
+    ticks_owed: f64,
+}
+impl TickClock {
+    pub fn advance(&mut self, delta_time: f64) -> u32 {
+        self.ticks_owed += delta_time * f64::from(TICKS_PER_SECOND);
+        // the small allowance stops a frame of exactly one tick rounding down to nothing
+        let ticks = (self.ticks_owed + 1e-9).floor();
+        self.ticks_owed -= ticks;
+        if ticks > f64::from(MAX_TICKS_PER_UPDATE) {
+            self.ticks_owed = 0.0;
+            return MAX_TICKS_PER_UPDATE;
+        }
+        ticks as u32
+    }
+}
+
+// A single game from the first piece to game over.
+// The game only moves on in fixed ticks and its pieces come from its own seed,
+// so the same seed, ruleset & inputs on each tick always give the same game.
+// Pausing is just not calling update
+pub struct Game {
+    mode: GameMode,
+    ruleset: Ruleset,
+    board: Board,
+    bag: Bag,
+    current_tetromino: Tetromino,
+    held: Option<TetrominoShape>,
+    hold_used: bool, // only one hold is allowed per piece
+    score: u32,
+    lines: u32,
+    speed: f64,
+    ticks: u64,
+    last_update: f64,
+    filled_lines_shown_at: Option<f64>,
+    handling: PieceHandling,
+    ending: Option<Ending>,        // set once the game is over
+    master: Option<MasterState>,   // the arcade timing & levels, only in Master mode
+    puzzle: Option<PuzzleState>,   // the goal & the pieces left, only in Puzzle mode
+    finesse: Option<FinesseState>, // the pieces left & the last fault, only in Finesse mode
+    opener: Option<OpenerState>,   // the template & what's placed, only in Opener mode
+    undo: Vec<Undo>, // the game before each piece locked, only in Opener mode, never saved
+    piece_inputs: u32, // moves & rotations pressed for the current piece, for finesse
+    rotated_last: bool, // the piece's last move was a rotation, for spotting T-spins
+    events: Vec<GameEvent>,
+    statistics: Statistics,
+    clock: TickClock,
+    unticked_presses: ActionState,
+    recording: Replay,
+}
+impl Game {
+    #[must_use]
+    pub fn new(mode: GameMode, ruleset: Ruleset, seed: u64) -> Self {
+        let mut bag = Bag::new(seed);
+        let current_tetromino = Tetromino::new(bag.next_shape());
+        let mut ret_game = Self {
+            mode,
+            board: Board::new(),
+            bag,
+            current_tetromino,
+            held: None,
+            hold_used: false,
+            score: 0,
+            lines: 0,
+            speed: if mode.has_levels() {
+                gravity(ruleset.rules.start_level)
+            } else {
+                ruleset.rules.speed
+            },
+            ticks: 0,
+            last_update: 0.0,
+            filled_lines_shown_at: None,
+            handling: PieceHandling::new(&ruleset.handling),
+            ending: None,
+            master: (mode == GameMode::Master).then(MasterState::new),
+            puzzle: None,
+            finesse: (mode == GameMode::Finesse).then(FinesseState::new),
+            opener: None,
+            undo: Vec::new(),
+            piece_inputs: 0,
+            rotated_last: false,
+            events: Vec::new(),
+            statistics: Statistics::new(),
+            clock: TickClock::default(),
+            unticked_presses: ActionState::new(),
+            recording: Replay::new(mode, seed, ruleset.clone()),
+            ruleset,
+        };
+        if mode == GameMode::Dig {
+            for row in 0..ret_game.ruleset.rules.dig_rows {
+                ret_game.add_garbage_row(u64::from(row));
+            }
+        }
+        ret_game.emit(GameEvent::PieceSpawned(current_tetromino.get_shape()));
+        ret_game
+    }
+    // A game on the puzzle's board with only the puzzle's pieces, in its order
+    #[must_use]
+    pub fn puzzle(puzzle: &Puzzle, ruleset: Ruleset) -> Self {
+        let mut state = PuzzleState::new(puzzle);
+        let first_shape = state.next_shape().unwrap_or(TetrominoShape::I);
+        let mut ret_game = Self::new(GameMode::Puzzle, ruleset, 0);
+        ret_game.board = puzzle.board.clone();
+        ret_game.puzzle = Some(state);
+        ret_game.recording.setup = Some(ReplaySetup::Puzzle(Box::new(puzzle.clone())));
+        ret_game.events.clear();
+        ret_game.statistics = Statistics::new();
+        ret_game.spawn_tetromino(first_shape);
+        ret_game
+    }
+    // A game dealing the opener's pieces first, in its order, then the usual bags
+    #[must_use]
+    pub fn opener(opener: &Opener, ruleset: Ruleset, seed: u64) -> Self {
+        let mut bag = Bag::starting_with(seed, &opener.order);
+        let first_shape = bag.next_shape();
+        let mut ret_game = Self::new(GameMode::Opener, ruleset, seed);
+        ret_game.bag = bag;
+        ret_game.opener = Some(OpenerState::new(opener));
+        ret_game.recording.setup = Some(ReplaySetup::Opener(opener.clone()));
+        ret_game.events.clear();
+        ret_game.statistics = Statistics::new();
+        ret_game.spawn_tetromino(first_shape);
+        ret_game
+    }
+    #[must_use]
+    pub fn get_mode(&self) -> GameMode {
+        self.mode
+    }
+    #[must_use]
+    pub fn get_ruleset(&self) -> &Ruleset {
+        &self.ruleset
+    }
+    #[must_use]
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+    #[must_use]
+    pub fn get_current_tetromino(&self) -> Tetromino {
+        self.current_tetromino
+    }
+    // where the current piece would land if it was hard dropped
+    #[must_use]
+    pub fn get_ghost(&self) -> Tetromino {
+        let mut ret_ghost = self.current_tetromino;
+        while self.board.can_piece_move(ret_ghost, Direction::Down) {
+            ret_ghost.move_down();
+        }
+        ret_ghost
+    }
+    #[must_use]
+    pub fn get_held(&self) -> Option<TetrominoShape> {
+        self.held
+    }
+    #[must_use]
+    pub fn get_next(&self, count: usize) -> Vec<TetrominoShape> {
+        match &self.puzzle {
+            Some(puzzle) => puzzle.peek(count),
+            None => self.bag.peek(count),
+        }
+    }
+    // where the game is up to, as it would be written out for a bug report
+    #[must_use]
+    pub fn get_position(&self) -> Position {
+        Position {
+            board: self.board.clone(),
+            current: (!self.is_between_pieces() && !self.is_game_over())
+                .then(|| self.current_tetromino.get_shape()),
+            hold: self.held,
+            queue: self.get_next(NEXT_QUEUE_SIZE),
+        }
+    }
+    // the board and the piece in play as a fumen, to look at in other tools
+    #[must_use]
+    pub fn get_fumen(&self) -> String {
+        let piece =
+            (!self.is_between_pieces() && !self.is_game_over()).then_some(self.current_tetromino);
+        fumen::encode(&[FumenPage::new(self.board.clone(), piece)])
+    }
+    #[must_use]
+    pub fn get_score(&self) -> u32 {
+        self.score
+    }
+    #[must_use]
+    pub fn get_lines(&self) -> u32 {
+        self.lines
+    }
+    #[must_use]
+    pub fn get_level(&self) -> u32 {
+        if let Some(master) = &self.master {
+            return master.get_level();
+        }
+        // modes with levels go up every 10 lines from the level they started at
+        let start = if self.mode.has_levels() {
+            self.ruleset.rules.start_level
+        } else {
+            1
+        };
+        start + self.lines / LINES_PER_LEVEL
+    }
+    #[must_use]
+    pub fn get_ticks(&self) -> u64 {
+        self.ticks
+    }
+    // how long the game has been played for in seconds, not counting time paused
+    #[must_use]
+    pub fn get_time(&self) -> f64 {
+        self.ticks as f64 / f64::from(TICKS_PER_SECOND)
+    }
+    // lines still to clear, for modes with a line goal
+    #[must_use]
+    pub fn get_lines_left(&self) -> Option<u32> {
+        // digging is done when the garbage is, however much has come up since
+        if self.mode == GameMode::Dig {
+            return Some(self.board.get_garbage_rows());
+        }
+        if let Some(puzzle) = &self.puzzle {
+            return puzzle.get_lines_left();
+        }
+        let mut goal = self.mode.goal_lines()?;
+        // a marathon ends with level 15 wherever it started
+        if self.mode.has_levels() {
+            goal = goal.saturating_sub((self.ruleset.rules.start_level - 1) * LINES_PER_LEVEL);
+        }
+        Some(goal.saturating_sub(self.lines))
+    }
+    #[must_use]
+    pub fn get_master(&self) -> Option<&MasterState> {
+        self.master.as_ref()
+    }
+    #[must_use]
+    pub fn get_puzzle(&self) -> Option<&PuzzleState> {
+        self.puzzle.as_ref()
+    }
+    #[must_use]
+    pub fn get_finesse(&self) -> Option<&FinesseState> {
+        self.finesse.as_ref()
+    }
+    #[must_use]
+    pub fn get_opener(&self) -> Option<&OpenerState> {
+        self.opener.as_ref()
+    }
+    // Master mode has a wait after each piece locks, when there's no piece in play
+    #[must_use]
+    pub fn is_between_pieces(&self) -> bool {
+        self.master.as_ref().is_some_and(MasterState::is_waiting)
+    }
+    // seconds still to play, for modes against the clock
+    #[must_use]
+    pub fn get_time_left(&self) -> Option<f64> {
+        match self.mode {
+            GameMode::Ultra => Some((self.ruleset.rules.time_limit - self.get_time()).max(0.0)),
+            GameMode::Classic
+            | GameMode::Sprint
+            | GameMode::Marathon
+            | GameMode::Endless
+            | GameMode::Dig
+            | GameMode::Master
+            | GameMode::Puzzle
+            | GameMode::Finesse
+            | GameMode::Opener => None,
+        }
+    }
+    #[must_use]
+    pub fn is_game_over(&self) -> bool {
+        self.ending.is_some()
+    }
+    // finished by reaching the mode's goal rather than topping out
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.ending == Some(Ending::Completed)
+    }
+    // what has happened since the events were last cleared, which update does every frame
+    #[must_use]
+    pub fn get_events(&self) -> &[GameEvent] {
+        &self.events
+    }
+    pub fn clear_events(&mut self) {
+        self.events.clear();
+    }
+    #[must_use]
+    pub fn get_statistics(&self) -> &Statistics {
+        &self.statistics
+    }
+    // the replay of the game so far, with the results as they stand now
+    #[must_use]
+    pub fn get_replay(&self) -> Replay {
+        let mut ret_replay = self.recording.clone();
+        ret_replay.result = ReplayResult {
+            ticks: self.ticks,
+            score: self.score,
+            lines: self.lines,
+            level: self.get_level(),
+            board_hash: self.board.get_hash(),
+        };
+        ret_replay
+    }
+
+    // Everything needed to carry on with this game another time
+    #[must_use]
+    pub fn to_saved(&self) -> SavedGame {
+        let tetromino = self.current_tetromino;
+        SavedGame {
+            version: SAVE_VERSION,
+            mode: self.mode,
+            seed: self.recording.seed,
+            score: self.score,
+            lines: self.lines,
+            speed: self.speed,
+            ticks: self.ticks,
+            last_update: self.last_update,
+            filled_lines_shown_at: self.filled_lines_shown_at,
+            held: self.held,
+            hold_used: self.hold_used,
+            piece_inputs: self.piece_inputs,
+            rotated_last: self.rotated_last,
+            piece_handling: self.handling,
+            queue: self.bag.get_queue(),
+            rng_state: self.bag.get_rng_state(),
+            board: self.board.get_rows(),
+            inputs: self
+                .recording
+                .inputs
+                .iter()
+                .map(|input| (input.tick, actions_to_bits(&input.actions)))
+                .collect(),
+            current: SavedTetromino {
+                shape: tetromino.get_shape(),
+                rotation: tetromino.get_rotation(),
+                x: tetromino.get_coordinates().x,
+                y: tetromino.get_coordinates().y,
+            },
+            rules: self.ruleset.rules.clone(),
+            handling: self.ruleset.handling.clone(),
+            statistics: self.statistics.clone(),
+            master: self.master.clone(),
+            puzzle: self.puzzle.clone(),
+            finesse: self.finesse.clone(),
+            opener: self.opener.clone(),
+            setup: self.recording.setup.clone(),
+        }
+    }
+
+    pub fn from_saved(saved: &SavedGame) -> Result<Game, SaveError> {
+        let board = Board::from_rows(&saved.board).ok_or(SaveError::Invalid("board"))?;
+        let mut current_tetromino = Tetromino::new(saved.current.shape);
+        current_tetromino.set_rotation(saved.current.rotation);
+        current_tetromino.set_coordinates(ICoordinate::new(saved.current.x, saved.current.y));
+        if !board.does_piece_fit(current_tetromino) {
+            return Err(SaveError::Invalid("current piece"));
+        }
+        let ruleset = Ruleset {
+            rules: saved.rules.clone(),
+            handling: saved.handling.clone(),
+        };
+        let mut recording = Replay::new(saved.mode, saved.seed, ruleset.clone());
+        recording.setup.clone_from(&saved.setup);
+        recording.inputs = saved
+            .inputs
+            .iter()
+            .map(|(tick, bits)| ReplayInput {
+                tick: *tick,
+                actions: actions_from_bits(*bits),
+            })
+            .collect();
+        Ok(Self {
+            mode: saved.mode,
+            board,
+            bag: Bag::from_state(saved.rng_state, &saved.queue),
+            current_tetromino,
+            held: saved.held,
+            hold_used: saved.hold_used,
+            score: saved.score,
+            lines: saved.lines,
+            speed: saved.speed,
+            ticks: saved.ticks,
+            last_update: saved.last_update,
+            filled_lines_shown_at: saved.filled_lines_shown_at,
+            handling: {
+                let mut handling = saved.piece_handling;
+                handling.apply_settings(&ruleset.handling);
+                handling
+            },
+            ending: None,
+            master: saved.master.clone(),
+            puzzle: saved.puzzle.clone(),
+            finesse: saved.finesse.clone(),
+            opener: saved.opener.clone(),
+            // the undo history isn't saved, so the pieces placed before the game was saved
+            // stay put
+            undo: Vec::new(),
+            piece_inputs: saved.piece_inputs,
+            rotated_last: saved.rotated_last,
+            events: Vec::new(),
+            statistics: saved.statistics.clone(),
+            clock: TickClock::default(),
+            unticked_presses: ActionState::new(),
+            recording,
+            ruleset,
+        })
+    }
+
+    fn emit(&mut self, event: GameEvent) {
+        self.statistics.record(&event);
+        self.events.push(event);
+    }
+
+    // Run however many ticks this frame's time adds up to
+    pub fn update(&mut self, actions: &ActionState, delta_time: f64) {
+        self.events.clear();
+        // a quick tap that is over before the next tick still needs to reach the game
+        let actions = actions.union(&self.unticked_presses);
+        let ticks = self.clock.advance(delta_time);
+        if ticks == 0 {
+            self.unticked_presses = actions.only_presses();
+            return;
+        }
+        self.unticked_presses = ActionState::new();
+        self.tick(&actions);
+        for _ in 1..ticks {
+            self.tick(&actions.without_presses());
+        }
+    }
+
+    // Move the game on by exactly one tick
+    pub fn tick(&mut self, actions: &ActionState) {
+        if self.is_game_over() {
+            return;
+        }
+        self.recording.record(self.ticks, actions);
+        self.ticks += 1;
+        let time = self.get_time();
+        self.statistics.set_time(time);
+        for action in [
+            Action::MoveLeft,
+            Action::MoveRight,
+            Action::SoftDrop,
+            Action::HardDrop,
+            Action::Rotate,
+            Action::RotateCounterClockwise,
+            Action::Hold,
+        ] {
+            if actions.is_pressed(action) {
+                if matches!(
+                    action,
+                    Action::MoveLeft
+                        | Action::MoveRight
+                        | Action::Rotate
+                        | Action::RotateCounterClockwise
+                ) {
+                    self.piece_inputs += 1;
+                }
+                self.emit(GameEvent::KeyPressed(action));
+            }
+        }
+        if self.wait_between_pieces() {
+            return;
+        }
+        if actions.is_pressed(Action::Undo) {
+            self.undo_placement();
+        }
+        if actions.is_pressed(Action::Hold) {
+            self.hold_tetromino();
+        }
+        for (direction, moves) in self
+            .handling
+            .update(actions, 1.0 / f64::from(TICKS_PER_SECOND))
+        {
+            self.shift_tetromino(direction, moves);
+        }
+        if actions.is_pressed(Action::Rotate) {
+            let mut rotated = self.current_tetromino;
+            rotated.rotate();
+            self.try_rotation(rotated);
+        }
+        if actions.is_pressed(Action::RotateCounterClockwise) {
+            let mut rotated = self.current_tetromino;
+            rotated.rotate_counter_clockwise();
+            self.try_rotation(rotated);
+        }
+        if actions.is_pressed(Action::HardDrop) {
+            self.shift_tetromino(Direction::Down, BOARD_HEIGHT);
+            self.lock_tetromino();
+            self.last_update = time;
+        }
+
+        if let Some(shown_at) = self.filled_lines_shown_at
+            && time - shown_at > self.ruleset.rules.show_filled_lines_time
+        {
+            self.board.remove_filled_lines();
+            self.filled_lines_shown_at = None;
+        }
+
+        if self.master.is_some() {
+            self.fall_at_master_speed(actions);
+        } else if !self.is_game_over() && time - self.last_update > self.speed {
+            self.last_update = time;
+            if self
+                .board
+                .can_piece_move(self.current_tetromino, Direction::Down)
+            {
+                self.current_tetromino.move_down();
+                self.rotated_last = false;
+            } else {
+                self.lock_tetromino();
+            }
+        }
+
+        let rising_ticks = (self.ruleset.rules.rising_garbage * f64::from(TICKS_PER_SECOND)) as u64;
+        if self.mode == GameMode::Dig
+            && rising_ticks > 0
+            && self.ticks.is_multiple_of(rising_ticks)
+            && !self.is_game_over()
+        {
+            let row = u64::from(self.ruleset.rules.dig_rows) + self.ticks / rising_ticks - 1;
+            self.add_garbage_row(row);
+        }
+
+        if !self.is_game_over() && self.get_time_left().is_some_and(|left| left <= 0.0) {
+            self.ending = Some(Ending::Completed);
+            self.emit(GameEvent::Completed);
+        }
+    }
+
+    // Master mode's wait after a piece locks, true while it's still going
+    fn wait_between_pieces(&mut self) -> bool {
+        let Some(wait) = self
+            .master
+            .as_mut()
+            .filter(|master| master.is_waiting())
+            .map(MasterState::wait)
+        else {
+            return false;
+        };
+        match wait {
+            Wait::Waiting => {}
+            Wait::ClearLines => self.board.remove_filled_lines(),
+            Wait::Spawn => self.spawn_next_tetromino(),
+        }
+        true
+    }
+
+    // Master mode falls a number of rows each tick rather than a row every so often,
+    // and locks once the piece has been on the ground for the lock delay
+    fn fall_at_master_speed(&mut self, actions: &ActionState) {
+        if self.is_game_over() || self.is_between_pieces() {
+            return;
+        }
+        let Some(master) = self.master.as_mut() else {
+            return;
+        };
+        for _ in 0..master.fall() {
+            if !self
+                .board
+                .can_piece_move(self.current_tetromino, Direction::Down)
+            {
+                break;
+            }
+            self.current_tetromino.move_down();
+            self.rotated_last = false;
+            master.reset_lock();
+        }
+        // holding soft drop on the ground locks straight away
+        let locking = !self
+            .board
+            .can_piece_move(self.current_tetromino, Direction::Down)
+            && (master.on_ground() || actions.is_down(Action::SoftDrop));
+        if locking {
+            self.lock_tetromino();
+        }
+    }
+
+    // The row'th row of garbage this game has had. Pushing the stack into the
+    // current piece or off the top ends the game
+    fn add_garbage_row(&mut self, row: u64) {
+        let hole_column = garbage_hole(self.recording.seed, row);
+        if self.board.insert_garbage_rows(1, hole_column)
+            || !self.board.does_piece_fit(self.current_tetromino)
+        {
+            self.ending = Some(Ending::ToppedOut);
+            self.emit(GameEvent::GameOver);
+        }
+    }
+
+    fn try_rotation(&mut self, rotated: Tetromino) {
+        if self.board.does_piece_fit(rotated) {
+            self.current_tetromino = rotated;
+            self.rotated_last = true;
+            self.emit(GameEvent::PieceRotated);
+        }
+    }
+
+    fn shift_tetromino(&mut self, direction: Direction, moves: u32) {
+        // INSTANT_REPEAT asks for as many moves as it takes, which is never more than the board size
+        for _ in 0..moves.min(BOARD_WIDTH.max(BOARD_HEIGHT)) {
+            if !self.board.can_piece_move(self.current_tetromino, direction) {
+                break;
+            }
+            match direction {
+                Direction::Left => self.current_tetromino.move_left(),
+                Direction::Right => self.current_tetromino.move_right(),
+                Direction::Down => {
+                    self.current_tetromino.move_down();
+                    if let Some(master) = self.master.as_mut() {
+                        master.reset_lock();
+                    }
+                }
+            }
+            self.rotated_last = false;
+            self.emit(GameEvent::PieceMoved(direction));
+        }
+    }
+
+    // Swap the current piece for the held one, or the next one if nothing is held yet
+    fn hold_tetromino(&mut self) {
+        if self.hold_used || self.is_game_over() {
+            return;
+        }
+        let shape = self.current_tetromino.get_shape();
+        // a puzzle's last piece can only be swapped for one that's already held
+        let held = self.held;
+        let Some(next_shape) = held.or_else(|| self.next_shape()) else {
+            return;
+        };
+        self.held = Some(shape);
+        self.emit(GameEvent::PieceHeld(shape));
+        self.spawn_tetromino(next_shape);
+        self.hold_used = true;
+    }
+
+    // Opener practice takes the last piece off the board and gives it back, with the
+    // hold and the queue as they were when it locked
+    fn undo_placement(&mut self) {
+        let Some(undo) = self.undo.pop() else {
+            return;
+        };
+        self.board = undo.board;
+        self.bag = undo.bag;
+        self.held = undo.held;
+        self.opener = Some(undo.opener);
+        self.score = undo.score;
+        self.lines = undo.lines;
+        self.speed = undo.speed;
+        // lines the piece filled aren't there to be cleared any more
+        self.filled_lines_shown_at = undo.filled_lines_shown_at;
+        self.emit(GameEvent::PlacementUndone(undo.shape));
+        self.spawn_tetromino(undo.shape);
+        self.hold_used = undo.hold_used;
+        // the piece was already counted when it first turned up, so the statistics go
+        // back to how they were after spawning it again rather than before
+        let time = self.statistics.get_time();
+        self.statistics = undo.statistics;
+        self.statistics.set_time(time);
+    }
+
+    // what comes next, from the bag or the puzzle. A puzzle can run out
+    fn next_shape(&mut self) -> Option<TetrominoShape> {
+        match self.puzzle.as_mut() {
+            Some(puzzle) => puzzle.next_shape(),
+            None => Some(self.bag.next_shape()),
+        }
+    }
+
+    // Running out of pieces before a puzzle is solved ends the game
+    fn spawn_next_tetromino(&mut self) {
+        if let Some(shape) = self.next_shape() {
+            self.spawn_tetromino(shape);
+        } else {
+            self.ending = Some(Ending::ToppedOut);
+            self.emit(GameEvent::GameOver);
+        }
+    }
+
+    fn spawn_tetromino(&mut self, shape: TetrominoShape) {
+        self.current_tetromino = Tetromino::new(shape);
+        if let Some(master) = self.master.as_mut() {
+            master.reset_lock();
+        }
+        self.piece_inputs = 0;
+        self.rotated_last = false;
+        self.hold_used = false;
+        self.emit(GameEvent::PieceSpawned(shape));
+        if !self
+            .board
+            .can_piece_move(self.current_tetromino, Direction::Down)
+        {
+            self.ending = Some(Ending::ToppedOut);
+            self.emit(GameEvent::GameOver);
+        }
+    }
+
+    fn lock_tetromino(&mut self) {
+        let rules = &self.ruleset.rules;
+        let (score_increment, score_completed_lines_increment, speed_increase) = (
+            rules.score_increment,
+            rules.score_completed_lines_increment,
+            rules.speed_increase,
+        );
+        let t_spin = self.rotated_last && is_t_spin(&self.board, &self.current_tetromino);
+        let Some(finesse_faults) = self.judge_finesse() else {
+            return;
+        };
+        if let Some(opener) = &self.opener {
+            self.undo.push(Undo {
+                board: self.board.clone(),
+                bag: self.bag.clone(),
+                shape: self.current_tetromino.get_shape(),
+                held: self.held,
+                opener: opener.clone(),
+                hold_used: self.hold_used,
+                score: self.score,
+                lines: self.lines,
+                speed: self.speed,
+                filled_lines_shown_at: self.filled_lines_shown_at,
+                statistics: self.statistics.clone(),
+            });
+        }
+        self.board.lock_tetromino_in_place(self.current_tetromino);
+        self.emit(GameEvent::PieceLocked {
+            shape: self.current_tetromino.get_shape(),
+            finesse_faults,
+            cells: self.current_tetromino.get_cells(),
+            t_spin,
+        });
+        let filled_rows = self.board.get_filled_lines();
+        let filled_lines = filled_rows.len();
+        let solved = self
+            .puzzle
+            .as_mut()
+            .is_some_and(|puzzle| puzzle.piece_locked(filled_lines as u32, t_spin));
+        if filled_lines > 0 {
+            let level = self.get_level();
+            self.lines += filled_lines as u32;
+            self.emit(GameEvent::LinesCleared(filled_rows));
+            let time = self.get_time();
+            let master_points = self
+                .master
+                .as_mut()
+                .map(|master| master.lines_cleared(filled_lines as u32, time));
+            if self.is_level_up(level) {
+                self.emit(GameEvent::LevelUp(self.get_level()));
+                if self.mode.has_levels() {
+                    self.speed = gravity(self.get_level());
+                }
+            }
+            if let Some(points) = master_points {
+                self.score += points;
+            } else {
+                self.score += if filled_lines == 4 {
+                    score_completed_lines_increment
+                } else {
+                    score_increment
+                };
+                // increment the score if we have completed a line & we are on a multiple of 100
+                // this score increment may need to be tweaked
+                if self.score.is_multiple_of(100) && self.score != 0 && !self.mode.has_levels() {
+                    self.speed = (self.speed - speed_increase).max(0.01);
+                }
+                self.filled_lines_shown_at = Some(time);
+            }
+            self.board.colour_in_filled_lines();
+            if self.get_lines_left() == Some(0)
+                || self.master.as_ref().is_some_and(MasterState::is_finished)
+                || solved
+            {
+                self.ending = Some(Ending::Completed);
+                self.emit(GameEvent::Completed);
+                return;
+            }
+        }
+        if let Some(opener) = self.opener.as_mut() {
+            let tetromino = self.current_tetromino;
+            if !opener.piece_locked(&tetromino) {
+                self.emit(GameEvent::OffTemplate(tetromino.get_shape()));
+            } else if opener.is_built() {
+                self.ending = Some(Ending::Completed);
+                self.emit(GameEvent::Completed);
+                return;
+            }
+        }
+        if let Some(trainer) = self.finesse.as_mut() {
+            trainer.piece_placed();
+            if trainer.is_finished() {
+                self.ending = Some(Ending::Completed);
+                self.emit(GameEvent::Completed);
+                return;
+            }
+        }
+        // Master mode brings the next piece in after a wait, see tick
+        if let Some(master) = self.master.as_mut() {
+            master.piece_locked(filled_lines > 0);
+            return;
+        }
+        self.spawn_next_tetromino();
+    }
+
+    // How many more presses the piece took than it needed, before it locks. The finesse
+    // trainer takes a piece with any to spare back to be tried again, giving None.
+    // A piece that couldn't have got there from where it spawned isn't judged
+    fn judge_finesse(&mut self) -> Option<u32> {
+        // only the trainer needs the search, the stats get by on drops onto an empty board
+        let Some(trainer) = self.finesse.as_mut() else {
+            let fewest = finesse::minimal_inputs(&self.board, &self.current_tetromino);
+            return Some(fewest.map_or(0, |fewest| self.piece_inputs.saturating_sub(fewest)));
+        };
+        let Some(path) = finesse::minimal_path(&self.board, &self.current_tetromino) else {
+            return Some(0);
+        };
+        let faults = self.piece_inputs.saturating_sub(finesse::presses(&path));
+        if faults == 0 {
+            return Some(0);
+        }
+        let shape = self.current_tetromino.get_shape();
+        trainer.fault(FinesseFault {
+            shape,
+            presses: self.piece_inputs,
+            path,
+        });
+        self.emit(GameEvent::FinesseFault { shape, faults });
+        self.spawn_tetromino(shape);
+        None
+    }
+
+    // Master mode only counts the start of each section as going up a level
+    fn is_level_up(&self, level_before: u32) -> bool {
+        if self.master.is_some() {
+            master::section(level_before) != master::section(self.get_level())
+        } else {
+            self.get_level() != level_before
+        }
+    }
+}
+
+// How long a piece takes to fall a row at a level, in seconds. The usual curve from
+// the modern games, so level 15 is close to a row every frame
+#[must_use]
+pub fn gravity(level: u32) -> f64 {
+    let level = f64::from(level.max(1) - 1);
+    (0.8 - level * 0.007).powf(level)
+}
+
+// Where the gap goes in a game's row'th row of garbage. It comes from the seed rather
+// than the bag's random numbers, so the pieces are the same as in any other mode and
+// there's nothing extra to save
+fn garbage_hole(seed: u64, row: u64) -> u32 {
+    let mut rng = Rng::new(seed ^ (row + 1).wrapping_mul(0xD1B5_4A32_D192_ED03));
+    1 + rng.gen_range(BOARD_WIDTH - 2)
+}
+
+// A T turned into place with at least three of the four squares diagonal to its
+// middle filled in, counting the walls
+fn is_t_spin(board: &Board, tetromino: &Tetromino) -> bool {
+    if tetromino.get_shape() != TetrominoShape::T {
+        return false;
+    }
+    let cells = tetromino.get_cells();
+    // the middle is the square touching the other three
+    let Some(middle) = cells.iter().find(|cell| {
+        cells
+            .iter()
+            .filter(|other| (other.x - cell.x).abs() + (other.y - cell.y).abs() == 1)
+            .count()
+            == 3
+    }) else {
+        return false;
+    };
+    let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+        .into_iter()
+        .filter(|(x, y)| {
+            let (x, y) = (middle.x + x, middle.y + y);
+            x < 0
+                || y < 0
+                || x >= BOARD_WIDTH as i32
+                || y >= BOARD_HEIGHT as i32
+                || board.get_board_character_at_coordinate(&UCoordinate::new(x as u32, y as u32))
+                    != ' '
+        })
+        .count();
+    corners >= 3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::FINESSE_PIECES;
+    use crate::storage;
+    #[test]
+    fn test_ghost_is_where_the_piece_lands() {
+        let mut game = Game::new(GameMode::Classic, Ruleset::default(), 11);
+        let ghost = game.get_ghost();
+        assert!(ghost.get_coordinates().y > game.get_current_tetromino().get_coordinates().y);
+        let mut drop = ActionState::new();
+        drop.set_pressed(Action::HardDrop);
+        game.tick(&drop);
+        for cell in ghost.get_cells() {
+            let square = game
+                .get_board()
+                .get_board_character_at_coordinate(&UCoordinate::new(cell.x as u32, cell.y as u32));
+            assert_eq!(square.to_digit(10), Some(ghost.get_colour()));
+        }
+    }
+    #[test]
+    fn test_t_spin_needs_three_corners() {
+        let board: Board = "__J_______\nJJ___JJJJJ\nJJJ_JJJJJJ".parse().unwrap();
+        // pointing down into the slot, under the overhang
+        let mut t = Tetromino::new(TetrominoShape::T);
+        t.set_rotation(crate::Rotation::TwoSeventy);
+        t.set_coordinates(ICoordinate::new(3, 14));
+        assert!(board.does_piece_fit(t));
+        assert!(is_t_spin(&board, &t));
+        // without the overhang only the two corners underneath are filled
+        let board: Board = "JJ___JJJJJ\nJJJ_JJJJJJ".parse().unwrap();
+        assert!(!is_t_spin(&board, &t));
+        // and nothing else counts
+        let mut o = Tetromino::new(TetrominoShape::O);
+        o.set_coordinates(ICoordinate::new(3, 14));
+        assert!(!is_t_spin(&board, &o));
+    }
+    // makes the next piece an I over rows of square that it will fill, so a hard
+    // drop clears every row it lands in
+    fn set_up_clear(game: &mut Game, square: char) {
+        game.board = Board::new();
+        game.current_tetromino = Tetromino::new(TetrominoShape::I);
+        // fill the rows the I lands in, apart from where it goes
+        let landing = game.get_ghost().get_cells();
+        let rows: Vec<String> = (0..BOARD_HEIGHT as i32)
+            .map(|y| {
+                (0..BOARD_WIDTH as i32)
+                    .map(|x| {
+                        if x == 0 || x == BOARD_WIDTH as i32 - 1 || y == BOARD_HEIGHT as i32 - 1 {
+                            '9'
+                        } else if landing.iter().any(|cell| cell.y == y)
+                            && !landing.contains(&ICoordinate::new(x, y))
+                        {
+                            square
+                        } else {
+                            ' '
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        game.board = Board::from_rows(&rows).unwrap();
+    }
+    fn hard_drop(game: &mut Game) {
+        let mut drop = ActionState::new();
+        drop.set_pressed(Action::HardDrop);
+        game.tick(&drop);
+    }
+    #[test]
+    fn test_sprint_ends_at_forty_lines() {
+        let mut game = Game::new(GameMode::Sprint, Ruleset::default(), 5);
+        assert_eq!(game.get_lines_left(), Some(SPRINT_LINES));
+        game.lines = SPRINT_LINES - 1;
+        set_up_clear(&mut game, '1');
+        hard_drop(&mut game);
+        assert!(game.is_complete());
+        assert!(game.is_game_over());
+        assert_eq!(game.get_lines_left(), Some(0));
+        assert!(game.get_events().contains(&GameEvent::Completed));
+        // the clock stops with the last line
+        let ticks = game.get_ticks();
+        game.tick(&ActionState::new());
+        assert_eq!(game.get_ticks(), ticks);
+        // and a classic game has no goal to reach
+        let classic = Game::new(GameMode::Classic, Ruleset::default(), 5);
+        assert_eq!(classic.get_lines_left(), None);
+    }
+    #[test]
+    fn test_ultra_ends_when_the_time_is_up() {
+        let ruleset = Ruleset {
+            rules: RulesSettings {
+                time_limit: 10.0,
+                ..RulesSettings::default()
+            },
+            ..Ruleset::default()
+        };
+        let mut game = Game::new(GameMode::Ultra, ruleset, 8);
+        assert_eq!(game.get_time_left(), Some(10.0));
+        for tick in 0..10 * TICKS_PER_SECOND - 1 {
+            game.tick(&ActionState::new());
+            assert!(!game.is_game_over(), "over early at tick {tick}");
+        }
+        game.tick(&ActionState::new());
+        assert!(game.is_complete());
+        assert_eq!(game.get_time_left(), Some(0.0));
+        assert!(game.get_events().contains(&GameEvent::Completed));
+    }
+    fn marathon_from(mode: GameMode, start_level: u32) -> Game {
+        let ruleset = Ruleset {
+            rules: RulesSettings {
+                start_level,
+                ..RulesSettings::default()
+            },
+            ..Ruleset::default()
+        };
+        Game::new(mode, ruleset, 3)
+    }
+    #[test]
+    fn test_marathon_levels_set_the_speed() {
+        let mut game = marathon_from(GameMode::Marathon, 5);
+        assert_eq!(game.get_level(), 5);
+        assert!((game.speed - gravity(5)).abs() < f64::EPSILON);
+        // the finish is still the end of level 15
+        assert_eq!(game.get_lines_left(), Some(110));
+        game.lines = 4;
+        set_up_clear(&mut game, '1');
+        hard_drop(&mut game);
+        assert_eq!(game.get_level(), 5);
+        // let the cleared lines go before the next one
+        for _ in 0..TICKS_PER_SECOND / 2 {
+            game.tick(&ActionState::new());
+        }
+        set_up_clear(&mut game, '1');
+        hard_drop(&mut game);
+        assert_eq!(game.get_level(), 6);
+        assert!(game.get_events().contains(&GameEvent::LevelUp(6)));
+        assert!((game.speed - gravity(6)).abs() < f64::EPSILON);
+        // each level is quicker than the one before
+        assert!((gravity(1) - 1.0).abs() < f64::EPSILON);
+        assert!((1..15).all(|level| gravity(level + 1) < gravity(level)));
+    }
+    #[test]
+    fn test_marathon_is_won_at_150_lines() {
+        let mut game = marathon_from(GameMode::Marathon, 1);
+        game.lines = MARATHON_LINES - 1;
+        set_up_clear(&mut game, '1');
+        hard_drop(&mut game);
+        assert!(game.is_complete());
+        assert!(game.get_events().contains(&GameEvent::Completed));
+        // the endless version just carries on
+        let mut endless = marathon_from(GameMode::Endless, 1);
+        assert_eq!(endless.get_lines_left(), None);
+        endless.lines = MARATHON_LINES - 1;
+        set_up_clear(&mut endless, '1');
+        hard_drop(&mut endless);
+        assert!(!endless.is_game_over());
+        assert_eq!(endless.get_level(), 16);
+    }
+    fn dig(dig_rows: u32, rising_garbage: f64, seed: u64) -> Game {
+        let ruleset = Ruleset {
+            rules: RulesSettings {
+                dig_rows,
+                rising_garbage,
+                ..RulesSettings::default()
+            },
+            ..Ruleset::default()
+        };
+        Game::new(GameMode::Dig, ruleset, seed)
+    }
+    #[test]
+    fn test_dig_starts_with_garbage() {
+        let game = dig(8, 0.0, 12);
+        let rows = game.get_board().get_rows();
+        assert_eq!(game.get_lines_left(), Some(8));
+        // each row of garbage has exactly one gap, and they aren't all in a line
+        let garbage = &rows[BOARD_HEIGHT as usize - 9..BOARD_HEIGHT as usize - 1];
+        assert!(garbage.iter().all(|row| row.matches(' ').count() == 1));
+        assert!(
+            garbage
+                .iter()
+                .any(|row| row.find(' ') != garbage[0].find(' '))
+        );
+        assert!(
+            rows[..BOARD_HEIGHT as usize - 9]
+                .iter()
+                .all(|row| row.trim_matches('9').trim().is_empty())
+        );
+        // the same seed digs through the same garbage
+        assert_eq!(dig(8, 0.0, 12).get_board(), game.get_board());
+    }
+    #[test]
+    fn test_dig_ends_when_the_garbage_is_gone() {
+        let mut game = dig(1, 0.0, 12);
+        set_up_clear(&mut game, '9');
+        assert_eq!(game.get_lines_left(), Some(4));
+        hard_drop(&mut game);
+        assert!(game.is_complete());
+        assert_eq!(game.get_lines_left(), Some(0));
+        assert!(game.get_events().contains(&GameEvent::Completed));
+    }
+    #[test]
+    fn test_rising_garbage_tops_out() {
+        let mut game = dig(12, 1.0, 12);
+        for _ in 0..TICKS_PER_SECOND {
+            game.tick(&ActionState::new());
+        }
+        assert!(game.get_lines_left() >= Some(12));
+        assert!(!game.is_game_over());
+        for _ in 0..10 * TICKS_PER_SECOND {
+            game.tick(&ActionState::new());
+        }
+        assert!(game.is_game_over());
+        assert!(!game.is_complete());
+        // pushing anything off the top is what does it
+        let mut board = Board::new();
+        assert!(!board.insert_garbage_rows(BOARD_HEIGHT - 1, 3));
+        assert!(board.insert_garbage_rows(1, 3));
+    }
+    #[test]
+    fn test_master_locks_and_waits_for_the_next_piece() {
+        let mut game = Game::new(GameMode::Master, Ruleset::default(), 4);
+        hard_drop(&mut game);
+        assert!(game.is_between_pieces());
+        for _ in 0..24 {
+            game.tick(&ActionState::new());
+        }
+        assert!(game.is_between_pieces());
+        game.tick(&ActionState::new());
+        assert!(!game.is_between_pieces());
+        assert_eq!(game.get_level(), 1);
+    }
+    #[test]
+    fn test_master_twenty_g() {
+        // start from level 500 by way of a saved game
+        let game = Game::new(GameMode::Master, Ruleset::default(), 4);
+        let text = game
+            .to_saved()
+            .to_toml()
+            .unwrap()
+            .replace("[master]\nlevel = 0", "[master]\nlevel = 500");
+        let mut game = Game::from_saved(&SavedGame::from_toml(&text).unwrap()).unwrap();
+        assert_eq!(game.get_level(), 500);
+        game.tick(&ActionState::new());
+        assert_eq!(
+            game.get_current_tetromino().get_cells(),
+            game.get_ghost().get_cells()
+        );
+        // then it sits on the ground for the lock delay
+        for _ in 1..30 {
+            assert!(!game.is_between_pieces());
+            game.tick(&ActionState::new());
+        }
+        assert!(game.is_between_pieces());
+        assert!(matches!(
+            game.get_events().first(),
+            Some(GameEvent::PieceLocked { .. })
+        ));
+    }
+    fn puzzle(goal: &str, pieces: &str) -> Game {
+        let text = format!(
+            "name = \"Test\"\ngoal = {goal}\npieces = \"{pieces}\"\nboard = \"\"\"\n{}\"\"\"",
+            "JJJJJJ_JJJ\n".repeat(4)
+        );
+        Game::puzzle(&storage::from_toml(&text).unwrap(), Ruleset::default())
+    }
+    #[test]
+    fn test_puzzle_solved_by_its_goal() {
+        let mut game = puzzle("{ type = \"clear_lines\", lines = 4 }", "I O T");
+        assert_eq!(game.get_current_tetromino().get_shape(), TetrominoShape::I);
+        assert_eq!(game.get_next(5), vec![TetrominoShape::O, TetrominoShape::T]);
+        assert_eq!(game.get_lines_left(), Some(4));
+        hard_drop(&mut game);
+        assert!(game.is_complete());
+        assert!(game.get_puzzle().unwrap().is_solved());
+        assert!(game.get_events().contains(&GameEvent::Completed));
+    }
+    #[test]
+    fn test_puzzle_failed_when_the_pieces_run_out() {
+        let mut game = puzzle("{ type = \"clear_lines\", lines = 4 }", "O");
+        assert!(game.get_next(5).is_empty());
+        // there's nothing to swap the last piece for
+        let mut hold = ActionState::new();
+        hold.set_pressed(Action::Hold);
+        game.tick(&hold);
+        assert_eq!(game.get_held(), None);
+        hard_drop(&mut game);
+        assert!(game.is_game_over());
+        assert!(!game.is_complete());
+        assert!(game.get_events().contains(&GameEvent::GameOver));
+    }
+    #[test]
+    fn test_position_of_a_game() {
+        let mut game = puzzle("{ type = \"clear_lines\", lines = 4 }", "I O T");
+        let mut hold = ActionState::new();
+        hold.set_pressed(Action::Hold);
+        game.tick(&hold);
+        let position = game.get_position();
+        let text = format!(
+            "current: O\nhold: I\nqueue: T\n{}",
+            ["JJJJJJ_JJJ"; 4].join("\n")
+        );
+        assert_eq!(position.to_string(), text);
+        assert_eq!(text.parse::<Position>(), Ok(position));
+    }
+    #[test]
+    fn test_finesse_trainer_takes_faults_back() {
+        let mut game = Game::new(GameMode::Finesse, Ruleset::default(), 3);
+        let shape = game.get_current_tetromino().get_shape();
+        // there and back again is two presses for a piece that needed none
+        for action in [Action::MoveLeft, Action::MoveRight] {
+            let mut actions = ActionState::new();
+            actions.set_pressed(action);
+            game.tick(&actions);
+        }
+        hard_drop(&mut game);
+        assert_eq!(game.get_board(), &Board::new());
+        assert_eq!(game.get_current_tetromino().get_shape(), shape);
+        assert!(
+            game.get_events()
+                .contains(&GameEvent::FinesseFault { shape, faults: 2 })
+        );
+        let fault = game.get_finesse().unwrap().get_last_fault().unwrap();
+        assert_eq!((fault.presses, fault.path.len()), (2, 0));
+        assert_eq!(game.get_statistics().get_finesse_faults(), 2);
+        // doing it properly puts it down and moves on
+        hard_drop(&mut game);
+        let trainer = game.get_finesse().unwrap();
+        assert_eq!(trainer.get_pieces_left(), FINESSE_PIECES - 1);
+        assert_eq!(trainer.get_last_fault(), None);
+        assert_eq!(game.get_statistics().get_pieces(), 1);
+    }
+    #[test]
+    fn test_opener_checks_placements_and_undoes() {
+        let opener = &Opener::built_in()[0];
+        let mut game = Game::opener(opener, Ruleset::default(), 2);
+        assert_eq!(game.get_current_tetromino().get_shape(), opener.order[0]);
+        assert_eq!(game.get_next(5), opener.order[1..6]);
+        // straight down from where it spawns isn't where a TKI has its I
+        hard_drop(&mut game);
+        let shape = opener.order[0];
+        assert!(game.get_events().contains(&GameEvent::OffTemplate(shape)));
+        assert_eq!(game.get_opener().unwrap().get_misplaced(), 1);
+        let mut undo = ActionState::new();
+        undo.set_pressed(Action::Undo);
+        game.tick(&undo);
+        assert_eq!(game.get_board(), &Board::new());
+        assert_eq!(game.get_current_tetromino().get_shape(), shape);
+        assert_eq!(game.get_next(5), opener.order[1..6]);
+        assert_eq!(game.get_opener().unwrap().get_misplaced(), 0);
+        // nothing left to undo is fine too
+        game.tick(&undo);
+        game.tick(&undo);
+        assert_eq!(game.get_current_tetromino().get_shape(), shape);
+        // placing each where the template has it builds it
+        for target in &opener.targets {
+            assert_eq!(game.get_current_tetromino().get_shape(), target.shape);
+            game.current_tetromino = target.get_tetromino().unwrap();
+            game.lock_tetromino();
+        }
+        assert!(game.is_complete());
+        assert!(game.get_opener().unwrap().is_built());
+    }
+    #[test]
+    fn test_undo_puts_the_statistics_back() {
+        let opener = &Opener::built_in()[0];
+        let mut game = Game::opener(opener, Ruleset::default(), 2);
+        set_up_clear(&mut game, '1');
+        // what the piece changes, leaving out the keys pressed to place it which still count
+        let placed = |game: &Game| {
+            let statistics = game.get_statistics();
+            (
+                game.get_score(),
+                game.get_lines(),
+                game.speed,
+                statistics.get_pieces(),
+                statistics.get_lines(),
+                (1..=4)
+                    .map(|lines| statistics.get_clears(lines))
+                    .collect::<Vec<u32>>(),
+                statistics.get_attack(),
+                statistics.get_spawned(TetrominoShape::I),
+            )
+        };
+        let before = placed(&game);
+        let board = game.get_board().clone();
+        hard_drop(&mut game);
+        assert_ne!(placed(&game), before);
+        // taken back straight away, while the cleared lines are still showing
+        let mut undo = ActionState::new();
+        undo.set_pressed(Action::Undo);
+        game.tick(&undo);
+        assert_eq!(placed(&game), before);
+        assert_eq!(game.filled_lines_shown_at, None);
+        for _ in 0..TICKS_PER_SECOND {
+            game.tick(&ActionState::new());
+        }
+        assert_eq!(game.get_board(), &board);
+    }
+    #[test]
+    fn test_undo_puts_the_hold_back() {
+        let opener = &Opener::built_in()[0];
+        let mut game = Game::opener(opener, Ruleset::default(), 2);
+        let mut press_hold = ActionState::new();
+        press_hold.set_pressed(Action::Hold);
+        game.tick(&press_hold);
+        let (held, shape) = (game.get_held(), game.get_current_tetromino().get_shape());
+        hard_drop(&mut game);
+        assert!(!game.hold_used);
+        let mut undo = ActionState::new();
+        undo.set_pressed(Action::Undo);
+        game.tick(&undo);
+        assert_eq!(game.get_held(), held);
+        assert_eq!(game.get_current_tetromino().get_shape(), shape);
+        // it was held once already before it locked
+        assert!(game.hold_used);
+    }
+    #[test]
+    fn test_continue_drops_the_undo_history() {
+        let opener = &Opener::built_in()[0];
+        let mut game = Game::opener(opener, Ruleset::default(), 2);
+        hard_drop(&mut game);
+        let board = game.get_board().clone();
+        let mut continued = Game::from_saved(&game.to_saved()).unwrap();
+        assert_eq!(continued.get_opener(), game.get_opener());
+        let mut undo = ActionState::new();
+        undo.set_pressed(Action::Undo);
+        continued.tick(&undo);
+        assert_eq!(continued.get_board(), &board);
+        assert!(
+            !continued
+                .get_events()
+                .contains(&GameEvent::PlacementUndone(opener.order[0]))
+        );
+        // the game that wasn't saved can still take it back
+        game.tick(&undo);
+        assert_eq!(game.get_board(), &Board::new());
+    }
+    #[test]
+    fn test_continued_t_spin() {
+        let mut game = Game::new(GameMode::Classic, Ruleset::default(), 3);
+        game.board = "__J_______\nJJ___JJJJJ\nJJJ_JJJJJJ".parse().unwrap();
+        let mut t = Tetromino::new(TetrominoShape::T);
+        t.set_rotation(crate::Rotation::OneEighty);
+        t.set_coordinates(ICoordinate::new(3, 14));
+        game.current_tetromino = t;
+        // turned into the slot, then saved before it's dropped
+        let mut rotate = ActionState::new();
+        rotate.set_pressed(Action::Rotate);
+        game.tick(&rotate);
+        assert_eq!(
+            game.get_current_tetromino().get_rotation(),
+            crate::Rotation::TwoSeventy
+        );
+        let saved = SavedGame::from_toml(&game.to_saved().to_toml().unwrap()).unwrap();
+        let mut continued = Game::from_saved(&saved).unwrap();
+        for game in [&mut game, &mut continued] {
+            hard_drop(game);
+            assert!(
+                game.get_events()
+                    .iter()
+                    .any(|event| matches!(event, GameEvent::PieceLocked { t_spin: true, .. }))
+            );
+        }
+        assert_eq!(continued.get_statistics(), game.get_statistics());
+        assert_eq!(continued.get_replay(), game.get_replay());
+    }
+}
@@ -0,0 +1,349 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::input::{Action, ActionState, InputSource};
+
+pub const DEFAULT_DEADZONE: f32 = 0.3; // how far the stick has to move before it counts
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftShoulder,
+    RightShoulder,
+    Select,
+    Start,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+}
+
+// Axis values run from -1.0 to 1.0 with positive values pointing right & down
+// (the same way round as the board), whatever the backend reports
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GamepadEvent {
+    ButtonPressed(GamepadButton),
+    ButtonReleased(GamepadButton),
+    AxisMoved(GamepadAxis, f32),
+    Disconnected,
+}
+
+// A stream of raw gamepad events. The real one talks to the OS,
+// the simulated one is fed by hand from tests
+pub trait GamepadEventSource {
+    fn next_event(&mut self) -> Option<GamepadEvent>;
+}
+
+#[derive(Clone, Debug)]
+pub struct GamepadMapping {
+    buttons: Vec<(GamepadButton, Action)>,
+    deadzone: f32,
+}
+impl Default for GamepadMapping {
+    fn default() -> Self {
+        Self::new(vec![
+            (GamepadButton::DPadLeft, Action::MoveLeft),
+            (GamepadButton::DPadRight, Action::MoveRight),
+            (GamepadButton::DPadDown, Action::SoftDrop),
+            (GamepadButton::DPadUp, Action::Rotate),
+            (GamepadButton::South, Action::Rotate),
+            (GamepadButton::West, Action::RotateCounterClockwise),
+            (GamepadButton::North, Action::HardDrop),
+            (GamepadButton::LeftShoulder, Action::Hold),
+            (GamepadButton::RightShoulder, Action::Hold),
+            (GamepadButton::Start, Action::Pause),
+            (GamepadButton::DPadUp, Action::MenuUp),
+            (GamepadButton::DPadDown, Action::MenuDown),
+            (GamepadButton::South, Action::Confirm),
+            (GamepadButton::Start, Action::Confirm),
+            (GamepadButton::East, Action::Back),
+            (GamepadButton::Select, Action::Back),
+            (GamepadButton::Select, Action::Undo),
+        ])
+    }
+}
+impl GamepadMapping {
+    #[must_use]
+    pub fn new(buttons: Vec<(GamepadButton, Action)>) -> Self {
+        Self {
+            buttons,
+            deadzone: DEFAULT_DEADZONE,
+        }
+    }
+    #[must_use]
+    pub fn with_deadzone(mut self, deadzone: f32) -> Self {
+        self.set_deadzone(deadzone);
+        self
+    }
+    pub fn set_deadzone(&mut self, deadzone: f32) {
+        // a deadzone of 1.0 or more would mean the stick never registers
+        self.deadzone = deadzone.clamp(0.0, 0.95);
+    }
+    #[must_use]
+    pub fn get_deadzone(&self) -> f32 {
+        self.deadzone
+    }
+    fn actions_for_button(&self, button: GamepadButton) -> impl Iterator<Item = Action> + '_ {
+        self.buttons
+            .iter()
+            .filter(move |(mapped_button, _)| *mapped_button == button)
+            .map(|(_, action)| *action)
+    }
+    fn actions_for_stick(&self, x: f32, y: f32) -> Vec<Action> {
+        // the stick behaves like the D-pad once it is outside the deadzone.
+        // Pushing up is ignored, it is too easy to do by accident while moving sideways
+        let mut ret_actions = Vec::new();
+        if x < -self.deadzone {
+            ret_actions.push(Action::MoveLeft);
+        }
+        if x > self.deadzone {
+            ret_actions.push(Action::MoveRight);
+        }
+        if y > self.deadzone {
+            ret_actions.push(Action::SoftDrop);
+        }
+        ret_actions
+    }
+}
+
+pub struct GamepadInput<S: GamepadEventSource> {
+    source: S,
+    mapping: GamepadMapping,
+    buttons_down: HashSet<GamepadButton>,
+    stick: (f32, f32),
+    previous: ActionState,
+}
+impl<S: GamepadEventSource> GamepadInput<S> {
+    pub fn new(source: S, mapping: GamepadMapping) -> Self {
+        Self {
+            source,
+            mapping,
+            buttons_down: HashSet::new(),
+            stick: (0.0, 0.0),
+            previous: ActionState::new(),
+        }
+    }
+    pub fn get_mapping_mut(&mut self) -> &mut GamepadMapping {
+        &mut self.mapping
+    }
+    pub fn get_source_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+}
+impl<S: GamepadEventSource> InputSource for GamepadInput<S> {
+    fn poll(&mut self) -> ActionState {
+        let mut ret_state = ActionState::new();
+        while let Some(event) = self.source.next_event() {
+            match event {
+                GamepadEvent::ButtonPressed(button) => {
+                    self.buttons_down.insert(button);
+                    // record the press straight away so a tap that is
+                    // released before the next poll isn't lost
+                    for action in self.mapping.actions_for_button(button) {
+                        if !self.previous.is_down(action) {
+                            ret_state.set_pressed(action);
+                        }
+                    }
+                }
+                GamepadEvent::ButtonReleased(button) => {
+                    self.buttons_down.remove(&button);
+                }
+                GamepadEvent::AxisMoved(GamepadAxis::LeftStickX, value) => self.stick.0 = value,
+                GamepadEvent::AxisMoved(GamepadAxis::LeftStickY, value) => self.stick.1 = value,
+                GamepadEvent::Disconnected => {
+                    self.buttons_down.clear();
+                    self.stick = (0.0, 0.0);
+                }
+            }
+        }
+        let mut held: Vec<Action> = self
+            .buttons_down
+            .iter()
+            .flat_map(|button| self.mapping.actions_for_button(*button))
+            .collect();
+        held.extend(self.mapping.actions_for_stick(self.stick.0, self.stick.1));
+        for action in held {
+            if self.previous.is_down(action) {
+                ret_state.set_down(action);
+            } else {
+                ret_state.set_pressed(action);
+            }
+        }
+        self.previous = ret_state;
+        ret_state
+    }
+}
+
+// Test harness - queue up events by hand and they are handed out
+// in order the next time the input is polled
+#[derive(Default)]
+pub struct SimulatedGamepad {
+    events: VecDeque<GamepadEvent>,
+}
+impl SimulatedGamepad {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(&mut self, event: GamepadEvent) {
+        self.events.push_back(event);
+    }
+    pub fn press(&mut self, button: GamepadButton) {
+        self.push(GamepadEvent::ButtonPressed(button));
+    }
+    pub fn release(&mut self, button: GamepadButton) {
+        self.push(GamepadEvent::ButtonReleased(button));
+    }
+    pub fn move_stick(&mut self, x: f32, y: f32) {
+        self.push(GamepadEvent::AxisMoved(GamepadAxis::LeftStickX, x));
+        self.push(GamepadEvent::AxisMoved(GamepadAxis::LeftStickY, y));
+    }
+}
+impl GamepadEventSource for SimulatedGamepad {
+    fn next_event(&mut self) -> Option<GamepadEvent> {
+        self.events.pop_front()
+    }
+}
+
+// Real controllers via gilrs. This needs libudev on Linux
+// so it sits behind the "gamepad" feature
+#[cfg(feature = "gamepad")]
+pub struct GilrsEventSource {
+    gilrs: gilrs::Gilrs,
+}
+#[cfg(feature = "gamepad")]
+impl GilrsEventSource {
+    pub fn new() -> Result<Self, Box<gilrs::Error>> {
+        Ok(Self {
+            gilrs: gilrs::Gilrs::new().map_err(Box::new)?,
+        })
+    }
+    fn convert_button(button: gilrs::Button) -> Option<GamepadButton> {
+        match button {
+            gilrs::Button::South => Some(GamepadButton::South),
+            gilrs::Button::East => Some(GamepadButton::East),
+            gilrs::Button::North => Some(GamepadButton::North),
+            gilrs::Button::West => Some(GamepadButton::West),
+            gilrs::Button::LeftTrigger => Some(GamepadButton::LeftShoulder),
+            gilrs::Button::RightTrigger => Some(GamepadButton::RightShoulder),
+            gilrs::Button::Select => Some(GamepadButton::Select),
+            gilrs::Button::Start => Some(GamepadButton::Start),
+            gilrs::Button::DPadUp => Some(GamepadButton::DPadUp),
+            gilrs::Button::DPadDown => Some(GamepadButton::DPadDown),
+            gilrs::Button::DPadLeft => Some(GamepadButton::DPadLeft),
+            gilrs::Button::DPadRight => Some(GamepadButton::DPadRight),
+            _ => None,
+        }
+    }
+    fn convert_event(event: gilrs::EventType) -> Option<GamepadEvent> {
+        match event {
+            gilrs::EventType::ButtonPressed(button, _) => {
+                Self::convert_button(button).map(GamepadEvent::ButtonPressed)
+            }
+            gilrs::EventType::ButtonReleased(button, _) => {
+                Self::convert_button(button).map(GamepadEvent::ButtonReleased)
+            }
+            gilrs::EventType::AxisChanged(gilrs::Axis::LeftStickX, value, _) => {
+                Some(GamepadEvent::AxisMoved(GamepadAxis::LeftStickX, value))
+            }
+            // gilrs reports up as positive, the board counts down the screen
+            gilrs::EventType::AxisChanged(gilrs::Axis::LeftStickY, value, _) => {
+                Some(GamepadEvent::AxisMoved(GamepadAxis::LeftStickY, -value))
+            }
+            gilrs::EventType::Disconnected => Some(GamepadEvent::Disconnected),
+            _ => None,
+        }
+    }
+}
+#[cfg(feature = "gamepad")]
+impl GamepadEventSource for GilrsEventSource {
+    fn next_event(&mut self) -> Option<GamepadEvent> {
+        // skip over anything we don't have a use for
+        while let Some(gilrs::Event { event, .. }) = self.gilrs.next_event() {
+            if let Some(converted) = Self::convert_event(event) {
+                return Some(converted);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulated_input() -> GamepadInput<SimulatedGamepad> {
+        GamepadInput::new(SimulatedGamepad::new(), GamepadMapping::default())
+    }
+    #[test]
+    fn test_button_press_and_hold() {
+        let mut input = simulated_input();
+        input.get_source_mut().press(GamepadButton::DPadLeft);
+        let state = input.poll();
+        assert!(state.is_pressed(Action::MoveLeft));
+        assert!(state.is_down(Action::MoveLeft));
+        // still held on the next frame but no longer a fresh press
+        let state = input.poll();
+        assert!(!state.is_pressed(Action::MoveLeft));
+        assert!(state.is_down(Action::MoveLeft));
+        input.get_source_mut().release(GamepadButton::DPadLeft);
+        let state = input.poll();
+        assert!(!state.is_down(Action::MoveLeft));
+    }
+    #[test]
+    fn test_tap_between_polls_is_not_lost() {
+        let mut input = simulated_input();
+        input.get_source_mut().press(GamepadButton::South);
+        input.get_source_mut().release(GamepadButton::South);
+        let state = input.poll();
+        assert!(state.is_pressed(Action::Rotate));
+        let state = input.poll();
+        assert!(!state.is_down(Action::Rotate));
+    }
+    #[test]
+    fn test_stick_deadzone() {
+        let mut input = GamepadInput::new(
+            SimulatedGamepad::new(),
+            GamepadMapping::default().with_deadzone(0.5),
+        );
+        input.get_source_mut().move_stick(-0.4, 0.2);
+        let state = input.poll();
+        assert!(!state.is_down(Action::MoveLeft));
+        assert!(!state.is_down(Action::SoftDrop));
+        input.get_source_mut().move_stick(-0.6, 0.7);
+        let state = input.poll();
+        assert!(state.is_pressed(Action::MoveLeft));
+        assert!(state.is_pressed(Action::SoftDrop));
+        input.get_source_mut().move_stick(0.9, 0.0);
+        let state = input.poll();
+        assert!(!state.is_down(Action::MoveLeft));
+        assert!(state.is_pressed(Action::MoveRight));
+    }
+    #[test]
+    fn test_deadzone_is_clamped() {
+        let mapping = GamepadMapping::default().with_deadzone(2.0);
+        assert!(mapping.get_deadzone() < 1.0);
+        let mapping = GamepadMapping::default().with_deadzone(-1.0);
+        assert!(mapping.get_deadzone().abs() < f32::EPSILON);
+    }
+    #[test]
+    fn test_disconnect_releases_everything() {
+        let mut input = simulated_input();
+        input.get_source_mut().press(GamepadButton::DPadDown);
+        input.get_source_mut().move_stick(1.0, 0.0);
+        let state = input.poll();
+        assert!(state.is_down(Action::SoftDrop));
+        assert!(state.is_down(Action::MoveRight));
+        input.get_source_mut().push(GamepadEvent::Disconnected);
+        let state = input.poll();
+        assert_eq!(state, ActionState::new());
+    }
+}
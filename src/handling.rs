@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Direction;
+use crate::input::{Action, ActionState};
+use crate::settings::HandlingSettings;
+
+// Turns a held button into a stream of moves.
+// The first move happens as soon as the button goes down, then nothing until the
+// delayed auto shift (DAS) has passed, then one move every auto repeat rate (ARR).
+// An ARR of zero means the piece should go as far as it can straight away
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AutoRepeat {
+    delay: f64,
+    rate: f64,
+    held_for: f64,
+    repeats: u32,
+}
+pub const INSTANT_REPEAT: u32 = u32::MAX;
+
+impl AutoRepeat {
+    #[must_use]
+    pub fn new(delay: f64, rate: f64) -> Self {
+        Self {
+            delay,
+            rate,
+            held_for: 0.0,
+            repeats: 0,
+        }
+    }
+    pub fn set_timing(&mut self, delay: f64, rate: f64) {
+        self.delay = delay;
+        self.rate = rate;
+    }
+    pub fn reset(&mut self) {
+        self.held_for = 0.0;
+        self.repeats = 0;
+    }
+    // returns the number of moves to make this frame. INSTANT_REPEAT means as many as possible
+    pub fn update(&mut self, is_down: bool, is_pressed: bool, delta_time: f64) -> u32 {
+        if !is_down {
+            self.reset();
+            return 0;
+        }
+        if is_pressed {
+            self.reset();
+            return 1;
+        }
+        self.held_for += delta_time;
+        if self.held_for < self.delay {
+            return 0;
+        }
+        if self.rate <= 0.0 {
+            return INSTANT_REPEAT;
+        }
+        // work out how many repeats should have happened since the delay ran out
+        // and hand back the ones we haven't done yet. That way a slow frame catches up
+        let repeats_due = 1.0 + ((self.held_for - self.delay) / self.rate).floor();
+        // a rate so quick that the count won't fit is as good as instant
+        if repeats_due >= f64::from(INSTANT_REPEAT) {
+            return INSTANT_REPEAT;
+        }
+        let repeats_due = repeats_due as u32;
+        let ret_moves = repeats_due - self.repeats;
+        self.repeats = repeats_due;
+        ret_moves
+    }
+}
+
+// The three directions a piece can be pushed in, each repeating on its own timer.
+// Soft drop starts repeating straight away, there is no delay before it kicks in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PieceHandling {
+    left: AutoRepeat,
+    right: AutoRepeat,
+    down: AutoRepeat,
+}
+impl PieceHandling {
+    #[must_use]
+    pub fn new(settings: &HandlingSettings) -> Self {
+        let mut ret_handling = Self::default();
+        ret_handling.apply_settings(settings);
+        ret_handling
+    }
+    pub fn apply_settings(&mut self, settings: &HandlingSettings) {
+        self.left
+            .set_timing(settings.delayed_auto_shift, settings.auto_repeat_rate);
+        self.right
+            .set_timing(settings.delayed_auto_shift, settings.auto_repeat_rate);
+        self.down.set_timing(0.0, settings.soft_drop_speed);
+    }
+    // how many times to move the piece in each direction this frame
+    pub fn update(&mut self, actions: &ActionState, delta_time: f64) -> [(Direction, u32); 3] {
+        let update = |repeat: &mut AutoRepeat, action: Action| {
+            repeat.update(
+                actions.is_down(action),
+                actions.is_pressed(action),
+                delta_time,
+            )
+        };
+        [
+            (Direction::Left, update(&mut self.left, Action::MoveLeft)),
+            (Direction::Right, update(&mut self.right, Action::MoveRight)),
+            (Direction::Down, update(&mut self.down, Action::SoftDrop)),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_press_moves_once() {
+        let mut repeat = AutoRepeat::new(0.2, 0.05);
+        assert_eq!(repeat.update(true, true, 0.016), 1);
+        assert_eq!(repeat.update(true, false, 0.1), 0);
+        assert_eq!(repeat.update(false, false, 0.016), 0);
+    }
+    #[test]
+    fn test_repeats_after_delay() {
+        let mut repeat = AutoRepeat::new(0.2, 0.05);
+        assert_eq!(repeat.update(true, true, 0.0), 1);
+        assert_eq!(repeat.update(true, false, 0.19), 0);
+        assert_eq!(repeat.update(true, false, 0.02), 1);
+        assert_eq!(repeat.update(true, false, 0.03), 0);
+        assert_eq!(repeat.update(true, false, 0.03), 1);
+        // a long frame catches up on all the moves it missed
+        assert_eq!(repeat.update(true, false, 0.2), 4);
+    }
+    #[test]
+    fn test_zero_rate_is_instant() {
+        let mut repeat = AutoRepeat::new(0.1, 0.0);
+        assert_eq!(repeat.update(true, true, 0.0), 1);
+        assert_eq!(repeat.update(true, false, 0.05), 0);
+        assert_eq!(repeat.update(true, false, 0.06), INSTANT_REPEAT);
+    }
+    #[test]
+    fn test_tiny_rate_is_instant() {
+        // a hand edited settings file can have any rate above zero
+        let mut repeat = AutoRepeat::new(0.0, 1e-9);
+        assert_eq!(repeat.update(true, true, 0.0), 1);
+        assert_eq!(repeat.update(true, false, 5.0), INSTANT_REPEAT);
+        assert_eq!(repeat.update(true, false, 5.0), INSTANT_REPEAT);
+    }
+    #[test]
+    fn test_release_resets() {
+        let mut repeat = AutoRepeat::new(0.1, 0.05);
+        assert_eq!(repeat.update(true, true, 0.0), 1);
+        assert_eq!(repeat.update(true, false, 0.16), 2);
+        assert_eq!(repeat.update(false, false, 0.016), 0);
+        assert_eq!(repeat.update(true, false, 0.05), 0);
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{HIGH_SCORE_TABLE_SIZE, MAX_NAME_LENGTH};
+use crate::game::{GameMode, Ranking};
+use crate::input::{Action, ActionState};
+use crate::storage::{self, StorageError};
+
+const HIGH_SCORES_FILE: &str = "highscores.toml";
+const NAME_CHARACTERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+const DEFAULT_NAME: &str = "PLAYER";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    pub lines: u32,
+    pub level: u32,
+    pub time: f64, // how long the game lasted in seconds
+    pub date: String,
+}
+
+// The best scores for each game mode, best first. Best is the highest score or
+// the quickest time, depending on the mode
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HighScores {
+    tables: BTreeMap<String, Vec<HighScore>>,
+}
+impl HighScores {
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        storage::data_path(HIGH_SCORES_FILE)
+    }
+
+    pub fn load() -> Result<HighScores, StorageError> {
+        let path = Self::default_path().ok_or(StorageError::NoDirectory)?;
+        Self::load_from(&path)
+    }
+
+    pub fn load_from(path: &Path) -> Result<HighScores, StorageError> {
+        storage::load_toml(path)
+    }
+
+    pub fn save(&self) -> Result<(), StorageError> {
+        let path = Self::default_path().ok_or(StorageError::NoDirectory)?;
+        self.save_to(&path)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), StorageError> {
+        storage::save_toml(path, self)
+    }
+
+    #[must_use]
+    pub fn get_table(&self, mode: GameMode) -> &[HighScore] {
+        self.tables.get(mode.name()).map_or(&[], Vec::as_slice)
+    }
+
+    #[must_use]
+    pub fn qualifies(&self, mode: GameMode, high_score: &HighScore) -> bool {
+        // a score or time of nothing never makes it onto the table, nor does a puzzle
+        let counts = mode.has_high_scores()
+            && match mode.ranking() {
+                Ranking::Score => high_score.score > 0,
+                Ranking::Time => high_score.time > 0.0,
+            };
+        let table = self.get_table(mode);
+        counts
+            && (table.len() < HIGH_SCORE_TABLE_SIZE
+                || table
+                    .last()
+                    .is_some_and(|lowest| is_better(mode, high_score, lowest)))
+    }
+
+    // Add a score to the table for its mode, returning where it ended up.
+    // Ties go below the existing score, whoever got there first keeps their place
+    pub fn insert(&mut self, mode: GameMode, high_score: HighScore) -> Option<usize> {
+        if !self.qualifies(mode, &high_score) {
+            return None;
+        }
+        let table = self.tables.entry(String::from(mode.name())).or_default();
+        let position = table
+            .iter()
+            .position(|existing| is_better(mode, &high_score, existing))
+            .unwrap_or(table.len());
+        table.insert(position, high_score);
+        table.truncate(HIGH_SCORE_TABLE_SIZE);
+        Some(position)
+    }
+}
+
+fn is_better(mode: GameMode, high_score: &HighScore, other: &HighScore) -> bool {
+    match mode.ranking() {
+        Ranking::Score => high_score.score > other.score,
+        Ranking::Time => high_score.time < other.time,
+    }
+}
+
+// Typing a name in for the high score table. Keyboards can just type it,
+// gamepads scroll through the letters with up & down and move along with left & right
+#[derive(Clone, Debug, Default)]
+pub struct NameEntry {
+    name: Vec<char>,
+    cursor: usize,
+}
+impl NameEntry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    #[must_use]
+    pub fn get_cursor(&self) -> usize {
+        self.cursor
+    }
+    #[must_use]
+    pub fn get_name(&self) -> String {
+        let name: String = self.name.iter().collect();
+        let name = name.trim();
+        if name.is_empty() {
+            String::from(DEFAULT_NAME)
+        } else {
+            String::from(name)
+        }
+    }
+    #[must_use]
+    pub fn get_text(&self) -> String {
+        self.name.iter().collect()
+    }
+    fn type_character(&mut self, character: char) {
+        if self.cursor >= MAX_NAME_LENGTH {
+            return;
+        }
+        let character = character.to_ascii_uppercase();
+        if self.cursor == self.name.len() {
+            self.name.push(character);
+        } else {
+            self.name[self.cursor] = character;
+        }
+        self.cursor += 1;
+    }
+    fn delete_character(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.name.remove(self.cursor);
+        }
+    }
+    fn cycle_character(&mut self, step: i32) {
+        if self.cursor >= MAX_NAME_LENGTH {
+            return;
+        }
+        if self.cursor == self.name.len() {
+            self.name.push(' ');
+        }
+        // a blank (or anything typed that isn't in the list) starts from the beginning
+        let characters: Vec<char> = NAME_CHARACTERS.chars().collect();
+        let count = characters.len() as i32;
+        let current = characters
+            .iter()
+            .position(|character| *character == self.name[self.cursor])
+            .map_or(if step > 0 { -1 } else { 0 }, |index| index as i32);
+        self.name[self.cursor] = characters[(current + step).rem_euclid(count) as usize];
+    }
+    // returns true once the name has been confirmed
+    pub fn update(&mut self, actions: &ActionState, typed: &[char]) -> bool {
+        if actions.is_pressed(Action::Confirm) {
+            return true;
+        }
+        for character in typed {
+            if character.is_ascii_alphanumeric() {
+                self.type_character(*character);
+            }
+        }
+        if actions.is_pressed(Action::Back) {
+            self.delete_character();
+        }
+        if actions.is_pressed(Action::MenuUp) {
+            self.cycle_character(1);
+        }
+        if actions.is_pressed(Action::MenuDown) {
+            self.cycle_character(-1);
+        }
+        if actions.is_pressed(Action::MoveLeft) {
+            self.cursor = self.cursor.saturating_sub(1);
+        }
+        if actions.is_pressed(Action::MoveRight) && self.cursor < self.name.len() {
+            self.cursor = (self.cursor + 1).min(MAX_NAME_LENGTH - 1);
+        }
+        false
+    }
+}
+
+// Today's date as YYYY-MM-DD (UTC). Saves pulling in a date library just for this
+#[must_use]
+pub fn today() -> String {
+    date_from_seconds(seconds_since_epoch())
+}
+
+// The date & time now as YYYY-MM-DD_HHMMSS (UTC), used to name files so they sort oldest first
+#[must_use]
+pub fn timestamp() -> String {
+    let seconds = seconds_since_epoch();
+    let time_of_day = seconds % 86_400;
+    format!(
+        "{}_{:02}{:02}{:02}",
+        date_from_seconds(seconds),
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60
+    )
+}
+
+fn seconds_since_epoch() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+fn date_from_seconds(seconds: u64) -> String {
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+// Convert a count of days since 1970-01-01 into a year, month & day.
+// This is Howard Hinnant's days to civil algorithm
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn high_score(score: u32) -> HighScore {
+        HighScore {
+            name: String::from("TEST"),
+            score,
+            lines: 0,
+            level: 1,
+            time: 0.0,
+            date: String::from("2024-01-01"),
+        }
+    }
+    #[test]
+    fn test_insert_keeps_order() {
+        let mut high_scores = HighScores::default();
+        assert_eq!(
+            high_scores.insert(GameMode::Classic, high_score(100)),
+            Some(0)
+        );
+        assert_eq!(
+            high_scores.insert(GameMode::Classic, high_score(300)),
+            Some(0)
+        );
+        assert_eq!(
+            high_scores.insert(GameMode::Classic, high_score(200)),
+            Some(1)
+        );
+        // a tie goes underneath the score that was already there
+        assert_eq!(
+            high_scores.insert(GameMode::Classic, high_score(200)),
+            Some(2)
+        );
+        let scores: Vec<u32> = high_scores
+            .get_table(GameMode::Classic)
+            .iter()
+            .map(|entry| entry.score)
+            .collect();
+        assert_eq!(scores, vec![300, 200, 200, 100]);
+    }
+    #[test]
+    fn test_table_is_capped() {
+        let mut high_scores = HighScores::default();
+        for score in 1..=HIGH_SCORE_TABLE_SIZE as u32 {
+            high_scores.insert(GameMode::Classic, high_score(score * 10));
+        }
+        assert!(!high_scores.qualifies(GameMode::Classic, &high_score(10)));
+        assert!(high_scores.qualifies(GameMode::Classic, &high_score(11)));
+        assert_eq!(high_scores.insert(GameMode::Classic, high_score(5)), None);
+        high_scores.insert(GameMode::Classic, high_score(1000));
+        let table = high_scores.get_table(GameMode::Classic);
+        assert_eq!(table.len(), HIGH_SCORE_TABLE_SIZE);
+        assert_eq!(table.last().unwrap().score, 20);
+    }
+    #[test]
+    fn test_zero_never_qualifies() {
+        assert!(!HighScores::default().qualifies(GameMode::Classic, &high_score(0)));
+    }
+    #[test]
+    fn test_sprints_are_ranked_by_time() {
+        let mut high_scores = HighScores::default();
+        let sprint = |time: f64| HighScore {
+            lines: 40,
+            time,
+            ..high_score(0)
+        };
+        assert_eq!(high_scores.insert(GameMode::Sprint, sprint(62.5)), Some(0));
+        assert_eq!(high_scores.insert(GameMode::Sprint, sprint(48.25)), Some(0));
+        assert_eq!(high_scores.insert(GameMode::Sprint, sprint(50.0)), Some(1));
+        assert!(!high_scores.qualifies(GameMode::Sprint, &sprint(0.0)));
+        let times: Vec<f64> = high_scores
+            .get_table(GameMode::Sprint)
+            .iter()
+            .map(|entry| entry.time)
+            .collect();
+        assert_eq!(times, vec![48.25, 50.0, 62.5]);
+        // and they have their own table
+        assert!(high_scores.get_table(GameMode::Classic).is_empty());
+    }
+    #[test]
+    fn test_round_trip() {
+        let mut high_scores = HighScores::default();
+        high_scores.insert(GameMode::Classic, high_score(500));
+        let text = storage::to_toml(&high_scores).unwrap();
+        assert_eq!(
+            storage::from_toml::<HighScores>(&text).unwrap(),
+            high_scores
+        );
+    }
+    fn pressed(action: Action) -> ActionState {
+        let mut actions = ActionState::new();
+        actions.set_pressed(action);
+        actions
+    }
+    #[test]
+    fn test_name_entry_typing() {
+        let mut entry = NameEntry::new();
+        assert!(!entry.update(&ActionState::new(), &['a', 'b', '!', 'c']));
+        assert_eq!(entry.get_name(), "ABC");
+        entry.update(&pressed(Action::Back), &[]);
+        assert_eq!(entry.get_name(), "AB");
+        assert!(entry.update(&pressed(Action::Confirm), &[]));
+    }
+    #[test]
+    fn test_name_entry_scrolling() {
+        let mut entry = NameEntry::new();
+        entry.update(&pressed(Action::MenuUp), &[]);
+        entry.update(&pressed(Action::MenuUp), &[]);
+        assert_eq!(entry.get_name(), "B");
+        entry.update(&pressed(Action::MoveRight), &[]);
+        entry.update(&pressed(Action::MenuDown), &[]);
+        assert_eq!(entry.get_name(), "B9");
+    }
+    #[test]
+    fn test_name_entry_length_and_default() {
+        let mut entry = NameEntry::new();
+        assert_eq!(entry.get_name(), DEFAULT_NAME);
+        let typed: Vec<char> = "ABCDEFGHIJKLMNOP".chars().collect();
+        entry.update(&ActionState::new(), &typed);
+        assert_eq!(entry.get_name().len(), MAX_NAME_LENGTH);
+    }
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_723), (2024, 1, 1));
+    }
+}
@@ -0,0 +1,199 @@
+#[cfg(feature = "macroquad")]
+use macroquad::input::{KeyCode, get_char_pressed, is_key_down, is_key_pressed};
+
+// Everything the player can ask the game to do. Keyboards and gamepads are
+// both translated into these so the game loop never cares where they came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    Rotate, // clockwise
+    RotateCounterClockwise,
+    Hold,
+    Undo, // takes the last piece back, in opener practice
+    Pause,
+    MenuUp,
+    MenuDown,
+    Confirm,
+    Back,
+}
+impl Action {
+    pub const ALL: [Action; 13] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::Rotate,
+        Action::RotateCounterClockwise,
+        Action::Hold,
+        Action::Undo,
+        Action::Pause,
+        Action::MenuUp,
+        Action::MenuDown,
+        Action::Confirm,
+        Action::Back,
+    ];
+    pub const COUNT: usize = Self::ALL.len();
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+// The state of every action for a single frame.
+// "down" is true for as long as the action is held,
+// "pressed" is only true on the frame it went down
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ActionState {
+    down: [bool; Action::COUNT],
+    pressed: [bool; Action::COUNT],
+}
+impl ActionState {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    #[must_use]
+    pub fn is_down(&self, action: Action) -> bool {
+        self.down[action.index()]
+    }
+    #[must_use]
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.pressed[action.index()]
+    }
+    pub fn set_down(&mut self, action: Action) {
+        self.down[action.index()] = true;
+    }
+    pub fn set_pressed(&mut self, action: Action) {
+        // a press always implies the action is down for this frame
+        self.down[action.index()] = true;
+        self.pressed[action.index()] = true;
+    }
+    #[must_use]
+    pub fn union(&self, other: &ActionState) -> ActionState {
+        // combine two devices - if either has the action down then it is down
+        let mut ret_state = *self;
+        for action in Action::ALL {
+            ret_state.down[action.index()] |= other.is_down(action);
+            ret_state.pressed[action.index()] |= other.is_pressed(action);
+        }
+        ret_state
+    }
+    // the same actions held down but with nothing newly pressed
+    #[must_use]
+    pub fn without_presses(&self) -> ActionState {
+        ActionState {
+            down: self.down,
+            pressed: [false; Action::COUNT],
+        }
+    }
+    // only the actions that were newly pressed
+    #[must_use]
+    pub fn only_presses(&self) -> ActionState {
+        ActionState {
+            down: self.pressed,
+            pressed: self.pressed,
+        }
+    }
+}
+
+// Anything that can be polled once a frame to find out what the player is doing
+pub trait InputSource {
+    fn poll(&mut self) -> ActionState;
+}
+
+#[cfg(feature = "macroquad")]
+pub struct KeyboardInput {
+    bindings: Vec<(KeyCode, Action)>,
+}
+#[cfg(feature = "macroquad")]
+impl Default for KeyboardInput {
+    fn default() -> Self {
+        Self::new(vec![
+            (KeyCode::Left, Action::MoveLeft),
+            (KeyCode::Right, Action::MoveRight),
+            (KeyCode::Down, Action::SoftDrop),
+            (KeyCode::Space, Action::HardDrop),
+            (KeyCode::Up, Action::Rotate),
+            (KeyCode::X, Action::Rotate),
+            (KeyCode::Z, Action::RotateCounterClockwise),
+            (KeyCode::C, Action::Hold),
+            (KeyCode::LeftShift, Action::Hold),
+            (KeyCode::U, Action::Undo),
+            (KeyCode::Backspace, Action::Undo),
+            (KeyCode::Escape, Action::Pause),
+            (KeyCode::P, Action::Pause),
+            (KeyCode::Up, Action::MenuUp),
+            (KeyCode::Down, Action::MenuDown),
+            (KeyCode::Enter, Action::Confirm),
+            (KeyCode::Space, Action::Confirm),
+            (KeyCode::Escape, Action::Back),
+            (KeyCode::Backspace, Action::Back),
+        ])
+    }
+}
+#[cfg(feature = "macroquad")]
+impl KeyboardInput {
+    #[must_use]
+    pub fn new(bindings: Vec<(KeyCode, Action)>) -> Self {
+        Self { bindings }
+    }
+    // any text typed since the last frame, for entering names
+    #[must_use]
+    pub fn typed_characters(&mut self) -> Vec<char> {
+        std::iter::from_fn(get_char_pressed).collect()
+    }
+}
+#[cfg(feature = "macroquad")]
+impl InputSource for KeyboardInput {
+    fn poll(&mut self) -> ActionState {
+        let mut ret_state = ActionState::new();
+        for (key, action) in &self.bindings {
+            if is_key_pressed(*key) {
+                ret_state.set_pressed(*action);
+            } else if is_key_down(*key) {
+                ret_state.set_down(*action);
+            }
+        }
+        ret_state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_pressed_implies_down() {
+        let mut state = ActionState::new();
+        state.set_pressed(Action::Rotate);
+        assert!(state.is_down(Action::Rotate));
+        assert!(state.is_pressed(Action::Rotate));
+        assert!(!state.is_down(Action::MoveLeft));
+    }
+    #[test]
+    fn test_union() {
+        let mut keyboard = ActionState::new();
+        keyboard.set_down(Action::MoveLeft);
+        let mut gamepad = ActionState::new();
+        gamepad.set_pressed(Action::Rotate);
+        let combined = keyboard.union(&gamepad);
+        assert!(combined.is_down(Action::MoveLeft));
+        assert!(!combined.is_pressed(Action::MoveLeft));
+        assert!(combined.is_pressed(Action::Rotate));
+        assert!(!combined.is_down(Action::SoftDrop));
+    }
+    #[test]
+    fn test_splitting_presses() {
+        let mut state = ActionState::new();
+        state.set_down(Action::MoveLeft);
+        state.set_pressed(Action::Hold);
+        let held = state.without_presses();
+        assert!(held.is_down(Action::MoveLeft) && held.is_down(Action::Hold));
+        assert!(!held.is_pressed(Action::Hold));
+        let presses = state.only_presses();
+        assert!(!presses.is_down(Action::MoveLeft));
+        assert!(presses.is_pressed(Action::Hold));
+    }
+}
@@ -0,0 +1,219 @@
+use crate::constants::{BOARD_HEIGHT, BOARD_WIDTH};
+
+// The block size the screens were first laid out for. Text & gaps are given at this
+// size and scaled along with the blocks
+pub const DESIGN_CELL: f32 = 30.0;
+// The menus were laid out for a window this big, they're scaled to fit the real one
+pub const DESIGN_WIDTH: f32 = 800.0;
+pub const DESIGN_HEIGHT: f32 = 600.0;
+
+// The game screen is a grid of squares the size of the board's: a margin, the left
+// panel, a gap, the board, a gap, the right panel & a margin across, and the board
+// with a margin above & below it down
+const PANEL_COLUMNS: f32 = 8.0;
+const MARGIN: f32 = 1.0;
+const COLUMNS: f32 = BOARD_WIDTH as f32 + (PANEL_COLUMNS + MARGIN * 2.0) * 2.0;
+const ROWS: f32 = BOARD_HEIGHT as f32 + MARGIN * 2.0;
+// how the panels are split up, in squares
+const HOLD_ROWS: f32 = 3.0;
+const NEXT_ROWS: f32 = 13.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+impl Rect {
+    #[must_use]
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+    #[must_use]
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+    #[must_use]
+    pub fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+    #[must_use]
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.x < other.right()
+            && other.x < self.right()
+            && self.y < other.bottom()
+            && other.y < self.bottom()
+    }
+    #[must_use]
+    pub fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.right() <= self.right()
+            && other.bottom() <= self.bottom()
+    }
+}
+
+// Where everything goes for a window of a given size. The board is kept in the middle
+// with the panels either side, all scaled together so nothing overlaps whatever shape
+// the window is
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub cell: f32,    // size of one square of the board, in whole pixels
+    pub board: Rect,  // the board including its walls
+    pub hold: Rect,   // left of the board, at the top
+    pub stats: Rect,  // score & statistics, under the hold piece
+    pub next: Rect,   // right of the board, at the top
+    pub info: Rect,   // under the next pieces, for replay controls
+    pub screen: Rect, // the whole window
+    pub menu: Rect,   // the area the menus are scaled into, 4:3 in the middle
+}
+impl Layout {
+    // max_cell is the biggest the squares are allowed to get, however big the window
+    #[must_use]
+    pub fn new(width: f32, height: f32, max_cell: f32) -> Self {
+        let cell = (width / COLUMNS)
+            .min(height / ROWS)
+            .min(max_cell)
+            .floor()
+            .max(1.0);
+        // the grid in the middle of the window, on whole pixels
+        let left = ((width - COLUMNS * cell) / 2.0).floor();
+        let top = ((height - ROWS * cell) / 2.0).floor();
+        let area = |column: f32, row: f32, columns: f32, rows: f32| {
+            Rect::new(
+                left + column * cell,
+                top + row * cell,
+                columns * cell,
+                rows * cell,
+            )
+        };
+        let board_column = MARGIN * 2.0 + PANEL_COLUMNS;
+        let right_column = board_column + BOARD_WIDTH as f32 + MARGIN;
+        let board_rows = BOARD_HEIGHT as f32;
+
+        let menu_scale = (width / DESIGN_WIDTH).min(height / DESIGN_HEIGHT);
+        let menu_width = DESIGN_WIDTH * menu_scale;
+        let menu_height = DESIGN_HEIGHT * menu_scale;
+        Self {
+            cell,
+            board: area(board_column, MARGIN, BOARD_WIDTH as f32, board_rows),
+            hold: area(MARGIN, MARGIN, PANEL_COLUMNS, HOLD_ROWS),
+            stats: area(
+                MARGIN,
+                MARGIN + HOLD_ROWS + 1.0,
+                PANEL_COLUMNS,
+                board_rows - HOLD_ROWS - 1.0,
+            ),
+            next: area(right_column, MARGIN, PANEL_COLUMNS, NEXT_ROWS),
+            info: area(
+                right_column,
+                MARGIN + NEXT_ROWS + 1.0,
+                PANEL_COLUMNS,
+                board_rows - NEXT_ROWS - 1.0,
+            ),
+            screen: Rect::new(0.0, 0.0, width, height),
+            menu: Rect::new(
+                (width - menu_width) / 2.0,
+                (height - menu_height) / 2.0,
+                menu_width,
+                menu_height,
+            ),
+        }
+    }
+    // a size picked for DESIGN_CELL sized squares, at the size they really are
+    #[must_use]
+    pub fn scaled(&self, design: f32) -> f32 {
+        design * self.cell / DESIGN_CELL
+    }
+    #[must_use]
+    pub fn menu_scale(&self) -> f32 {
+        self.menu.width / DESIGN_WIDTH
+    }
+    #[must_use]
+    pub fn panels(&self) -> [Rect; 5] {
+        [self.board, self.hold, self.stats, self.next, self.info]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_fits(layout: &Layout) {
+        let panels = layout.panels();
+        for (index, panel) in panels.iter().enumerate() {
+            assert!(
+                layout.screen.contains(panel),
+                "{panel:?} is outside {:?}",
+                layout.screen
+            );
+            for other in &panels[index + 1..] {
+                assert!(!panel.overlaps(other), "{panel:?} overlaps {other:?}");
+            }
+        }
+        assert!(layout.screen.contains(&layout.menu));
+        assert!((layout.cell - layout.cell.round()).abs() < f32::EPSILON);
+        // the board is in the middle, give or take rounding to whole pixels
+        let middle = layout.board.x + layout.board.width / 2.0;
+        assert!((middle - layout.screen.width / 2.0).abs() <= 1.0);
+        // and the hold & next panels are either side of it
+        assert!(layout.hold.right() <= layout.board.x);
+        assert!(layout.next.x >= layout.board.right());
+    }
+    #[test]
+    fn test_original_window_size() {
+        let layout = Layout::new(800.0, 600.0, 100.0);
+        assert_fits(&layout);
+        assert!((layout.cell - 25.0).abs() < f32::EPSILON);
+        assert!((layout.menu_scale() - 1.0).abs() < f32::EPSILON);
+    }
+    #[test]
+    fn test_aspect_ratios() {
+        for (width, height) in [
+            (1920.0, 1080.0),
+            (1080.0, 1920.0),
+            (3440.0, 1440.0),
+            (1024.0, 768.0),
+            (600.0, 600.0),
+            (2560.0, 1600.0),
+            (333.0, 777.0),
+        ] {
+            let layout = Layout::new(width, height, 100.0);
+            assert_fits(&layout);
+            // the squares are as big as will fit one way or the other
+            let across = layout.cell * COLUMNS;
+            let down = layout.cell * ROWS;
+            assert!(
+                width - across < COLUMNS || height - down < ROWS,
+                "{width}x{height} could fit bigger squares than {}",
+                layout.cell
+            );
+        }
+    }
+    #[test]
+    fn test_wide_windows_centre_the_grid() {
+        let layout = Layout::new(3000.0, 600.0, 100.0);
+        assert_fits(&layout);
+        // height decides the size, so there's space either side
+        assert!((layout.cell - 30.0).abs() < f32::EPSILON);
+        assert!((layout.hold.x - (3000.0 - 960.0) / 2.0 - 30.0).abs() < f32::EPSILON);
+        assert!((layout.menu.x - 1100.0).abs() < f32::EPSILON);
+    }
+    #[test]
+    fn test_squares_have_a_largest_and_smallest_size() {
+        let layout = Layout::new(7680.0, 4320.0, 40.0);
+        assert_fits(&layout);
+        assert!((layout.cell - 40.0).abs() < f32::EPSILON);
+        assert!((layout.scaled(24.0) - 32.0).abs() < f32::EPSILON);
+        // too small to play on, but still drawn somewhere
+        let tiny = Layout::new(10.0, 10.0, 40.0);
+        assert!((tiny.cell - 1.0).abs() < f32::EPSILON);
+        assert!(tiny.board.width > 0.0);
+    }
+}
@@ -0,0 +1,72 @@
+use macroquad::Error;
+use macroquad::audio::{
+    PlaySoundParams, Sound as LoadedSound, load_sound_from_bytes, play_sound, set_sound_volume,
+    stop_sound,
+};
+
+use crate::audio::{AudioBackend, MUSIC_TEMPOS, Sound};
+use crate::synth;
+
+// Plays the synthesised sounds through macroquad. Every sound & each speed of the
+// music is made up front, as macroquad can only change a sound's volume once loaded
+pub struct MacroquadAudio {
+    sounds: Vec<LoadedSound>, // in the order of Sound::ALL
+    music: Vec<LoadedSound>,  // in the order of MUSIC_TEMPOS
+    playing: Option<usize>,   // which speed of the music is on
+}
+impl MacroquadAudio {
+    pub async fn load() -> Result<Self, Error> {
+        let mut sounds = Vec::new();
+        for sound in Sound::ALL {
+            sounds.push(load_sound_from_bytes(&synth::sound_wav(sound)).await?);
+        }
+        let mut music = Vec::new();
+        for tempo in MUSIC_TEMPOS {
+            music.push(load_sound_from_bytes(&synth::music_wav(tempo)).await?);
+        }
+        Ok(Self {
+            sounds,
+            music,
+            playing: None,
+        })
+    }
+}
+impl AudioBackend for MacroquadAudio {
+    fn play_sound(&mut self, sound: Sound, volume: f32) {
+        let index = Sound::ALL
+            .iter()
+            .position(|each| *each == sound)
+            .unwrap_or(0);
+        play_sound(
+            &self.sounds[index],
+            PlaySoundParams {
+                looped: false,
+                volume,
+            },
+        );
+    }
+    fn play_music(&mut self, volume: f32, tempo: f32) {
+        let index = MUSIC_TEMPOS
+            .iter()
+            .position(|each| (each - tempo).abs() < f32::EPSILON)
+            .unwrap_or(0);
+        if self.playing == Some(index) {
+            set_sound_volume(&self.music[index], volume);
+            return;
+        }
+        self.stop_music();
+        play_sound(
+            &self.music[index],
+            PlaySoundParams {
+                looped: true,
+                volume,
+            },
+        );
+        self.playing = Some(index);
+    }
+    fn stop_music(&mut self) {
+        if let Some(index) = self.playing.take() {
+            stop_sound(&self.music[index]);
+        }
+    }
+}
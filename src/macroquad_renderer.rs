@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use macroquad::color::{Color, WHITE};
+use macroquad::math::{Rect, vec2};
+use macroquad::shapes::draw_rectangle;
+use macroquad::text::{draw_text, measure_text};
+use macroquad::texture::{DrawTextureParams, FilterMode, Texture2D, draw_texture_ex};
+use macroquad::window::{screen_height, screen_width};
+
+use crate::render::{Colour, Renderer};
+use crate::theme::Skin;
+
+// Draws straight onto the macroquad window
+#[derive(Default)]
+pub struct MacroquadRenderer {
+    // skins uploaded to the GPU, by skin id so each is only uploaded once
+    skin_textures: HashMap<u64, Texture2D>,
+}
+
+fn to_color(colour: Colour) -> Color {
+    Color::new(colour.r, colour.g, colour.b, colour.a)
+}
+
+impl Renderer for MacroquadRenderer {
+    fn draw_cell(&mut self, x: f32, y: f32, size: f32, colour: Colour) {
+        draw_rectangle(x, y, size, size, to_color(colour));
+    }
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, font_size: f32, colour: Colour) {
+        draw_text(text, x, y, font_size, to_color(colour));
+    }
+    fn draw_panel(&mut self, x: f32, y: f32, width: f32, height: f32, colour: Colour) {
+        draw_rectangle(x, y, width, height, to_color(colour));
+    }
+    fn measure_text(&self, text: &str, font_size: f32) -> f32 {
+        measure_text(text, None, font_size as u16, 1.0).width
+    }
+    fn get_size(&self) -> (f32, f32) {
+        (screen_width(), screen_height())
+    }
+    fn draw_skin_cell(&mut self, skin: &Skin, tile: u32, x: f32, y: f32, size: f32) {
+        let texture = self.skin_textures.entry(skin.get_id()).or_insert_with(|| {
+            let texture = Texture2D::from_rgba8(
+                skin.get_width() as u16,
+                skin.get_tile_size() as u16,
+                skin.get_pixels(),
+            );
+            // keep pixel art sharp when it's scaled up
+            texture.set_filter(FilterMode::Nearest);
+            texture
+        });
+        let tile_size = skin.get_tile_size() as f32;
+        draw_texture_ex(
+            texture,
+            x,
+            y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(size, size)),
+                source: Some(Rect::new(
+                    tile as f32 * tile_size,
+                    0.0,
+                    tile_size,
+                    tile_size,
+                )),
+                ..Default::default()
+            },
+        );
+    }
+}
@@ -1,94 +1,126 @@
 use macroquad::prelude::*;
-use rust_tetris::{
-    Direction, draw_board, draw_game_over_message, draw_score, draw_tetromino, spawn_tetromino,
-};
 
-use rust_tetris::board::Board;
-use rust_tetris::constants::{SCORE_COMPLETED_LINES_INCREMENT, SCORE_INCREMENT, SPEED};
-use rust_tetris::tetromino::Tetromino;
+use rust_tetris::audio::Audio;
+use rust_tetris::effects::Effects;
+#[cfg(feature = "gamepad")]
+use rust_tetris::gamepad::{GamepadInput, GamepadMapping, GilrsEventSource};
+use rust_tetris::highscores::HighScores;
+use rust_tetris::input::{ActionState, InputSource, KeyboardInput};
+#[cfg(feature = "audio")]
+use rust_tetris::macroquad_audio::MacroquadAudio;
+use rust_tetris::macroquad_renderer::MacroquadRenderer;
+use rust_tetris::opener::Opener;
+use rust_tetris::puzzle::Puzzle;
+use rust_tetris::render::draw_screen;
+use rust_tetris::replay::Replay;
+use rust_tetris::save::SavedGame;
+use rust_tetris::screen::{Context, ScreenStack};
+use rust_tetris::settings::Settings;
+use rust_tetris::theme::Theme;
 
-#[macroquad::main("Rust Tetris")]
-async fn main() {
-    let mut board = Board::new();
-    let mut score: u32 = 0;
-    let mut current_tetromino: Tetromino;
-    let mut last_update = get_time();
-    let mut force_down: bool = false;
-    let mut navigation_lock: bool = false;
-    let mut game_over: bool = false;
-    let mut game_speed: f64 = SPEED;
-
-    current_tetromino = spawn_tetromino();
-
-    loop {
-        if is_key_down(KeyCode::Left) && !navigation_lock {
-            if board.can_piece_move(current_tetromino, Direction::Left) {
-                current_tetromino.move_left();
-            }
-            navigation_lock = true;
-        }
-        if is_key_down(KeyCode::Right) && !navigation_lock {
-            if board.can_piece_move(current_tetromino, Direction::Right) {
-                current_tetromino.move_right();
+// All the input devices, polled together each frame
+struct Controls {
+    keyboard: KeyboardInput,
+    #[cfg(feature = "gamepad")]
+    gamepad: Option<GamepadInput<GilrsEventSource>>,
+}
+impl Controls {
+    fn new(settings: &Settings) -> Self {
+        #[cfg(feature = "gamepad")]
+        let gamepad = match GilrsEventSource::new() {
+            Ok(source) => Some(GamepadInput::new(
+                source,
+                GamepadMapping::default().with_deadzone(settings.handling.gamepad_deadzone),
+            )),
+            Err(error) => {
+                warn!("No gamepad support: {error}");
+                None
             }
-            navigation_lock = true;
+        };
+        #[cfg(not(feature = "gamepad"))]
+        let _ = settings;
+        Self {
+            keyboard: KeyboardInput::default(),
+            #[cfg(feature = "gamepad")]
+            gamepad,
         }
-        if is_key_down(KeyCode::Down) && !navigation_lock {
-            if board.can_piece_move(current_tetromino, Direction::Down) {
-                current_tetromino.move_down();
-            }
-            navigation_lock = true;
+    }
+    #[cfg_attr(not(feature = "gamepad"), allow(clippy::unused_self))]
+    fn apply_settings(&mut self, settings: &Settings) {
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepad) = self.gamepad.as_mut() {
+            gamepad
+                .get_mapping_mut()
+                .set_deadzone(settings.handling.gamepad_deadzone);
         }
-        if is_key_down(KeyCode::Space) && game_over {
-            score = 0;
-            board = Board::new();
-            current_tetromino = spawn_tetromino();
-            game_over = false;
+        #[cfg(not(feature = "gamepad"))]
+        let _ = settings;
+    }
+    fn typed_characters(&mut self) -> Vec<char> {
+        self.keyboard.typed_characters()
+    }
+    fn poll(&mut self) -> ActionState {
+        let actions = self.keyboard.poll();
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepad) = self.gamepad.as_mut() {
+            return actions.union(&gamepad.poll());
         }
+        actions
+    }
+}
 
-        if is_key_pressed(KeyCode::Up) && board.can_piece_rotate(current_tetromino) {
-            current_tetromino.rotate();
-            navigation_lock = true;
-        }
+// Without the audio feature, or a sound card, the game plays silently
+#[cfg_attr(not(feature = "audio"), allow(clippy::unused_async))]
+async fn load_audio() -> Audio {
+    #[cfg(feature = "audio")]
+    match MacroquadAudio::load().await {
+        Ok(backend) => return Audio::new(Box::new(backend)),
+        Err(error) => warn!("No sound: {error}"),
+    }
+    Audio::default()
+}
 
-        if get_time() - last_update > game_speed {
-            last_update = get_time();
-            force_down = true;
-            navigation_lock = false;
-            board.remove_filled_lines();
-        }
+#[macroquad::main("Rust Tetris")]
+async fn main() {
+    let mut context = Context {
+        settings: Settings::load().unwrap_or_else(|error| {
+            warn!("Using default settings: {error}");
+            Settings::default()
+        }),
+        high_scores: HighScores::load().unwrap_or_else(|error| {
+            warn!("Could not load high scores: {error}");
+            HighScores::default()
+        }),
+        themes: Theme::load_all(Theme::default_directory().as_deref()),
+        puzzles: Puzzle::load_all(Puzzle::default_directory().as_deref()),
+        openers: Opener::built_in(),
+        effects: Effects::new(),
+        audio: load_audio().await,
+        replay_directory: Replay::default_directory(),
+        save_path: SavedGame::default_path(),
+    };
+    let mut controls = Controls::new(&context.settings);
+    let mut screens = ScreenStack::new(&context);
+    let mut renderer = MacroquadRenderer::default();
+    // closing the window saves the game in progress rather than losing it
+    prevent_quit();
 
-        if force_down {
-            if board.can_piece_move(current_tetromino, Direction::Down) {
-                current_tetromino.move_down();
-                force_down = false;
-            } else {
-                force_down = false;
-                board.lock_tetromino_in_place(current_tetromino);
-                if board.get_filled_lines().len() == 4 {
-                    score += SCORE_COMPLETED_LINES_INCREMENT;
-                } else if !board.get_filled_lines().is_empty() {
-                    score += SCORE_INCREMENT;
-                }
-                // increment the score if we have completed a line & we are on a multiple of 100
-                // this score increment may need to be tweaked
-                if !board.get_filled_lines().is_empty() && score % 100 == 0 && score != 0 {
-                    game_speed -= 0.01;
-                }
-                board.colour_in_filled_lines();
-                if !game_over {
-                    current_tetromino = spawn_tetromino();
-                    if !board.can_piece_move(current_tetromino, Direction::Down) {
-                        game_over = true;
-                    }
-                }
-            }
+    loop {
+        if is_quit_requested() {
+            screens.suspend(&context);
+            break;
+        }
+        let delta_time = f64::from(get_frame_time());
+        let actions = controls.poll();
+        let typed = controls.typed_characters();
+        if !screens.update(&actions, &typed, &mut context, delta_time) {
+            break;
         }
-        draw_board(&board);
-        draw_tetromino(&mut current_tetromino);
-        draw_score(score);
-        if game_over {
-            draw_game_over_message();
+        // the options screen may have changed the deadzone
+        controls.apply_settings(&context.settings);
+
+        for screen in screens.visible() {
+            draw_screen(&mut renderer, screen, &context);
         }
         next_frame().await;
     }
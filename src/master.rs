@@ -0,0 +1,336 @@
+use serde::{Deserialize, Serialize};
+
+// Master mode plays like the arcade Tetris: The Grand Master. Everything is counted in
+// ticks rather than seconds, the level goes up with every piece & line, the delays get
+// shorter each section and the game ends with a grade.
+pub const MASTER_LEVELS: u32 = 999;
+pub const SECTION_LEVELS: u32 = 100;
+// Gravity is in 256ths of a row per tick, so 256 is a row every tick (1G) and 20G drops a
+// piece straight to the bottom
+const ROW: u32 = 256;
+pub const TWENTY_G: u32 = 20 * ROW;
+// (from level, gravity), the same steps as the arcade game
+const GRAVITY: [(u32, u32); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4), // a breather before it really starts
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, TWENTY_G),
+];
+
+// How long things take in a section, in ticks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Delays {
+    pub are: u32,        // between a piece locking & the next coming in
+    pub lock: u32,       // a piece can sit on the ground this long before it locks
+    pub line_clear: u32, // filled rows stay up this long before they go
+}
+// (from level, delays). Once everything falls at 20G the delays are all that's left
+// to speed up
+const DELAYS: [(u32, Delays); 6] = [
+    (0, delays(25, 30, 40)),
+    (500, delays(25, 30, 25)),
+    (600, delays(16, 30, 16)),
+    (700, delays(12, 30, 12)),
+    (800, delays(6, 30, 6)),
+    (900, delays(6, 17, 6)),
+];
+const fn delays(are: u32, lock: u32, line_clear: u32) -> Delays {
+    Delays {
+        are,
+        lock,
+        line_clear,
+    }
+}
+
+// The grades and the score needed for each, lowest first
+const GRADES: [(&str, u32); 18] = [
+    ("9", 0),
+    ("8", 400),
+    ("7", 800),
+    ("6", 1400),
+    ("5", 2000),
+    ("4", 3500),
+    ("3", 5500),
+    ("2", 8000),
+    ("1", 12000),
+    ("S1", 16000),
+    ("S2", 22000),
+    ("S3", 30000),
+    ("S4", 40000),
+    ("S5", 52000),
+    ("S6", 66000),
+    ("S7", 82000),
+    ("S8", 100_000),
+    ("S9", 120_000),
+];
+// Grand Master needs S9's score and then some, and each of these levels reached in
+// time (in seconds): 300 by 4:15, 500 by 7:30 and the end by 13:30
+const GRAND_MASTER_SCORE: u32 = 126_000;
+const GRAND_MASTER_TIMES: [(u32, f64); 3] = [(300, 255.0), (500, 450.0), (999, 810.0)];
+
+#[must_use]
+pub fn gravity(level: u32) -> u32 {
+    GRAVITY
+        .iter()
+        .rev()
+        .find(|(from, _)| level >= *from)
+        .map_or(GRAVITY[0].1, |(_, gravity)| *gravity)
+}
+#[must_use]
+pub fn get_delays(level: u32) -> Delays {
+    DELAYS
+        .iter()
+        .rev()
+        .find(|(from, _)| level >= *from)
+        .map_or(DELAYS[0].1, |(_, delays)| *delays)
+}
+// which section a level is in. Reaching 999 counts as a section of its own
+#[must_use]
+pub fn section(level: u32) -> u32 {
+    if level >= MASTER_LEVELS {
+        MASTER_LEVELS / SECTION_LEVELS + 1
+    } else {
+        level / SECTION_LEVELS
+    }
+}
+
+// What happened while waiting between pieces
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wait {
+    Waiting,
+    ClearLines, // the line clear delay is over
+    Spawn,      // time for the next piece
+}
+
+// Everything Master mode keeps track of on top of an ordinary game
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MasterState {
+    level: u32,
+    gravity: u32,    // 256ths of a row built up towards the next fall
+    lock: u32,       // ticks the piece has been on the ground
+    line_clear: u32, // ticks until the filled rows go
+    are: u32,        // ticks until the next piece comes in
+    combo: u32,
+    // when each hundred levels were passed, and the end, in seconds
+    section_times: Vec<f64>,
+}
+impl Default for MasterState {
+    fn default() -> Self {
+        Self {
+            level: 0,
+            gravity: 0,
+            lock: 0,
+            line_clear: 0,
+            are: 0,
+            combo: 1,
+            section_times: Vec::new(),
+        }
+    }
+}
+impl MasterState {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    #[must_use]
+    pub fn get_level(&self) -> u32 {
+        self.level
+    }
+    // the level the current section stops at until a line is cleared
+    #[must_use]
+    pub fn get_section_end(&self) -> u32 {
+        ((self.level / SECTION_LEVELS + 1) * SECTION_LEVELS).min(MASTER_LEVELS)
+    }
+    #[must_use]
+    pub fn get_section_times(&self) -> &[f64] {
+        &self.section_times
+    }
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.level >= MASTER_LEVELS
+    }
+    // between one piece locking & the next coming in
+    #[must_use]
+    pub fn is_waiting(&self) -> bool {
+        self.line_clear > 0 || self.are > 0
+    }
+    // A new piece adds a level, apart from at the end of a section where only
+    // clearing lines will move it on
+    fn piece_spawned(&mut self) {
+        if self.level % SECTION_LEVELS != SECTION_LEVELS - 1 && self.level < MASTER_LEVELS - 1 {
+            self.level += 1;
+        }
+    }
+    // Adds the lines to the level and gives the points for them. Clears in a row build
+    // up a combo that multiplies the points
+    pub fn lines_cleared(&mut self, lines: u32, time: f64) -> u32 {
+        self.combo += 2 * lines - 2;
+        let ret_points = (self.level + lines).div_ceil(4) * lines * self.combo;
+        let section_before = section(self.level);
+        self.level = (self.level + lines).min(MASTER_LEVELS);
+        if section(self.level) != section_before {
+            self.section_times.push(time);
+        }
+        ret_points
+    }
+    // the piece has locked, so start waiting for the next one
+    pub fn piece_locked(&mut self, cleared_lines: bool) {
+        let delays = get_delays(self.level);
+        if cleared_lines {
+            self.line_clear = delays.line_clear;
+        } else {
+            self.combo = 1;
+        }
+        self.are = delays.are;
+    }
+    pub fn wait(&mut self) -> Wait {
+        if self.line_clear > 0 {
+            self.line_clear -= 1;
+            return if self.line_clear == 0 {
+                Wait::ClearLines
+            } else {
+                Wait::Waiting
+            };
+        }
+        self.are = self.are.saturating_sub(1);
+        if self.are == 0 {
+            self.piece_spawned();
+            Wait::Spawn
+        } else {
+            Wait::Waiting
+        }
+    }
+    // how many rows the piece should fall this tick
+    pub fn fall(&mut self) -> u32 {
+        self.gravity += gravity(self.level);
+        let ret_rows = self.gravity / ROW;
+        self.gravity %= ROW;
+        ret_rows
+    }
+    // the piece moved down so it gets its full lock delay again
+    pub fn reset_lock(&mut self) {
+        self.lock = 0;
+    }
+    // another tick on the ground, true once it's time to lock
+    pub fn on_ground(&mut self) -> bool {
+        self.lock += 1;
+        self.lock >= get_delays(self.level).lock
+    }
+    #[must_use]
+    pub fn get_grade(&self, score: u32) -> &'static str {
+        // a section's time is pushed as it's left, so the nth is when section n started
+        let reached_in_time = GRAND_MASTER_TIMES.iter().all(|(level, limit)| {
+            self.section_times
+                .get(section(*level) as usize - 1)
+                .is_some_and(|time| time <= limit)
+        });
+        if score >= GRAND_MASTER_SCORE && reached_in_time {
+            return "GM";
+        }
+        GRADES
+            .iter()
+            .rev()
+            .find(|(_, needed)| score >= *needed)
+            .map_or(GRADES[0].0, |(grade, _)| *grade)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speeds_up_to_twenty_g() {
+        assert_eq!(gravity(0), 4);
+        assert_eq!(gravity(199), 144);
+        assert_eq!(gravity(200), 4);
+        assert_eq!(gravity(251), ROW);
+        assert_eq!(gravity(500), TWENTY_G);
+        assert_eq!(gravity(999), TWENTY_G);
+        assert_eq!(get_delays(0), delays(25, 30, 40));
+        assert_eq!(get_delays(650), delays(16, 30, 16));
+        assert_eq!(get_delays(999).lock, 17);
+        // 20G falls further than the board is tall in one go
+        let mut state = MasterState {
+            level: 500,
+            ..MasterState::new()
+        };
+        assert_eq!(state.fall(), 20);
+    }
+    #[test]
+    fn test_levels_stop_at_the_end_of_a_section() {
+        let mut state = MasterState {
+            level: 97,
+            ..MasterState::new()
+        };
+        state.piece_spawned();
+        state.piece_spawned();
+        state.piece_spawned();
+        assert_eq!(state.get_level(), 99);
+        assert_eq!(state.get_section_end(), 100);
+        assert_eq!(state.lines_cleared(2, 60.0), 26 * 2 * 3);
+        assert_eq!(state.get_level(), 101);
+        assert_eq!(state.get_section_times(), &[60.0]);
+        // only lines can finish the game
+        state.level = 998;
+        state.piece_spawned();
+        assert_eq!(state.get_level(), 998);
+        state.lines_cleared(4, 700.0);
+        assert!(state.is_finished());
+        assert_eq!(state.get_section_end(), MASTER_LEVELS);
+    }
+    #[test]
+    fn test_waits_for_the_lines_then_the_next_piece() {
+        let mut state = MasterState::new();
+        state.piece_locked(true);
+        let waits: Vec<Wait> =
+            std::iter::from_fn(|| state.is_waiting().then(|| state.wait())).collect();
+        assert_eq!(waits.len(), 40 + 25);
+        assert_eq!(waits[39], Wait::ClearLines);
+        assert_eq!(waits.last(), Some(&Wait::Spawn));
+        assert_eq!(
+            waits.iter().filter(|wait| **wait == Wait::Waiting).count(),
+            63
+        );
+    }
+    #[test]
+    fn test_grades() {
+        let mut state = MasterState::new();
+        assert_eq!(state.get_grade(0), "9");
+        assert_eq!(state.get_grade(12000), "1");
+        assert_eq!(state.get_grade(130_000), "S9");
+        // Grand Master needs the times as well as the score
+        state.section_times = vec![
+            60.0, 120.0, 250.0, 350.0, 440.0, 500.0, 600.0, 650.0, 700.0, 800.0,
+        ];
+        assert_eq!(state.get_grade(130_000), "GM");
+        state.section_times[2] = 260.0;
+        assert_eq!(state.get_grade(130_000), "S9");
+    }
+}
@@ -0,0 +1,88 @@
+use crate::input::{Action, ActionState};
+
+// A vertical list of choices. Up & down move the highlight (wrapping round at the ends)
+// and confirm hands back whatever value is attached to the highlighted entry
+#[derive(Clone, Debug)]
+pub struct Menu<T: Copy> {
+    items: Vec<(String, T)>,
+    selected: usize,
+}
+impl<T: Copy> Menu<T> {
+    #[must_use]
+    pub fn new(items: Vec<(String, T)>) -> Self {
+        Self { items, selected: 0 }
+    }
+    #[must_use]
+    pub fn get_items(&self) -> &[(String, T)] {
+        &self.items
+    }
+    #[must_use]
+    pub fn get_selected_index(&self) -> usize {
+        self.selected
+    }
+    #[must_use]
+    pub fn get_selected(&self) -> Option<T> {
+        self.items.get(self.selected).map(|(_, value)| *value)
+    }
+    pub fn select_next(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + 1) % self.items.len();
+        }
+    }
+    pub fn select_previous(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+        }
+    }
+    pub fn update(&mut self, actions: &ActionState) -> Option<T> {
+        if actions.is_pressed(Action::MenuUp) {
+            self.select_previous();
+        }
+        if actions.is_pressed(Action::MenuDown) {
+            self.select_next();
+        }
+        if actions.is_pressed(Action::Confirm) {
+            return self.get_selected();
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn menu() -> Menu<u32> {
+        Menu::new(vec![
+            (String::from("One"), 1),
+            (String::from("Two"), 2),
+            (String::from("Three"), 3),
+        ])
+    }
+    #[test]
+    fn test_navigation_wraps() {
+        let mut menu = menu();
+        let mut actions = ActionState::new();
+        actions.set_pressed(Action::MenuUp);
+        assert_eq!(menu.update(&actions), None);
+        assert_eq!(menu.get_selected(), Some(3));
+        let mut actions = ActionState::new();
+        actions.set_pressed(Action::MenuDown);
+        menu.update(&actions);
+        assert_eq!(menu.get_selected(), Some(1));
+    }
+    #[test]
+    fn test_confirm_returns_selection() {
+        let mut menu = menu();
+        menu.select_next();
+        let mut actions = ActionState::new();
+        actions.set_pressed(Action::Confirm);
+        assert_eq!(menu.update(&actions), Some(2));
+    }
+    #[test]
+    fn test_held_confirm_does_nothing() {
+        let mut menu = menu();
+        let mut actions = ActionState::new();
+        actions.set_down(Action::Confirm);
+        assert_eq!(menu.update(&actions), None);
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::constants::{BOARD_HEIGHT, BOARD_WIDTH};
+use crate::coordinate::{ICoordinate, UCoordinate};
+use crate::storage;
+use crate::tetromino::{Tetromino, TetrominoShape};
+use crate::warning;
+
+// the openers that come with the game, in the order they're listed
+const BUILT_IN: [&str; 3] = [
+    include_str!("../openers/tki.toml"),
+    include_str!("../openers/dt_cannon.toml"),
+    include_str!("../openers/pco.toml"),
+];
+
+// An opener as it's written in its file: the pieces in the order they're dealt and
+// the board once they're all down, written the way Board writes itself out
+#[derive(Serialize, Deserialize)]
+struct OpenerFile {
+    name: String,
+    #[serde(default)]
+    description: String,
+    order: String,
+    template: String,
+}
+
+// Where one piece of an opener has to end up
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Target {
+    pub shape: TetrominoShape,
+    cells: Vec<(i32, i32)>, // the board squares it covers, sorted
+}
+impl Target {
+    fn new(tetromino: &Tetromino) -> Self {
+        Self {
+            shape: tetromino.get_shape(),
+            cells: sorted(&tetromino.get_cells()),
+        }
+    }
+    #[must_use]
+    pub fn get_cells(&self) -> Vec<ICoordinate> {
+        self.cells
+            .iter()
+            .map(|(x, y)| ICoordinate::new(*x, *y))
+            .collect()
+    }
+    // the piece as it has to be placed
+    #[must_use]
+    pub fn get_tetromino(&self) -> Option<Tetromino> {
+        Tetromino::covering(self.shape, &self.get_cells())
+    }
+    #[must_use]
+    pub fn is_covered_by(&self, tetromino: &Tetromino) -> bool {
+        tetromino.get_shape() == self.shape && sorted(&tetromino.get_cells()) == self.cells
+    }
+}
+fn sorted(cells: &[ICoordinate]) -> Vec<(i32, i32)> {
+    let mut ret_cells: Vec<(i32, i32)> = cells.iter().map(|cell| (cell.x, cell.y)).collect();
+    ret_cells.sort_unstable();
+    ret_cells
+}
+
+// The first few pieces of a game placed a known way, like a TKI or a PCO. The bag
+// deals them in the opener's order so it can always be built, and each one is checked
+// against where the template has it as it locks
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "OpenerFile", into = "OpenerFile")]
+pub struct Opener {
+    pub name: String,
+    pub description: String,
+    pub order: Vec<TetrominoShape>,
+    pub targets: Vec<Target>, // in the same order
+}
+impl TryFrom<OpenerFile> for Opener {
+    type Error = String;
+    fn try_from(file: OpenerFile) -> Result<Self, Self::Error> {
+        let order = file
+            .order
+            .chars()
+            .filter(|letter| !letter.is_whitespace())
+            .map(|letter| {
+                TetrominoShape::from_letter(letter)
+                    .ok_or_else(|| format!("there is no {letter} piece"))
+            })
+            .collect::<Result<Vec<TetrominoShape>, String>>()?;
+        if order.is_empty() {
+            return Err(String::from("an opener needs at least one piece"));
+        }
+        let board: Board = file.template.parse().map_err(|error| format!("{error}"))?;
+        let mut squares = template_squares(&board)?;
+        let mut targets = Vec::new();
+        for shape in &order {
+            let letter = shape.letter();
+            // a piece's squares can only be told apart by colour, so one of each
+            let cells = squares
+                .remove(shape)
+                .ok_or_else(|| format!("there is no {letter} in the template, or more than one"))?;
+            let tetromino = Tetromino::covering(*shape, &cells).ok_or_else(|| {
+                format!("the {letter} squares in the template aren't an {letter}")
+            })?;
+            targets.push(Target::new(&tetromino));
+        }
+        if let Some(shape) = squares.keys().next() {
+            return Err(format!(
+                "the {} in the template isn't dealt",
+                shape.letter()
+            ));
+        }
+        Ok(Self {
+            name: file.name,
+            description: file.description,
+            order,
+            targets,
+        })
+    }
+}
+impl From<Opener> for OpenerFile {
+    fn from(opener: Opener) -> Self {
+        let mut template = Board::new();
+        for tetromino in opener.targets.iter().filter_map(Target::get_tetromino) {
+            template.lock_tetromino_in_place(tetromino);
+        }
+        Self {
+            name: opener.name,
+            description: opener.description,
+            order: opener.order.iter().map(|shape| shape.letter()).collect(),
+            template: template.to_string(),
+        }
+    }
+}
+// the squares of each piece in a template, which can only have pieces in it
+fn template_squares(board: &Board) -> Result<HashMap<TetrominoShape, Vec<ICoordinate>>, String> {
+    let mut ret_squares: HashMap<TetrominoShape, Vec<ICoordinate>> = HashMap::new();
+    for y in 0..BOARD_HEIGHT - 1 {
+        for x in 1..BOARD_WIDTH - 1 {
+            let square = board.get_board_character_at_coordinate(&UCoordinate::new(x, y));
+            if square == ' ' {
+                continue;
+            }
+            let shape = square
+                .to_digit(10)
+                .and_then(|colour| TetrominoShape::ALL.get(colour as usize))
+                .ok_or_else(|| String::from("the template can only have pieces in it"))?;
+            ret_squares
+                .entry(*shape)
+                .or_default()
+                .push(ICoordinate::new(x as i32, y as i32));
+        }
+    }
+    Ok(ret_squares)
+}
+impl Opener {
+    #[must_use]
+    pub fn built_in() -> Vec<Opener> {
+        BUILT_IN
+            .iter()
+            .filter_map(|text| match storage::from_toml(text) {
+                Ok(opener) => Some(opener),
+                Err(error) => {
+                    warning!("Skipping built in opener: {error}");
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+// How the opener is going, on top of an ordinary game. The board is the game's own,
+// so all that's kept is which of the pieces are where they should be and how many
+// aren't, which have to be undone before it can be finished
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OpenerState {
+    name: String,
+    targets: Vec<Target>,
+    placed: Vec<bool>,
+    misplaced: u32,
+}
+impl OpenerState {
+    #[must_use]
+    pub fn new(opener: &Opener) -> Self {
+        Self {
+            name: opener.name.clone(),
+            targets: opener.targets.clone(),
+            placed: vec![false; opener.targets.len()],
+            misplaced: 0,
+        }
+    }
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    // where the pieces still to be placed go, for the overlay
+    #[must_use]
+    pub fn get_targets_left(&self) -> Vec<&Target> {
+        self.targets
+            .iter()
+            .zip(&self.placed)
+            .filter(|(_, placed)| !**placed)
+            .map(|(target, _)| target)
+            .collect()
+    }
+    #[must_use]
+    pub fn get_pieces_left(&self) -> usize {
+        self.placed.iter().filter(|placed| !**placed).count()
+    }
+    // pieces on the board that the opener doesn't have there
+    #[must_use]
+    pub fn get_misplaced(&self) -> u32 {
+        self.misplaced
+    }
+    #[must_use]
+    pub fn is_built(&self) -> bool {
+        self.misplaced == 0 && self.get_pieces_left() == 0
+    }
+    // A piece has locked. Gives true if it went where the template has it
+    pub fn piece_locked(&mut self, tetromino: &Tetromino) -> bool {
+        let found = self
+            .targets
+            .iter()
+            .zip(&self.placed)
+            .position(|(target, placed)| !placed && target.is_covered_by(tetromino));
+        match found {
+            Some(index) => self.placed[index] = true,
+            None => self.misplaced += 1,
+        }
+        found.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finesse;
+
+    #[test]
+    fn test_built_in_openers_can_be_built() {
+        let openers = Opener::built_in();
+        assert_eq!(openers.len(), BUILT_IN.len());
+        for opener in openers {
+            // each piece has to be reachable without kicks, in the order it's dealt
+            let mut board = Board::new();
+            for target in &opener.targets {
+                let tetromino = target.get_tetromino().unwrap();
+                assert!(
+                    finesse::minimal_path(&board, &tetromino).is_some(),
+                    "{} can't place its {}",
+                    opener.name,
+                    target.shape.letter()
+                );
+                board.lock_tetromino_in_place(tetromino);
+            }
+            assert!(board.get_filled_lines().is_empty());
+        }
+    }
+    #[test]
+    fn test_opener_file() {
+        let text = r#"
+name = "Test"
+order = "I O"
+template = """
+OO________
+OOIIII____
+"""
+"#;
+        let opener: Opener = storage::from_toml(text).unwrap();
+        assert_eq!(opener.order, vec![TetrominoShape::I, TetrominoShape::O]);
+        assert_eq!(opener.targets[0].shape, TetrominoShape::I);
+        assert_eq!(
+            opener.targets[0].get_cells(),
+            (3..7)
+                .map(|x| ICoordinate::new(x, BOARD_HEIGHT as i32 - 2))
+                .collect::<Vec<ICoordinate>>()
+        );
+        for bad in [
+            text.replace("I O", "I O T"),
+            text.replace("I O", "I"),
+            text.replace("I O", "I O O"),
+            text.replace("I O", ""),
+            text.replace("OOIIII", "OOIII_"),
+            text.replace("OOIIII", "OOGIII"),
+        ] {
+            assert!(storage::from_toml::<Opener>(&bad).is_err(), "{bad}");
+        }
+    }
+    #[test]
+    fn test_placements_are_checked() {
+        let opener = &Opener::built_in()[0];
+        let mut state = OpenerState::new(opener);
+        assert_eq!(state.get_pieces_left(), opener.order.len());
+        // the right piece in the wrong place, then in the right place
+        let mut tetromino = opener.targets[0].get_tetromino().unwrap();
+        let coordinates = tetromino.get_coordinates();
+        tetromino.set_coordinates(ICoordinate::new(coordinates.x, coordinates.y - 1));
+        assert!(!state.piece_locked(&tetromino));
+        assert_eq!(state.get_misplaced(), 1);
+        assert!(state.piece_locked(&opener.targets[0].get_tetromino().unwrap()));
+        assert_eq!(state.get_targets_left().len(), opener.order.len() - 1);
+        for target in &opener.targets[1..] {
+            assert!(state.piece_locked(&target.get_tetromino().unwrap()));
+        }
+        // the one in the wrong place still has to go
+        assert_eq!(state.get_pieces_left(), 0);
+        assert!(!state.is_built());
+    }
+}
@@ -0,0 +1,355 @@
+use crate::effects::LineClearEffect;
+use crate::input::{Action, ActionState};
+use crate::settings::Settings;
+
+// The settings that can be changed from inside the game.
+// Each one knows how to read & write its value and how big a step to take
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptionItem {
+    DelayedAutoShift,
+    AutoRepeatRate,
+    SoftDropSpeed,
+    GamepadDeadzone,
+    Speed,
+    ShowFilledLinesTime,
+    TimeLimit,
+    StartLevel,
+    DigRows,
+    RisingGarbage,
+    DrawScale,
+    Theme,
+    LineClearEffect,
+    Particles,
+    LockFlash,
+    ScreenShake,
+    MasterVolume,
+    EffectsVolume,
+    MusicVolume,
+}
+impl OptionItem {
+    pub const ALL: [OptionItem; 19] = [
+        OptionItem::DelayedAutoShift,
+        OptionItem::AutoRepeatRate,
+        OptionItem::SoftDropSpeed,
+        OptionItem::GamepadDeadzone,
+        OptionItem::Speed,
+        OptionItem::ShowFilledLinesTime,
+        OptionItem::TimeLimit,
+        OptionItem::StartLevel,
+        OptionItem::DigRows,
+        OptionItem::RisingGarbage,
+        OptionItem::DrawScale,
+        OptionItem::Theme,
+        OptionItem::LineClearEffect,
+        OptionItem::Particles,
+        OptionItem::LockFlash,
+        OptionItem::ScreenShake,
+        OptionItem::MasterVolume,
+        OptionItem::EffectsVolume,
+        OptionItem::MusicVolume,
+    ];
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            OptionItem::DelayedAutoShift => "DAS",
+            OptionItem::AutoRepeatRate => "ARR",
+            OptionItem::SoftDropSpeed => "Soft drop speed",
+            OptionItem::GamepadDeadzone => "Stick deadzone",
+            OptionItem::Speed => "Starting speed",
+            OptionItem::ShowFilledLinesTime => "Line clear delay",
+            OptionItem::TimeLimit => "Ultra time limit",
+            OptionItem::StartLevel => "Marathon start level",
+            OptionItem::DigRows => "Dig garbage rows",
+            OptionItem::RisingGarbage => "Rising garbage",
+            OptionItem::DrawScale => "Max block size",
+            OptionItem::Theme => "Theme",
+            OptionItem::LineClearEffect => "Line clear effect",
+            OptionItem::Particles => "Particles",
+            OptionItem::LockFlash => "Lock flash",
+            OptionItem::ScreenShake => "Screen shake",
+            OptionItem::MasterVolume => "Master volume",
+            OptionItem::EffectsVolume => "Effects volume",
+            OptionItem::MusicVolume => "Music volume",
+        }
+    }
+    fn step(self) -> f64 {
+        match self {
+            OptionItem::DelayedAutoShift | OptionItem::Speed => 0.05,
+            OptionItem::AutoRepeatRate | OptionItem::SoftDropSpeed => 0.01,
+            OptionItem::ShowFilledLinesTime
+            | OptionItem::GamepadDeadzone
+            | OptionItem::MasterVolume
+            | OptionItem::EffectsVolume
+            | OptionItem::MusicVolume => 0.1,
+            OptionItem::DrawScale => 2.0,
+            OptionItem::TimeLimit => 30.0,
+            OptionItem::RisingGarbage => 5.0,
+            OptionItem::StartLevel
+            | OptionItem::DigRows
+            | OptionItem::Theme
+            | OptionItem::LineClearEffect
+            | OptionItem::Particles
+            | OptionItem::LockFlash
+            | OptionItem::ScreenShake => 1.0,
+        }
+    }
+    #[must_use]
+    pub fn get_value(self, settings: &Settings) -> f64 {
+        match self {
+            OptionItem::DelayedAutoShift => settings.handling.delayed_auto_shift,
+            OptionItem::AutoRepeatRate => settings.handling.auto_repeat_rate,
+            OptionItem::SoftDropSpeed => settings.handling.soft_drop_speed,
+            OptionItem::GamepadDeadzone => settings.handling.gamepad_deadzone.into(),
+            OptionItem::Speed => settings.rules.speed,
+            OptionItem::ShowFilledLinesTime => settings.rules.show_filled_lines_time,
+            OptionItem::TimeLimit => settings.rules.time_limit,
+            OptionItem::StartLevel => settings.rules.start_level.into(),
+            OptionItem::DigRows => settings.rules.dig_rows.into(),
+            OptionItem::RisingGarbage => settings.rules.rising_garbage,
+            OptionItem::DrawScale => settings.visuals.draw_scale.into(),
+            // themes are picked by name, see OptionsMenu::adjust
+            OptionItem::Theme => 0.0,
+            // the rest are picked from a list, so the value is the position in it
+            OptionItem::LineClearEffect => LineClearEffect::ALL
+                .iter()
+                .position(|effect| *effect == settings.visuals.line_clear_effect)
+                .unwrap_or(0) as f64,
+            OptionItem::Particles => f64::from(u8::from(settings.visuals.particles)),
+            OptionItem::LockFlash => f64::from(u8::from(settings.visuals.lock_flash)),
+            OptionItem::ScreenShake => f64::from(u8::from(settings.visuals.screen_shake)),
+            OptionItem::MasterVolume => settings.audio.master_volume.into(),
+            OptionItem::EffectsVolume => settings.audio.effects_volume.into(),
+            OptionItem::MusicVolume => settings.audio.music_volume.into(),
+        }
+    }
+    fn set_value(self, settings: &mut Settings, value: f64) {
+        match self {
+            OptionItem::DelayedAutoShift => settings.handling.delayed_auto_shift = value,
+            OptionItem::AutoRepeatRate => settings.handling.auto_repeat_rate = value,
+            OptionItem::SoftDropSpeed => settings.handling.soft_drop_speed = value,
+            OptionItem::GamepadDeadzone => settings.handling.gamepad_deadzone = value as f32,
+            OptionItem::Speed => settings.rules.speed = value,
+            OptionItem::ShowFilledLinesTime => settings.rules.show_filled_lines_time = value,
+            OptionItem::TimeLimit => settings.rules.time_limit = value,
+            OptionItem::StartLevel => settings.rules.start_level = value as u32,
+            OptionItem::DigRows => settings.rules.dig_rows = value as u32,
+            OptionItem::RisingGarbage => settings.rules.rising_garbage = value,
+            OptionItem::DrawScale => settings.visuals.draw_scale = value as f32,
+            OptionItem::Theme => {}
+            // stepping off either end of a list goes round to the other end
+            OptionItem::LineClearEffect => {
+                let index = (value as i64).rem_euclid(LineClearEffect::ALL.len() as i64);
+                settings.visuals.line_clear_effect = LineClearEffect::ALL[index as usize];
+            }
+            OptionItem::Particles => settings.visuals.particles = is_on(value),
+            OptionItem::LockFlash => settings.visuals.lock_flash = is_on(value),
+            OptionItem::ScreenShake => settings.visuals.screen_shake = is_on(value),
+            OptionItem::MasterVolume => settings.audio.master_volume = value as f32,
+            OptionItem::EffectsVolume => settings.audio.effects_volume = value as f32,
+            OptionItem::MusicVolume => settings.audio.music_volume = value as f32,
+        }
+    }
+    #[must_use]
+    pub fn format_value(self, settings: &Settings) -> String {
+        let value = self.get_value(settings);
+        match self {
+            OptionItem::DelayedAutoShift
+            | OptionItem::AutoRepeatRate
+            | OptionItem::SoftDropSpeed
+            | OptionItem::Speed
+            | OptionItem::ShowFilledLinesTime => format!("{:.0} ms", value * 1000.0),
+            OptionItem::DrawScale | OptionItem::StartLevel | OptionItem::DigRows => {
+                format!("{value:.0}")
+            }
+            OptionItem::RisingGarbage => {
+                if value > 0.0 {
+                    format!("Every {value:.0} s")
+                } else {
+                    "Off".to_string()
+                }
+            }
+            OptionItem::TimeLimit => {
+                let seconds = value as u32;
+                format!("{}:{:02}", seconds / 60, seconds % 60)
+            }
+            OptionItem::Theme => settings.visuals.theme.clone(),
+            OptionItem::LineClearEffect => settings.visuals.line_clear_effect.name().to_string(),
+            OptionItem::Particles | OptionItem::LockFlash | OptionItem::ScreenShake => {
+                if value > 0.5 { "On" } else { "Off" }.to_string()
+            }
+            OptionItem::GamepadDeadzone
+            | OptionItem::MasterVolume
+            | OptionItem::EffectsVolume
+            | OptionItem::MusicVolume => format!("{:.0}%", value * 100.0),
+        }
+    }
+}
+
+// on & off settings go on, off, on... either way
+fn is_on(value: f64) -> bool {
+    (value as i64).rem_euclid(2) == 1
+}
+
+#[derive(Default)]
+pub struct OptionsMenu {
+    selected: usize,
+    themes: Vec<String>, // the names the theme option steps through
+}
+impl OptionsMenu {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    #[must_use]
+    pub fn with_themes(mut self, themes: Vec<String>) -> Self {
+        self.themes = themes;
+        self
+    }
+    #[must_use]
+    pub fn get_selected(&self) -> OptionItem {
+        OptionItem::ALL[self.selected]
+    }
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % OptionItem::ALL.len();
+    }
+    pub fn select_previous(&mut self) {
+        self.selected = (self.selected + OptionItem::ALL.len() - 1) % OptionItem::ALL.len();
+    }
+    pub fn update(&mut self, actions: &ActionState, settings: &mut Settings) {
+        if actions.is_pressed(Action::MenuUp) {
+            self.select_previous();
+        }
+        if actions.is_pressed(Action::MenuDown) {
+            self.select_next();
+        }
+        if actions.is_pressed(Action::MoveLeft) {
+            self.adjust(settings, -1);
+        }
+        if actions.is_pressed(Action::MoveRight) {
+            self.adjust(settings, 1);
+        }
+    }
+    // nudge the selected setting up or down a step, keeping it within its valid range
+    pub fn adjust(&mut self, settings: &mut Settings, steps: i32) {
+        let item = self.get_selected();
+        if item == OptionItem::Theme {
+            self.step_theme(settings, steps);
+            return;
+        }
+        let value = item.get_value(settings) + item.step() * f64::from(steps);
+        // round away any floating point drift so the menu shows tidy numbers
+        let value = (value / item.step()).round() * item.step();
+        item.set_value(settings, value);
+        *settings = settings.clone().validated();
+    }
+    fn step_theme(&self, settings: &mut Settings, steps: i32) {
+        if self.themes.is_empty() {
+            return;
+        }
+        let count = self.themes.len() as i32;
+        let current = self
+            .themes
+            .iter()
+            .position(|name| *name == settings.visuals.theme)
+            .unwrap_or(0) as i32;
+        let index = (current + steps).rem_euclid(count) as usize;
+        settings.visuals.theme.clone_from(&self.themes[index]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_selection_wraps() {
+        let mut menu = OptionsMenu::new();
+        menu.select_previous();
+        assert_eq!(menu.get_selected(), OptionItem::MusicVolume);
+        menu.select_next();
+        assert_eq!(menu.get_selected(), OptionItem::DelayedAutoShift);
+    }
+    #[test]
+    fn test_adjust_stays_in_range() {
+        let mut menu = OptionsMenu::new();
+        let mut settings = Settings::default();
+        for _ in 0..3 {
+            menu.select_previous();
+        }
+        assert_eq!(menu.get_selected(), OptionItem::MasterVolume);
+        menu.adjust(&mut settings, -3);
+        assert_eq!(OptionItem::MasterVolume.format_value(&settings), "70%");
+        menu.adjust(&mut settings, 20);
+        assert_eq!(OptionItem::MasterVolume.format_value(&settings), "100%");
+        menu.adjust(&mut settings, -20);
+        assert_eq!(OptionItem::MasterVolume.format_value(&settings), "0%");
+    }
+    #[test]
+    fn test_theme_steps_through_names() {
+        let names = vec!["Classic".to_string(), "Outline".to_string()];
+        let mut menu = OptionsMenu::new().with_themes(names);
+        while menu.get_selected() != OptionItem::Theme {
+            menu.select_next();
+        }
+        let mut settings = Settings::default();
+        menu.adjust(&mut settings, 1);
+        assert_eq!(OptionItem::Theme.format_value(&settings), "Outline");
+        menu.adjust(&mut settings, 1);
+        assert_eq!(settings.visuals.theme, "Classic");
+        menu.adjust(&mut settings, -1);
+        assert_eq!(settings.visuals.theme, "Outline");
+    }
+    #[test]
+    fn test_effects_toggle_and_cycle() {
+        let mut menu = OptionsMenu::new();
+        let mut settings = Settings::default();
+        while menu.get_selected() != OptionItem::LineClearEffect {
+            menu.select_next();
+        }
+        assert_eq!(
+            OptionItem::LineClearEffect.format_value(&settings),
+            "Dissolve"
+        );
+        menu.adjust(&mut settings, 1);
+        assert_eq!(settings.visuals.line_clear_effect, LineClearEffect::Slide);
+        menu.adjust(&mut settings, 1);
+        assert_eq!(settings.visuals.line_clear_effect, LineClearEffect::Flash);
+        menu.select_next();
+        assert_eq!(OptionItem::Particles.format_value(&settings), "On");
+        menu.adjust(&mut settings, 1);
+        assert!(!settings.visuals.particles);
+        menu.adjust(&mut settings, 1);
+        assert!(settings.visuals.particles);
+        menu.adjust(&mut settings, -1);
+        assert_eq!(OptionItem::Particles.format_value(&settings), "Off");
+    }
+    #[test]
+    fn test_time_limit_in_minutes() {
+        let mut menu = OptionsMenu::new();
+        let mut settings = Settings::default();
+        while menu.get_selected() != OptionItem::TimeLimit {
+            menu.select_next();
+        }
+        assert_eq!(OptionItem::TimeLimit.format_value(&settings), "2:00");
+        menu.adjust(&mut settings, 1);
+        assert_eq!(OptionItem::TimeLimit.format_value(&settings), "2:30");
+        menu.adjust(&mut settings, -10);
+        assert_eq!(OptionItem::TimeLimit.format_value(&settings), "0:10");
+    }
+    #[test]
+    fn test_rising_garbage_can_be_turned_off() {
+        let mut menu = OptionsMenu::new();
+        let mut settings = Settings::default();
+        while menu.get_selected() != OptionItem::RisingGarbage {
+            menu.select_next();
+        }
+        assert_eq!(OptionItem::RisingGarbage.format_value(&settings), "Off");
+        menu.adjust(&mut settings, -1);
+        assert_eq!(OptionItem::RisingGarbage.format_value(&settings), "Off");
+        menu.adjust(&mut settings, 2);
+        assert_eq!(
+            OptionItem::RisingGarbage.format_value(&settings),
+            "Every 10 s"
+        );
+    }
+}
@@ -0,0 +1,577 @@
+use std::fmt;
+
+// Reads & writes PNG images without pulling in an image library.
+// Writing does only what the software renderer needs: 8 bit RGBA, compressed with
+// deflate's fixed Huffman codes. The output is always the same for the same pixels,
+// so it can be compared byte for byte. Reading handles the 8 bit images paint programs
+// save, which is enough for skins
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+
+// the start of each length & distance code, and how many extra bits follow it
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PngError {
+    NotAPng,
+    Unsupported(&'static str),
+    Corrupt,
+}
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PngError::NotAPng => write!(f, "not a PNG image"),
+            PngError::Unsupported(what) => write!(f, "PNGs with {what} aren't supported"),
+            PngError::Corrupt => write!(f, "the PNG is damaged"),
+        }
+    }
+}
+impl std::error::Error for PngError {}
+
+#[must_use]
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[must_use]
+pub fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for byte in bytes {
+        a = (a + u32::from(*byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+// deflate packs bits starting from the lowest bit of each byte
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            buffer: 0,
+            count: 0,
+        }
+    }
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+    // Huffman codes go in the other way round, most significant bit first
+    fn write_code(&mut self, code: u32, length: u32) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.write_bits(reversed, length);
+    }
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn write_literal(writer: &mut BitWriter, value: u32) {
+    match value {
+        0..=143 => writer.write_code(0x30 + value, 8),
+        144..=255 => writer.write_code(0x190 + value - 144, 9),
+        256..=279 => writer.write_code(value - 256, 7),
+        _ => writer.write_code(0xC0 + value - 280, 8),
+    }
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASES.partition_point(|base| usize::from(*base) <= length) - 1;
+    write_literal(writer, 257 + code as u32);
+    writer.write_bits(
+        (length - usize::from(LENGTH_BASES[code])) as u32,
+        u32::from(LENGTH_EXTRA_BITS[code]),
+    );
+    let code = DISTANCE_BASES.partition_point(|base| usize::from(*base) <= distance) - 1;
+    writer.write_code(code as u32, 5);
+    writer.write_bits(
+        (distance - usize::from(DISTANCE_BASES[code])) as u32,
+        u32::from(DISTANCE_EXTRA_BITS[code]),
+    );
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+    (value.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+// A single fixed Huffman block, looking back for repeats with one guess per position.
+// Rendered screens are mostly flat colour so that's plenty
+#[must_use]
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    writer.write_bits(1, 1); // last block
+    writer.write_bits(1, 2); // fixed Huffman codes
+    let mut last_seen = vec![usize::MAX; 1 << HASH_BITS];
+    let mut position = 0;
+    while position < data.len() {
+        let mut best_length = 0;
+        let mut best_distance = 0;
+        if position + MIN_MATCH <= data.len() {
+            let key = hash(&data[position..]);
+            let candidate = last_seen[key];
+            last_seen[key] = position;
+            if candidate != usize::MAX && position - candidate <= WINDOW_SIZE {
+                let limit = MAX_MATCH.min(data.len() - position);
+                while best_length < limit
+                    && data[candidate + best_length] == data[position + best_length]
+                {
+                    best_length += 1;
+                }
+                best_distance = position - candidate;
+            }
+        }
+        if best_length >= MIN_MATCH {
+            write_match(&mut writer, best_length, best_distance);
+            // remember where the skipped over bytes were so later repeats can find them
+            for skipped in position + 1..position + best_length {
+                if skipped + MIN_MATCH <= data.len() {
+                    last_seen[hash(&data[skipped..])] = skipped;
+                }
+            }
+            position += best_length;
+        } else {
+            write_literal(&mut writer, u32::from(data[position]));
+            position += 1;
+        }
+    }
+    write_literal(&mut writer, 256); // end of block
+    writer.finish()
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: [u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+// pixels are rows of red, green, blue & alpha bytes, top row first
+#[must_use]
+pub fn encode_rgba(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    let row_length = width as usize * 4;
+    assert_eq!(pixels.len(), row_length * height as usize);
+
+    let mut header = Vec::with_capacity(13);
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    // 8 bits per channel, RGBA, then the only compression, filter & interlace methods
+    header.extend([8, 6, 0, 0, 0]);
+
+    // every row starts with its filter type, always none here
+    let mut raw = Vec::with_capacity((row_length + 1) * height as usize);
+    for row in pixels.chunks(row_length.max(1)).take(height as usize) {
+        raw.push(0);
+        raw.extend(row);
+    }
+    let mut compressed = vec![0x78, 0x01]; // zlib header, 32K window
+    compressed.extend(deflate(&raw));
+    compressed.extend(adler32(&raw).to_be_bytes());
+
+    let mut ret_png = SIGNATURE.to_vec();
+    write_chunk(&mut ret_png, *b"IHDR", &header);
+    write_chunk(&mut ret_png, *b"IDAT", &compressed);
+    write_chunk(&mut ret_png, *b"IEND", &[]);
+    ret_png
+}
+
+// deflate reads bits starting from the lowest bit of each byte too
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize, // in bits
+}
+impl BitReader<'_> {
+    fn bit(&mut self) -> Result<u32, PngError> {
+        let byte = self.bytes.get(self.position / 8).ok_or(PngError::Corrupt)?;
+        let ret_bit = u32::from(byte >> (self.position % 8)) & 1;
+        self.position += 1;
+        Ok(ret_bit)
+    }
+    fn bits(&mut self, count: u32) -> Result<u32, PngError> {
+        let mut ret_value = 0;
+        for index in 0..count {
+            ret_value |= self.bit()? << index;
+        }
+        Ok(ret_value)
+    }
+    fn align_to_byte(&mut self) {
+        self.position = self.position.div_ceil(8) * 8;
+    }
+}
+
+// A canonical Huffman code, built from how many bits each symbol's code takes
+struct Huffman {
+    counts: [u16; 16], // how many codes there are of each length
+    symbols: Vec<u16>, // the symbols in code order
+}
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0_u16; 16];
+        for length in lengths {
+            counts[usize::from(*length)] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0_u16; 16];
+        for length in 1..16 {
+            offsets[length] = offsets[length - 1] + counts[length - 1];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[usize::from(offsets[usize::from(*length)])] = symbol as u16;
+                offsets[usize::from(*length)] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, PngError> {
+        // walk down the code a bit at a time, the codes of each length are consecutive
+        let (mut code, mut first, mut index) = (0_i32, 0_i32, 0_i32);
+        for count in &self.counts[1..] {
+            code |= reader.bit()? as i32;
+            let count = i32::from(*count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(PngError::Corrupt)
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [8_u8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), PngError> {
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    let mut code_lengths = [0_u8; 19];
+    for position in &ORDER[..code_length_count] {
+        code_lengths[*position] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (length, repeat) = match code_length_code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (
+                *lengths.last().ok_or(PngError::Corrupt)?,
+                3 + reader.bits(2)?,
+            ),
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(length, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(PngError::Corrupt);
+    }
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), PngError> {
+    loop {
+        let symbol = usize::from(literals.decode(reader)?);
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let code = symbol - 257;
+                let base = *LENGTH_BASES.get(code).ok_or(PngError::Corrupt)?;
+                let length =
+                    usize::from(base) + reader.bits(u32::from(LENGTH_EXTRA_BITS[code]))? as usize;
+                let code = usize::from(distances.decode(reader)?);
+                let base = *DISTANCE_BASES.get(code).ok_or(PngError::Corrupt)?;
+                let distance =
+                    usize::from(base) + reader.bits(u32::from(DISTANCE_EXTRA_BITS[code]))? as usize;
+                if distance > output.len() {
+                    return Err(PngError::Corrupt);
+                }
+                // copied a byte at a time as the match may overlap what it's copying
+                let start = output.len() - distance;
+                for index in 0..length {
+                    output.push(output[start + index]);
+                }
+            }
+        }
+    }
+}
+
+#[must_use = "inflating does nothing but return the data"]
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, PngError> {
+    let mut reader = BitReader {
+        bytes: data,
+        position: 0,
+    };
+    let mut ret_output = Vec::new();
+    loop {
+        let last = reader.bit()? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let start = reader.position / 8;
+                let header = data.get(start..start + 4).ok_or(PngError::Corrupt)?;
+                let length = usize::from(u16::from_le_bytes([header[0], header[1]]));
+                let stored = data
+                    .get(start + 4..start + 4 + length)
+                    .ok_or(PngError::Corrupt)?;
+                ret_output.extend(stored);
+                reader.position = (start + 4 + length) * 8;
+            }
+            1 => {
+                let (literals, distances) = fixed_codes();
+                inflate_block(&mut reader, &mut ret_output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut ret_output, &literals, &distances)?;
+            }
+            _ => return Err(PngError::Corrupt),
+        }
+        if last {
+            return Ok(ret_output);
+        }
+    }
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = i16::from(left) + i16::from(up) - i16::from(up_left);
+    let distance = |value: u8| (estimate - i16::from(value)).abs();
+    if distance(left) <= distance(up) && distance(left) <= distance(up_left) {
+        left
+    } else if distance(up) <= distance(up_left) {
+        up
+    } else {
+        up_left
+    }
+}
+
+// An image read from a PNG, always turned into 8 bit RGBA whatever it was saved as
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+pub fn decode_rgba(bytes: &[u8]) -> Result<Image, PngError> {
+    if bytes.get(..8) != Some(&SIGNATURE[..]) {
+        return Err(PngError::NotAPng);
+    }
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut compressed = Vec::new();
+    let mut position = 8;
+    while position + 8 <= bytes.len() {
+        let length =
+            u32::from_be_bytes(bytes[position..position + 4].try_into().unwrap_or([0; 4])) as usize;
+        let kind = &bytes[position + 4..position + 8];
+        let data = bytes
+            .get(position + 8..position + 8 + length)
+            .ok_or(PngError::Corrupt)?;
+        match kind {
+            b"IHDR" => header = Some(data),
+            b"PLTE" => palette = data,
+            b"tRNS" => transparency = data,
+            b"IDAT" => compressed.extend(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        position += 12 + length;
+    }
+    let header = header
+        .filter(|header| header.len() == 13)
+        .ok_or(PngError::Corrupt)?;
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    if header[8] != 8 {
+        return Err(PngError::Unsupported("other than 8 bits per channel"));
+    }
+    if header[12] != 0 {
+        return Err(PngError::Unsupported("interlacing"));
+    }
+    let channels = match header[9] {
+        0 | 3 => 1,
+        4 => 2,
+        2 => 3,
+        6 => 4,
+        _ => return Err(PngError::Corrupt),
+    };
+
+    // skip the two byte zlib header, the checksum at the end isn't needed
+    let raw = inflate(compressed.get(2..).ok_or(PngError::Corrupt)?)?;
+    let row_length = width as usize * channels;
+    if raw.len() < (row_length + 1) * height as usize {
+        return Err(PngError::Corrupt);
+    }
+    let mut image = vec![0_u8; row_length * height as usize];
+    for row in 0..height as usize {
+        let filter = raw[row * (row_length + 1)];
+        let line = &raw[row * (row_length + 1) + 1..(row + 1) * (row_length + 1)];
+        for (index, byte) in line.iter().enumerate() {
+            let at = row * row_length + index;
+            let left = if index >= channels {
+                image[at - channels]
+            } else {
+                0
+            };
+            let up = if row > 0 { image[at - row_length] } else { 0 };
+            let up_left = if row > 0 && index >= channels {
+                image[at - row_length - channels]
+            } else {
+                0
+            };
+            let predicted = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => u8::midpoint(left, up),
+                4 => paeth(left, up, up_left),
+                _ => return Err(PngError::Corrupt),
+            };
+            image[at] = byte.wrapping_add(predicted);
+        }
+    }
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+    for pixel in image.chunks_exact(channels) {
+        match header[9] {
+            0 => pixels.extend([pixel[0], pixel[0], pixel[0], 255]),
+            4 => pixels.extend([pixel[0], pixel[0], pixel[0], pixel[1]]),
+            2 => pixels.extend([pixel[0], pixel[1], pixel[2], 255]),
+            3 => {
+                let index = usize::from(pixel[0]);
+                let colour = palette
+                    .get(index * 3..index * 3 + 3)
+                    .ok_or(PngError::Corrupt)?;
+                let alpha = transparency.get(index).copied().unwrap_or(255);
+                pixels.extend([colour[0], colour[1], colour[2], alpha]);
+            }
+            _ => pixels.extend(pixel),
+        }
+    }
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+    #[test]
+    fn test_repeats_are_compressed() {
+        let flat = vec![7; 10_000];
+        assert!(deflate(&flat).len() < 200);
+        // a single byte is stored as a literal and the end of block code
+        assert_eq!(deflate(&[0]), vec![0x63, 0x00, 0x00]);
+    }
+    #[test]
+    fn test_png_layout() {
+        let png = encode_rgba(2, 1, &[255, 0, 0, 255, 0, 0, 255, 255]);
+        assert_eq!(png[..8], SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(png[16..24], [0, 0, 0, 2, 0, 0, 0, 1]);
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+    }
+    #[test]
+    fn test_inflate_dynamic_codes() {
+        // made by zlib, which picked its own Huffman codes for this
+        let compressed = [
+            0x35, 0x8E, 0x89, 0x11, 0x00, 0x21, 0x0C, 0x02, 0x6B, 0xE5, 0xE9, 0xBF, 0x86, 0x03,
+            0x72, 0xAA, 0x93, 0x87, 0x84, 0x1D, 0x41, 0x02, 0x26, 0x92, 0x08, 0x0B, 0x89, 0x79,
+            0x54, 0xD3, 0x3A, 0x75, 0xB8, 0xBA, 0x9A, 0xA0, 0xAA, 0xB4, 0x23, 0x19, 0x5B, 0x74,
+            0xEE, 0x9D, 0x6C, 0xCF, 0x24, 0x3C, 0x20, 0xBA, 0x99, 0x96, 0x43, 0xD0, 0xBC, 0x41,
+            0x51, 0x38, 0x62, 0xA6, 0xE7, 0x17, 0x46, 0x5E, 0x98, 0x8D, 0x3F, 0x94, 0x87, 0x2E,
+            0xBD, 0x55, 0x3F, 0x59, 0x67, 0x20, 0x0F, 0x95, 0xE2, 0x03,
+        ];
+        let text = "abbaadbabbabadcaabaababcbaabcaabacdbababcaacbaacaccaabbddabcdaabcbadadaaaaaaab\
+            acbcaabcababbabadabddacabbbabcabdbbabbabcbaaaaacbaaacaadaaaacacaabbcaabbabddabaaaa\
+            aabbaaaabaabacaaaadcaaacbabcbbbabcbaabbb";
+        assert_eq!(inflate(&compressed).unwrap(), text.as_bytes());
+    }
+    #[test]
+    fn test_decode_what_was_encoded() {
+        let pixels: Vec<u8> = (0..6 * 5 * 4)
+            .map(|value| (value * 37 % 256) as u8)
+            .collect();
+        let image = decode_rgba(&encode_rgba(6, 5, &pixels)).unwrap();
+        assert_eq!((image.width, image.height), (6, 5));
+        assert_eq!(image.pixels, pixels);
+        assert_eq!(decode_rgba(b"not a png"), Err(PngError::NotAPng));
+        let mut damaged = encode_rgba(6, 5, &pixels);
+        damaged.truncate(50);
+        assert!(decode_rgba(&damaged).is_err());
+    }
+}
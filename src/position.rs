@@ -0,0 +1,127 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::board::{Board, BoardTextError};
+use crate::tetromino::TetrominoShape;
+
+// A moment in a game as text, to paste into a bug report, a test or a puzzle file.
+// The pieces come first, one per line as "current: T", "hold: I" and "queue: OSZ",
+// any of which can be left out, then the board as Board writes it out:
+//
+//     current: T
+//     hold: I
+//     queue: OSZLJ
+//     ______LL__
+//     GGGG_GGGGG
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    pub board: Board,
+    pub current: Option<TetrominoShape>,
+    pub hold: Option<TetrominoShape>,
+    pub queue: Vec<TetrominoShape>,
+}
+impl Position {
+    #[must_use]
+    pub fn new(board: Board) -> Self {
+        Self {
+            board,
+            current: None,
+            hold: None,
+            queue: Vec::new(),
+        }
+    }
+}
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(current) = self.current {
+            writeln!(f, "current: {}", current.letter())?;
+        }
+        if let Some(hold) = self.hold {
+            writeln!(f, "hold: {}", hold.letter())?;
+        }
+        if !self.queue.is_empty() {
+            let queue: String = self.queue.iter().map(|shape| shape.letter()).collect();
+            writeln!(f, "queue: {queue}")?;
+        }
+        write!(f, "{}", self.board)
+    }
+}
+impl FromStr for Position {
+    type Err = BoardTextError;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut ret_position = Position::new(Board::new());
+        let mut rows = Vec::new();
+        for line in text.lines() {
+            let Some((field, pieces)) = line.split_once(':') else {
+                rows.push(line);
+                continue;
+            };
+            let pieces = pieces
+                .chars()
+                .filter(|letter| !letter.is_whitespace())
+                .map(|letter| {
+                    TetrominoShape::from_letter(letter).ok_or(BoardTextError::Piece(letter))
+                })
+                .collect::<Result<Vec<TetrominoShape>, BoardTextError>>()?;
+            match field.trim() {
+                "current" => ret_position.current = pieces.first().copied(),
+                "hold" => ret_position.hold = pieces.first().copied(),
+                "queue" => ret_position.queue = pieces,
+                other => return Err(BoardTextError::Field(other.to_string())),
+            }
+        }
+        ret_position.board = rows.join("\n").parse()?;
+        Ok(ret_position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{BOARD_HEIGHT, BOARD_WIDTH};
+    use crate::coordinate::UCoordinate;
+
+    #[test]
+    fn test_board_text() {
+        let text = "____TT____\n___TT_____\nGGGG_GGGGG";
+        let board: Board = text.parse().unwrap();
+        let square = |x, y| board.get_board_character_at_coordinate(&UCoordinate::new(x, y));
+        // the rows sit on the floor, inside the walls
+        assert_eq!(square(1, BOARD_HEIGHT - 2), '9');
+        assert_eq!(square(5, BOARD_HEIGHT - 2), ' ');
+        assert_eq!(
+            square(4, BOARD_HEIGHT - 3),
+            char::from_digit(TetrominoShape::T.index() as u32, 10).unwrap()
+        );
+        assert_eq!(square(BOARD_WIDTH - 1, 0), '9');
+        assert_eq!(board.to_string(), text);
+        assert_eq!(Board::new().to_string(), "");
+        assert_eq!("".parse::<Board>(), Ok(Board::new()));
+        assert_eq!("____".parse::<Board>(), Err(BoardTextError::RowWidth(1)));
+        assert_eq!(
+            "____X_____".parse::<Board>(),
+            Err(BoardTextError::Square('X'))
+        );
+        let too_tall = "__________\n".repeat(BOARD_HEIGHT as usize);
+        assert_eq!(too_tall.parse::<Board>(), Err(BoardTextError::TooManyRows));
+    }
+    #[test]
+    fn test_position_round_trips() {
+        let text = "current: T\nhold: I\nqueue: OSZLJ\n______LL__\nGGGG_GGGGG";
+        let position: Position = text.parse().unwrap();
+        assert_eq!(position.current, Some(TetrominoShape::T));
+        assert_eq!(position.hold, Some(TetrominoShape::I));
+        assert_eq!(position.queue.len(), 5);
+        assert_eq!(position.to_string(), text);
+        // the pieces can be left out, but not made up
+        assert_eq!("GGGG_GGGGG".parse::<Position>().unwrap().current, None);
+        assert_eq!(
+            "hold: Q".parse::<Position>(),
+            Err(BoardTextError::Piece('Q'))
+        );
+        assert_eq!(
+            "next: T".parse::<Position>(),
+            Err(BoardTextError::Field(String::from("next")))
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::fumen;
+use crate::storage::{self, StorageError};
+use crate::tetromino::TetrominoShape;
+use crate::warning;
+
+const PUZZLE_DIRECTORY: &str = "puzzles";
+// the puzzles that come with the game, in the order they're listed
+const BUILT_IN: [&str; 4] = [
+    include_str!("../puzzles/tetris.toml"),
+    include_str!("../puzzles/two_doubles.toml"),
+    include_str!("../puzzles/three_lines.toml"),
+    include_str!("../puzzles/t_spin_single.toml"),
+];
+
+// What has to be done with the pieces to solve a puzzle
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Goal {
+    // this many lines in total, however they come
+    ClearLines { lines: u32 },
+    // a T-spin clearing exactly this many lines
+    TSpin { lines: u32 },
+}
+impl Goal {
+    #[must_use]
+    pub fn description(self) -> String {
+        match self {
+            Goal::ClearLines { lines: 1 } => String::from("Clear a line"),
+            Goal::ClearLines { lines } => format!("Clear {lines} lines"),
+            Goal::TSpin { lines } => format!("T-spin {}", clear_name(lines)),
+        }
+    }
+}
+fn clear_name(lines: u32) -> &'static str {
+    match lines {
+        1 => "single",
+        2 => "double",
+        _ => "triple",
+    }
+}
+
+// A puzzle as it's written in its file: the board as text (see Board's FromStr) or as
+// a fumen, and the pieces as their letters, first one first. A puzzle is written back
+// out with the board as text
+#[derive(Serialize, Deserialize)]
+struct PuzzleFile {
+    name: String,
+    #[serde(default)]
+    description: String,
+    goal: Goal,
+    pieces: String,
+    board: String,
+}
+
+// A board to start from, the pieces to play on it, in order, and a goal to reach
+// before they run out. The built in puzzles are in the puzzles directory of the
+// source, more can be added as TOML files in the puzzles directory next to the settings
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "PuzzleFile", into = "PuzzleFile")]
+pub struct Puzzle {
+    pub name: String,
+    pub description: String,
+    pub goal: Goal,
+    pub pieces: Vec<TetrominoShape>,
+    pub board: Board,
+}
+impl TryFrom<PuzzleFile> for Puzzle {
+    type Error = String;
+    fn try_from(file: PuzzleFile) -> Result<Self, Self::Error> {
+        let pieces = file
+            .pieces
+            .chars()
+            .filter(|letter| !letter.is_whitespace())
+            .map(|letter| {
+                TetrominoShape::from_letter(letter)
+                    .ok_or_else(|| format!("there is no {letter} piece"))
+            })
+            .collect::<Result<Vec<TetrominoShape>, String>>()?;
+        if pieces.is_empty() {
+            return Err(String::from("a puzzle needs at least one piece"));
+        }
+        let (Goal::ClearLines { lines } | Goal::TSpin { lines }) = file.goal;
+        if lines == 0 || matches!(file.goal, Goal::TSpin { lines } if lines > 3) {
+            return Err(String::from("the goal can never be reached"));
+        }
+        // the board as it's written out for bug reports or the first page of a fumen
+        let board = if fumen::is_fumen(&file.board) {
+            let mut pages = fumen::decode(&file.board).map_err(|error| format!("{error}"))?;
+            pages.swap_remove(0).board
+        } else {
+            file.board.parse().map_err(|error| format!("{error}"))?
+        };
+        Ok(Self {
+            name: file.name,
+            description: file.description,
+            goal: file.goal,
+            pieces,
+            board,
+        })
+    }
+}
+impl From<Puzzle> for PuzzleFile {
+    fn from(puzzle: Puzzle) -> Self {
+        Self {
+            name: puzzle.name,
+            description: puzzle.description,
+            goal: puzzle.goal,
+            pieces: puzzle.pieces.iter().map(|shape| shape.letter()).collect(),
+            board: puzzle.board.to_string(),
+        }
+    }
+}
+impl Puzzle {
+    #[must_use]
+    pub fn built_in() -> Vec<Puzzle> {
+        BUILT_IN
+            .iter()
+            .filter_map(|text| match storage::from_toml(text) {
+                Ok(puzzle) => Some(puzzle),
+                Err(error) => {
+                    warning!("Skipping built in puzzle: {error}");
+                    None
+                }
+            })
+            .collect()
+    }
+
+    #[must_use]
+    pub fn default_directory() -> Option<PathBuf> {
+        storage::config_path(PUZZLE_DIRECTORY)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Puzzle, StorageError> {
+        storage::from_toml(&fs::read_to_string(path)?)
+    }
+
+    // The built in puzzles followed by any in the directory, in file name order.
+    // One that can't be read is skipped so it can't stop the game from starting
+    #[must_use]
+    pub fn load_all(directory: Option<&Path>) -> Vec<Puzzle> {
+        let mut ret_puzzles = Self::built_in();
+        let Some(entries) = directory.and_then(|directory| fs::read_dir(directory).ok()) else {
+            return ret_puzzles;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "toml")
+            })
+            .collect();
+        paths.sort();
+        for path in paths {
+            match Self::load_from(&path) {
+                Ok(puzzle) => ret_puzzles.push(puzzle),
+                Err(error) => warning!("Skipping puzzle {}: {error}", path.display()),
+            }
+        }
+        ret_puzzles
+    }
+}
+
+// How a puzzle is going, on top of an ordinary game. The board is the game's own,
+// so all that's kept here is the goal and the pieces still to come
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PuzzleState {
+    name: String,
+    goal: Goal,
+    pieces: VecDeque<TetrominoShape>,
+    lines: u32,
+    solved: bool,
+}
+impl PuzzleState {
+    #[must_use]
+    pub fn new(puzzle: &Puzzle) -> Self {
+        Self {
+            name: puzzle.name.clone(),
+            goal: puzzle.goal,
+            pieces: puzzle.pieces.iter().copied().collect(),
+            lines: 0,
+            solved: false,
+        }
+    }
+    #[must_use]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    #[must_use]
+    pub fn get_goal(&self) -> Goal {
+        self.goal
+    }
+    #[must_use]
+    pub fn is_solved(&self) -> bool {
+        self.solved
+    }
+    // lines still to clear, if that's the goal
+    #[must_use]
+    pub fn get_lines_left(&self) -> Option<u32> {
+        match self.goal {
+            Goal::ClearLines { lines } => Some(lines.saturating_sub(self.lines)),
+            Goal::TSpin { .. } => None,
+        }
+    }
+    // pieces that haven't come in yet, not counting the one being played
+    #[must_use]
+    pub fn get_pieces_left(&self) -> usize {
+        self.pieces.len()
+    }
+    #[must_use]
+    pub fn peek(&self, count: usize) -> Vec<TetrominoShape> {
+        self.pieces.iter().take(count).copied().collect()
+    }
+    // the next piece, or None once they've all been played
+    pub fn next_shape(&mut self) -> Option<TetrominoShape> {
+        self.pieces.pop_front()
+    }
+    // A piece has locked, clearing this many lines. Gives true if that solved it
+    pub fn piece_locked(&mut self, lines: u32, t_spin: bool) -> bool {
+        self.lines += lines;
+        self.solved |= match self.goal {
+            Goal::ClearLines { lines } => self.lines >= lines,
+            Goal::TSpin { lines: needed } => t_spin && lines == needed,
+        };
+        self.solved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{BOARD_HEIGHT, BOARD_WIDTH};
+    use crate::coordinate::UCoordinate;
+
+    #[test]
+    fn test_built_in_puzzles_load() {
+        let puzzles = Puzzle::built_in();
+        assert_eq!(puzzles.len(), BUILT_IN.len());
+        assert!(puzzles.iter().all(|puzzle| !puzzle.pieces.is_empty()));
+    }
+    #[test]
+    fn test_puzzle_file() {
+        let text = r#"
+name = "Test"
+goal = { type = "t_spin", lines = 2 }
+pieces = "T i"
+board = """
+__J_______
+JJ___JJJJJ
+JJJ_JJJJJJ
+"""
+"#;
+        let puzzle: Puzzle = storage::from_toml(text).unwrap();
+        assert_eq!(puzzle.goal, Goal::TSpin { lines: 2 });
+        assert_eq!(puzzle.goal.description(), "T-spin double");
+        assert_eq!(puzzle.pieces, vec![TetrominoShape::T, TetrominoShape::I]);
+        // the board sits on the floor, inside the walls
+        let square = |x, y| {
+            puzzle
+                .board
+                .get_board_character_at_coordinate(&UCoordinate::new(x, y))
+        };
+        assert_eq!(square(1, BOARD_HEIGHT - 2), '1');
+        assert_eq!(square(4, BOARD_HEIGHT - 2), ' ');
+        assert_eq!(square(3, BOARD_HEIGHT - 4), '1');
+        assert_eq!(square(BOARD_WIDTH - 1, 0), '9');
+        // things that can't be played are refused
+        for bad in [
+            text.replace("T i", "T Q"),
+            text.replace("T i", ""),
+            text.replace("lines = 2", "lines = 4"),
+            text.replace("__J_______", "__J______"),
+            text.replace("__J_______", "__x_______"),
+        ] {
+            assert!(storage::from_toml::<Puzzle>(&bad).is_err(), "{bad}");
+        }
+    }
+    #[test]
+    fn test_goals() {
+        let puzzle = |goal| Puzzle {
+            name: String::new(),
+            description: String::new(),
+            goal,
+            pieces: vec![TetrominoShape::T],
+            board: Board::new(),
+        };
+        let mut state = PuzzleState::new(&puzzle(Goal::ClearLines { lines: 4 }));
+        assert_eq!(state.get_lines_left(), Some(4));
+        assert!(!state.piece_locked(3, false));
+        assert!(state.piece_locked(1, false));
+        assert_eq!(state.get_lines_left(), Some(0));
+        // a T-spin has to clear exactly the right number
+        let mut state = PuzzleState::new(&puzzle(Goal::TSpin { lines: 2 }));
+        assert!(!state.piece_locked(2, false));
+        assert!(!state.piece_locked(1, true));
+        assert!(state.piece_locked(2, true));
+        assert_eq!(state.next_shape(), Some(TetrominoShape::T));
+        assert_eq!(state.next_shape(), None);
+    }
+}
@@ -0,0 +1,1009 @@
+use serde::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::constants::{self, BOARD_HEIGHT, BOARD_WIDTH, NEXT_QUEUE_SIZE, TETROMINO_SIZE};
+use crate::coordinate::UCoordinate;
+use crate::effects::{self, Effects, LineClearEffect};
+use crate::finesse::{self, FinesseState};
+use crate::game::{Game, GameMode, Ranking};
+use crate::highscores::HighScore;
+use crate::layout::{DESIGN_HEIGHT, DESIGN_WIDTH, Layout, Rect};
+use crate::menu::Menu;
+use crate::opener::{Opener, OpenerState};
+use crate::options::{OptionItem, OptionsMenu};
+use crate::puzzle::Puzzle;
+use crate::replay::Playback;
+use crate::screen::{Context, Screen};
+use crate::settings::Settings;
+use crate::stats::Statistics;
+use crate::tetromino::{Tetromino, TetrominoShape};
+use crate::theme::{BlockStyle, BorderStyle, FLASH_SQUARE, Skin, Theme, WALL_SQUARE};
+
+// Red, green, blue & alpha, each from 0.0 to 1.0. Saved as "#rrggbb" or "#rrggbbaa"
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Colour {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+impl Colour {
+    pub const WHITE: Colour = Colour::new(1.0, 1.0, 1.0, 1.0);
+    pub const BLACK: Colour = Colour::new(0.0, 0.0, 0.0, 1.0);
+    pub const GRAY: Colour = Colour::new(0.51, 0.51, 0.51, 1.0);
+    pub const YELLOW: Colour = Colour::new(0.99, 0.98, 0.0, 1.0);
+    pub const ORANGE: Colour = Colour::new(1.0, 0.63, 0.0, 1.0);
+    pub const RED: Colour = Colour::new(0.90, 0.16, 0.22, 1.0);
+    pub const GREEN: Colour = Colour::new(0.0, 0.89, 0.19, 1.0);
+    pub const SKYBLUE: Colour = Colour::new(0.40, 0.75, 1.0, 1.0);
+    pub const DARKBLUE: Colour = Colour::new(0.0, 0.32, 0.67, 1.0);
+    pub const PURPLE: Colour = Colour::new(0.78, 0.48, 1.0, 1.0);
+    pub const VIOLET: Colour = Colour::new(0.53, 0.24, 0.75, 1.0);
+
+    #[must_use]
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+    #[must_use]
+    pub fn from_bytes([r, g, b, a]: [u8; 4]) -> Self {
+        let channel = |value: u8| f32::from(value) / 255.0;
+        Self::new(channel(r), channel(g), channel(b), channel(a))
+    }
+    #[must_use]
+    pub fn from_hex(text: &str) -> Option<Colour> {
+        let digits = text.strip_prefix('#')?;
+        if !matches!(digits.len(), 6 | 8) || !digits.is_ascii() {
+            return None;
+        }
+        let channel = |index: usize| {
+            u8::from_str_radix(digits.get(index * 2..index * 2 + 2)?, 16)
+                .ok()
+                .map(|value| f32::from(value) / 255.0)
+        };
+        let alpha = if digits.len() == 8 { channel(3)? } else { 1.0 };
+        Some(Colour::new(channel(0)?, channel(1)?, channel(2)?, alpha))
+    }
+    #[must_use]
+    pub fn to_hex(self) -> String {
+        let byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let ret_hex = format!(
+            "#{:02x}{:02x}{:02x}",
+            byte(self.r),
+            byte(self.g),
+            byte(self.b)
+        );
+        if self.a < 1.0 {
+            format!("{ret_hex}{:02x}", byte(self.a))
+        } else {
+            ret_hex
+        }
+    }
+    // part of the way towards another colour, keeping this one's alpha
+    #[must_use]
+    pub fn mix(self, other: Colour, amount: f32) -> Colour {
+        let blend = |from: f32, to: f32| from + (to - from) * amount;
+        Colour::new(
+            blend(self.r, other.r),
+            blend(self.g, other.g),
+            blend(self.b, other.b),
+            self.a,
+        )
+    }
+    #[must_use]
+    pub fn with_alpha(self, a: f32) -> Colour {
+        Colour { a, ..self }
+    }
+}
+impl TryFrom<String> for Colour {
+    type Error = String;
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        Colour::from_hex(&text).ok_or_else(|| format!("{text} isn't a colour like #ff8800"))
+    }
+}
+impl From<Colour> for String {
+    fn from(colour: Colour) -> Self {
+        colour.to_hex()
+    }
+}
+
+// Whatever the game is being drawn onto. Everything the screens need is built out of
+// these, so the game itself never needs to know about a graphics library.
+// Positions are in pixels from the top left, text is positioned by its baseline
+pub trait Renderer {
+    // one square of a piece or the board
+    fn draw_cell(&mut self, x: f32, y: f32, size: f32, colour: Colour);
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, font_size: f32, colour: Colour);
+    // a filled rectangle, for backgrounds, bars & the like
+    fn draw_panel(&mut self, x: f32, y: f32, width: f32, height: f32, colour: Colour);
+    // how wide some text would be drawn
+    fn measure_text(&self, text: &str, font_size: f32) -> f32;
+    // width & height of whatever is being drawn on, the window or the picture
+    fn get_size(&self) -> (f32, f32);
+    // One square drawn from a skin's picture. Renderers that can't draw pictures
+    // fill it with the tile's average colour instead
+    fn draw_skin_cell(&mut self, skin: &Skin, tile: u32, x: f32, y: f32, size: f32) {
+        self.draw_cell(x, y, size, skin.average_colour(tile));
+    }
+}
+
+// Draws onto another renderer scaled & moved, so the screens laid out for an 800x600
+// window fill one of any size
+struct ScaledRenderer<'a, R: Renderer> {
+    renderer: &'a mut R,
+    area: Rect,
+    scale: f32,
+}
+impl<'a, R: Renderer> ScaledRenderer<'a, R> {
+    fn new(renderer: &'a mut R, layout: &Layout) -> Self {
+        Self {
+            renderer,
+            area: layout.menu,
+            scale: layout.menu_scale(),
+        }
+    }
+    fn position(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.area.x + x * self.scale, self.area.y + y * self.scale)
+    }
+}
+impl<R: Renderer> Renderer for ScaledRenderer<'_, R> {
+    fn draw_cell(&mut self, x: f32, y: f32, size: f32, colour: Colour) {
+        let (x, y) = self.position(x, y);
+        self.renderer.draw_cell(x, y, size * self.scale, colour);
+    }
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, font_size: f32, colour: Colour) {
+        let (x, y) = self.position(x, y);
+        self.renderer
+            .draw_text(text, x, y, font_size * self.scale, colour);
+    }
+    fn draw_panel(&mut self, x: f32, y: f32, width: f32, height: f32, colour: Colour) {
+        let (x, y) = self.position(x, y);
+        self.renderer
+            .draw_panel(x, y, width * self.scale, height * self.scale, colour);
+    }
+    fn measure_text(&self, text: &str, font_size: f32) -> f32 {
+        self.renderer.measure_text(text, font_size * self.scale) / self.scale
+    }
+    fn get_size(&self) -> (f32, f32) {
+        (DESIGN_WIDTH, DESIGN_HEIGHT)
+    }
+    fn draw_skin_cell(&mut self, skin: &Skin, tile: u32, x: f32, y: f32, size: f32) {
+        let (x, y) = self.position(x, y);
+        self.renderer
+            .draw_skin_cell(skin, tile, x, y, size * self.scale);
+    }
+}
+
+// the big line at the top of the stats, whatever matters most in the mode
+pub fn draw_headline(renderer: &mut impl Renderer, text: &str, layout: &Layout) {
+    let font_size = layout.scaled(30.);
+    let (x, y) = (layout.stats.x, layout.stats.y + layout.scaled(24.0));
+    renderer.draw_text(text, x, y, font_size, Colour::WHITE);
+}
+#[must_use]
+pub fn headline(game: &Game) -> String {
+    // Master is played for the grade
+    if let Some(master) = game.get_master() {
+        return format!("Grade: {}", master.get_grade(game.get_score()));
+    }
+    // a puzzle shows what it's asking for
+    if let Some(puzzle) = game.get_puzzle() {
+        return puzzle.get_goal().description();
+    }
+    if let Some(trainer) = game.get_finesse() {
+        return format!("Pieces left: {}", trainer.get_pieces_left());
+    }
+    // an opener can't be finished with a piece in the wrong place
+    if let Some(opener) = game.get_opener() {
+        return match opener.get_misplaced() {
+            0 => format!("Pieces left: {}", opener.get_pieces_left()),
+            _ => String::from("Wrong spot, undo"),
+        };
+    }
+    // races show how far there is to go, everything else the score
+    match (game.get_mode().ranking(), game.get_lines_left()) {
+        (Ranking::Time, Some(lines)) => format!("Lines left: {lines}"),
+        _ => format!("Score: {}", game.get_score()),
+    }
+}
+
+// one square of a piece or the walls, in the theme's block style
+pub fn draw_block(
+    renderer: &mut impl Renderer,
+    theme: &Theme,
+    square: u32,
+    (x, y): (f32, f32),
+    size: f32,
+) {
+    let colour = theme.colour(square);
+    let edge = (size / 8.0).round().max(1.0);
+    match (theme.block_style, &theme.skin_image) {
+        (BlockStyle::Skin, Some(skin)) => {
+            renderer.draw_skin_cell(skin, Skin::tile_for(square), x, y, size);
+        }
+        (BlockStyle::Bevelled, _) => {
+            let light = colour.mix(Colour::WHITE, 0.45);
+            let dark = colour.mix(Colour::BLACK, 0.4);
+            renderer.draw_cell(x, y, size, colour);
+            renderer.draw_panel(x, y, size, edge, light);
+            renderer.draw_panel(x, y, edge, size, light);
+            renderer.draw_panel(x, y + size - edge, size, edge, dark);
+            renderer.draw_panel(x + size - edge, y, edge, size, dark);
+        }
+        (BlockStyle::Outline, _) => {
+            renderer.draw_cell(x, y, size, colour.with_alpha(colour.a * 0.2));
+            renderer.draw_panel(x, y, size, edge, colour);
+            renderer.draw_panel(x, y + size - edge, size, edge, colour);
+            renderer.draw_panel(x, y, edge, size, colour);
+            renderer.draw_panel(x + size - edge, y, edge, size, colour);
+        }
+        // a skin style without a skin falls back to plain squares
+        (BlockStyle::Flat | BlockStyle::Skin, _) => renderer.draw_cell(x, y, size, colour),
+    }
+}
+
+// The board's top left corner is at origin, with squares scale pixels across
+pub fn draw_board(
+    renderer: &mut impl Renderer,
+    board: &Board,
+    (left, top): (f32, f32),
+    scale: f32,
+    theme: &Theme,
+) {
+    // the inside of the walls
+    let inside_width = (BOARD_WIDTH - 2) as f32 * scale;
+    let inside_height = (BOARD_HEIGHT - 1) as f32 * scale;
+    let inside_left = left + scale;
+    renderer.draw_panel(
+        inside_left,
+        top,
+        inside_width,
+        inside_height,
+        theme.background,
+    );
+    if let Some(grid) = theme.grid {
+        let thickness = (scale / 30.0).round().max(1.0);
+        for column in 2..BOARD_WIDTH - 1 {
+            let x = left + column as f32 * scale;
+            renderer.draw_panel(x, top, thickness, inside_height, grid);
+        }
+        for row in 1..BOARD_HEIGHT - 1 {
+            let y = top + row as f32 * scale;
+            renderer.draw_panel(inside_left, y, inside_width, thickness, grid);
+        }
+    }
+    if theme.border_style == BorderStyle::Line {
+        let thickness = (scale / 6.0).round().max(1.0);
+        let line_left = inside_left - thickness;
+        let line_right = inside_left + inside_width;
+        let line_height = inside_height + thickness;
+        renderer.draw_panel(line_left, top, thickness, line_height, theme.wall);
+        renderer.draw_panel(line_right, top, thickness, line_height, theme.wall);
+        renderer.draw_panel(
+            line_left,
+            top + inside_height,
+            inside_width + thickness * 2.0,
+            thickness,
+            theme.wall,
+        );
+    }
+    for y in 0..BOARD_HEIGHT {
+        for x in 0..BOARD_WIDTH {
+            let Some(square) = board
+                .get_board_character_at_coordinate(&UCoordinate::new(x, y))
+                .to_digit(10)
+            else {
+                continue;
+            };
+            if square == WALL_SQUARE && theme.border_style != BorderStyle::Blocks {
+                continue;
+            }
+            draw_block(
+                renderer,
+                theme,
+                square,
+                (left + x as f32 * scale, top + y as f32 * scale),
+                scale,
+            );
+        }
+    }
+}
+pub fn draw_tetromino(
+    renderer: &mut impl Renderer,
+    tetromino: &mut Tetromino,
+    (left, top): (f32, f32),
+    scale: f32,
+    theme: &Theme,
+) {
+    for y in 0..TETROMINO_SIZE {
+        for x in 0..TETROMINO_SIZE {
+            if tetromino.get_val_at_xy(&UCoordinate::new(x, y)) == 'X' {
+                draw_block(
+                    renderer,
+                    theme,
+                    tetromino.get_colour(),
+                    (
+                        left + (x as i32 + tetromino.get_coordinates().x) as f32 * scale,
+                        top + (y as i32 + tetromino.get_coordinates().y) as f32 * scale,
+                    ),
+                    scale,
+                );
+            }
+        }
+    }
+}
+// a faint outline of where the piece will land
+pub fn draw_ghost(
+    renderer: &mut impl Renderer,
+    ghost: &Tetromino,
+    (left, top): (f32, f32),
+    scale: f32,
+    theme: &Theme,
+) {
+    let colour = theme.colour(ghost.get_colour()).with_alpha(0.3);
+    for cell in ghost.get_cells() {
+        let (x, y) = (left + cell.x as f32 * scale, top + cell.y as f32 * scale);
+        renderer.draw_cell(x, y, scale, colour);
+    }
+}
+// where the opener still wants its pieces, fainter than the ghost
+fn draw_targets(
+    renderer: &mut impl Renderer,
+    opener: &OpenerState,
+    (left, top): (f32, f32),
+    scale: f32,
+    theme: &Theme,
+) {
+    for target in opener.get_targets_left() {
+        let colour = theme.pieces.get(target.shape).with_alpha(0.15);
+        for cell in target.get_cells() {
+            let (x, y) = (left + cell.x as f32 * scale, top + cell.y as f32 * scale);
+            renderer.draw_cell(x, y, scale, colour);
+        }
+    }
+}
+// a small picture of a piece that isn't on the board, for the next queue & hold
+fn draw_preview(
+    renderer: &mut impl Renderer,
+    shape: TetrominoShape,
+    (x, y): (f32, f32),
+    scale: f32,
+    theme: &Theme,
+) {
+    let tetromino = Tetromino::new(shape);
+    let origin = tetromino.get_coordinates();
+    for cell in tetromino.get_cells() {
+        draw_block(
+            renderer,
+            theme,
+            tetromino.get_colour(),
+            (
+                x + (cell.x - origin.x) as f32 * scale,
+                y + (cell.y - origin.y) as f32 * scale,
+            ),
+            scale,
+        );
+    }
+}
+pub fn draw_next_and_hold(
+    renderer: &mut impl Renderer,
+    game: &Game,
+    layout: &Layout,
+    theme: &Theme,
+) {
+    let font_size = layout.scaled(24.);
+    let preview_scale = layout.cell / 2.0;
+    let next = layout.next;
+    renderer.draw_text("Next", next.x, next.y + font_size, font_size, Colour::WHITE);
+    for (index, shape) in game.get_next(NEXT_QUEUE_SIZE).iter().enumerate() {
+        let y = next.y + layout.scaled(40.0 + index as f32 * 70.0);
+        draw_preview(renderer, *shape, (next.x, y), preview_scale, theme);
+    }
+    let hold = layout.hold;
+    renderer.draw_text("Hold", hold.x, hold.y + font_size, font_size, Colour::WHITE);
+    if let Some(shape) = game.get_held() {
+        let y = hold.y + layout.scaled(40.0);
+        draw_preview(renderer, shape, (hold.x, y), preview_scale, theme);
+    }
+}
+// just the board with the piece & where it will land, no panels
+pub fn draw_playfield(
+    renderer: &mut impl Renderer,
+    game: &Game,
+    origin: (f32, f32),
+    scale: f32,
+    theme: &Theme,
+) {
+    let mut tetromino = game.get_current_tetromino();
+    draw_board(renderer, game.get_board(), origin, scale, theme);
+    if let Some(opener) = game.get_opener() {
+        draw_targets(renderer, opener, origin, scale, theme);
+    }
+    if !game.is_between_pieces() {
+        draw_ghost(renderer, &game.get_ghost(), origin, scale, theme);
+        draw_tetromino(renderer, &mut tetromino, origin, scale, theme);
+    }
+}
+// The line clear animations, lock flash & particles, over the top of the board
+pub fn draw_effects(
+    renderer: &mut impl Renderer,
+    board: &Board,
+    effects: &Effects,
+    (left, top): (f32, f32),
+    scale: f32,
+    theme: &Theme,
+) {
+    if let Some(row_clear) = effects.get_row_clear() {
+        let progress = row_clear.get_progress();
+        let inside_width = (BOARD_WIDTH - 2) as f32 * scale;
+        for row in &row_clear.rows {
+            // the game may have taken the row away a moment before the animation finished
+            let square = board.get_board_character_at_coordinate(&UCoordinate::new(1, *row));
+            if square.to_digit(10) != Some(FLASH_SQUARE) {
+                continue;
+            }
+            let y = top + *row as f32 * scale;
+            match row_clear.effect {
+                LineClearEffect::Flash => {}
+                LineClearEffect::Dissolve => {
+                    for column in 1..BOARD_WIDTH - 1 {
+                        if effects::dissolved(*row, column, progress) {
+                            let x = left + column as f32 * scale;
+                            renderer.draw_cell(x, y, scale, theme.background);
+                        }
+                    }
+                }
+                LineClearEffect::Slide => {
+                    renderer.draw_panel(left + scale, y, inside_width, scale, theme.background);
+                    // squares that have reached the right hand wall are gone
+                    let offset = progress * inside_width;
+                    for column in 1..BOARD_WIDTH - 1 {
+                        let x = column as f32 * scale + offset;
+                        if x + scale <= scale + inside_width {
+                            draw_block(renderer, theme, FLASH_SQUARE, (left + x, y), scale);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let (cells, brightness) = effects.get_lock_flash();
+    let flash = Colour::WHITE.with_alpha(0.6 * brightness);
+    for cell in cells {
+        let (x, y) = (left + cell.x as f32 * scale, top + cell.y as f32 * scale);
+        renderer.draw_cell(x, y, scale, flash);
+    }
+    let size = (scale / 4.0).max(1.0);
+    for particle in effects.get_particles() {
+        let colour = theme
+            .colour(particle.square)
+            .with_alpha(1.0 - particle.get_progress());
+        let x = left + particle.x * scale - size / 2.0;
+        let y = top + particle.y * scale - size / 2.0;
+        renderer.draw_cell(x, y, size, colour);
+    }
+}
+pub fn draw_game(
+    renderer: &mut impl Renderer,
+    game: &Game,
+    effects: &Effects,
+    layout: &Layout,
+    theme: &Theme,
+) {
+    let (shake_x, shake_y) = effects.get_shake_offset();
+    let origin = (
+        layout.board.x + shake_x * layout.cell,
+        layout.board.y + shake_y * layout.cell,
+    );
+    draw_playfield(renderer, game, origin, layout.cell, theme);
+    draw_effects(
+        renderer,
+        game.get_board(),
+        effects,
+        origin,
+        layout.cell,
+        theme,
+    );
+    draw_headline(renderer, &headline(game), layout);
+    draw_statistics(renderer, game, layout, theme);
+    draw_next_and_hold(renderer, game, layout, theme);
+}
+pub fn draw_playback(
+    renderer: &mut impl Renderer,
+    playback: &Playback,
+    effects: &Effects,
+    layout: &Layout,
+    theme: &Theme,
+) {
+    draw_game(renderer, playback.get_game(), effects, layout, theme);
+    let ticks_per_second = f64::from(constants::TICKS_PER_SECOND);
+    let position = format!(
+        "Replay {} / {}",
+        format_time(playback.get_tick() as f64 / ticks_per_second),
+        format_time(playback.get_replay().get_length() as f64 / ticks_per_second),
+    );
+    let mut speed = format!("{}x", playback.get_speed());
+    if playback.is_paused() {
+        speed.push_str("  Paused");
+    }
+    let info = layout.info;
+    let line_height = layout.scaled(20.0);
+    for (index, (line, colour)) in [
+        (position.as_str(), Colour::YELLOW),
+        (speed.as_str(), Colour::YELLOW),
+        ("Enter pause", Colour::WHITE),
+        ("Left/Right seek", Colour::WHITE),
+        ("Up/Down speed", Colour::WHITE),
+        ("Esc back", Colour::WHITE),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let y = info.y + line_height * (index + 1) as f32;
+        renderer.draw_text(line, info.x, y, layout.scaled(18.), colour);
+    }
+}
+pub fn draw_statistics(renderer: &mut impl Renderer, game: &Game, layout: &Layout, theme: &Theme) {
+    let statistics = game.get_statistics();
+    let font_size = layout.scaled(24.);
+    let (x, top) = (layout.stats.x, layout.stats.y);
+    let lines = [
+        format!("Lines: {}", statistics.get_lines()),
+        match (game.get_master(), game.get_puzzle()) {
+            (Some(master), _) => {
+                format!("Level: {}/{}", master.get_level(), master.get_section_end())
+            }
+            (None, Some(puzzle)) => format!("Pieces to come: {}", puzzle.get_pieces_left()),
+            (None, None) => format!("Level: {}", game.get_level()),
+        },
+        match game.get_time_left() {
+            Some(left) => format!("Time left: {}", format_precise_time(left)),
+            None => format!("Time: {}", format_precise_time(statistics.get_time())),
+        },
+        format!("Pieces: {}", statistics.get_pieces()),
+        format!("PPS: {:.2}", statistics.pieces_per_second()),
+        format!("APM: {:.1}", statistics.attack_per_minute()),
+        format!("KPP: {:.2}", statistics.keys_per_piece()),
+        format!("Finesse faults: {}", statistics.get_finesse_faults()),
+    ];
+    for (index, line) in lines.iter().enumerate() {
+        let y = top + layout.scaled(60.0 + index as f32 * 28.0);
+        renderer.draw_text(line, x, y, font_size, Colour::WHITE);
+    }
+    // how many of each piece have turned up, as a bar chart
+    let most = TetrominoShape::ALL
+        .iter()
+        .map(|shape| statistics.get_spawned(*shape))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    for (index, shape) in TetrominoShape::ALL.iter().enumerate() {
+        let y = top + layout.scaled(298.0 + index as f32 * 20.0);
+        let text_y = y + layout.scaled(14.0);
+        let font_size = layout.scaled(20.);
+        let count = statistics.get_spawned(*shape);
+        let letter = shape.letter().to_string();
+        renderer.draw_text(&letter, x, text_y, font_size, Colour::WHITE);
+        renderer.draw_panel(
+            x + layout.scaled(20.0),
+            y,
+            layout.scaled(150.0) * count as f32 / most as f32,
+            layout.scaled(15.0),
+            theme.pieces.get(*shape),
+        );
+        let count_x = x + layout.scaled(180.0);
+        renderer.draw_text(
+            &count.to_string(),
+            count_x,
+            text_y,
+            font_size,
+            Colour::WHITE,
+        );
+    }
+    // the finesse trainer says what the last piece it took back should have been
+    if let Some(fault) = game.get_finesse().and_then(FinesseState::get_last_fault) {
+        let needed = finesse::presses(&fault.path);
+        for (index, (line, colour)) in [
+            (
+                format!(
+                    "{} took {}, needs {needed}",
+                    fault.shape.letter(),
+                    fault.presses
+                ),
+                Colour::WHITE,
+            ),
+            (finesse::describe(&fault.path), Colour::YELLOW),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let y = top + layout.scaled(430.0 + index as f32 * 24.0);
+            renderer.draw_text(line, x, y, layout.scaled(20.), *colour);
+        }
+    }
+}
+fn draw_overlay(renderer: &mut impl Renderer) {
+    // darken whatever is underneath so an overlay can be read
+    let (width, height) = renderer.get_size();
+    renderer.draw_panel(0.0, 0.0, width, height, Colour::new(0.0, 0.0, 0.0, 0.85));
+}
+pub fn draw_menu<T: Copy>(renderer: &mut impl Renderer, title: &str, menu: &Menu<T>) {
+    let font_size = 30.;
+    renderer.draw_text(title, 500.0, 60.0, font_size, Colour::WHITE);
+    for (index, (label, _)) in menu.get_items().iter().enumerate() {
+        let colour = if index == menu.get_selected_index() {
+            Colour::YELLOW
+        } else {
+            Colour::WHITE
+        };
+        renderer.draw_text(label, 500.0, 120.0 + index as f32 * 40.0, font_size, colour);
+    }
+}
+pub fn draw_options_menu(renderer: &mut impl Renderer, menu: &OptionsMenu, settings: &Settings) {
+    let font_size = 30.;
+    renderer.draw_text("Options", 200.0, 60.0, font_size, Colour::WHITE);
+    for (index, item) in OptionItem::ALL.iter().enumerate() {
+        let colour = if *item == menu.get_selected() {
+            Colour::YELLOW
+        } else {
+            Colour::WHITE
+        };
+        let y = 90.0 + index as f32 * 22.0;
+        renderer.draw_text(item.label(), 200.0, y, 24., colour);
+        renderer.draw_text(&item.format_value(settings), 460.0, y, 24., colour);
+    }
+    renderer.draw_text(
+        "Left/Right to change, Esc to close",
+        200.0,
+        520.0,
+        20.,
+        Colour::WHITE,
+    );
+}
+fn format_time(seconds: f64) -> String {
+    let whole_seconds = seconds as u32;
+    format!("{}:{:02}", whole_seconds / 60, whole_seconds % 60)
+}
+// to the millisecond, for races against the clock
+fn format_precise_time(seconds: f64) -> String {
+    let milliseconds = (seconds * 1000.0).round() as u64;
+    let whole_seconds = milliseconds / 1000;
+    format!(
+        "{}:{:02}.{:03}",
+        whole_seconds / 60,
+        whole_seconds % 60,
+        milliseconds % 1000
+    )
+}
+// what a finished game is judged on
+fn format_result(mode: GameMode, result: &HighScore) -> String {
+    match mode.ranking() {
+        Ranking::Score => format!("Score: {}", result.score),
+        Ranking::Time => format!("Time: {}", format_precise_time(result.time)),
+    }
+}
+// how the lines were cleared, for the end of a game
+#[must_use]
+pub fn clear_breakdown(statistics: &Statistics) -> String {
+    format!(
+        "Singles {}  Doubles {}  Triples {}  Tetrises {}  T-spins {}",
+        statistics.get_clears(1),
+        statistics.get_clears(2),
+        statistics.get_clears(3),
+        statistics.get_clears(4),
+        statistics.get_t_spins()
+    )
+}
+// the list of puzzles, with what the selected one asks for and what it gives you to do it
+fn draw_puzzle_select(renderer: &mut impl Renderer, menu: &Menu<usize>, puzzles: &[Puzzle]) {
+    draw_menu(renderer, "Select puzzle", menu);
+    let Some(puzzle) = menu.get_selected().and_then(|index| puzzles.get(index)) else {
+        return;
+    };
+    let pieces: String = puzzle.pieces.iter().map(|shape| shape.letter()).collect();
+    for (index, line) in [
+        puzzle.goal.description(),
+        puzzle.description.clone(),
+        format!("Pieces: {pieces}"),
+    ]
+    .iter()
+    .enumerate()
+    {
+        let y = 400.0 + index as f32 * 25.0;
+        renderer.draw_text(line, 500.0, y, 20., Colour::WHITE);
+    }
+}
+// the list of openers, with what the selected one is for and the order it's dealt in
+fn draw_opener_select(renderer: &mut impl Renderer, menu: &Menu<usize>, openers: &[Opener]) {
+    draw_menu(renderer, "Select opener", menu);
+    let Some(opener) = menu.get_selected().and_then(|index| openers.get(index)) else {
+        return;
+    };
+    let pieces: String = opener.order.iter().map(|shape| shape.letter()).collect();
+    for (index, line) in [opener.description.clone(), format!("Pieces: {pieces}")]
+        .iter()
+        .enumerate()
+    {
+        let y = 400.0 + index as f32 * 25.0;
+        renderer.draw_text(line, 500.0, y, 20., Colour::WHITE);
+    }
+}
+pub fn draw_high_score_table(
+    renderer: &mut impl Renderer,
+    table: &[HighScore],
+    mode: GameMode,
+    highlight: Option<usize>,
+    (x, y): (f32, f32),
+) {
+    let font_size = 20.;
+    let columns = [0.0, 40.0, 200.0, 300.0, 370.0, 440.0, 520.0];
+    // whatever the table is ordered by comes straight after the name
+    let by_time = mode.ranking() == Ranking::Time;
+    let headings = if by_time {
+        ["#", "Name", "Time", "Lines", "Level", "Score", "Date"]
+    } else {
+        ["#", "Name", "Score", "Lines", "Level", "Time", "Date"]
+    };
+    for (heading, column) in headings.iter().zip(columns) {
+        renderer.draw_text(heading, x + column, y, font_size, Colour::GRAY);
+    }
+    if table.is_empty() {
+        renderer.draw_text("No scores yet", x, y + 30.0, font_size, Colour::WHITE);
+    }
+    for (index, entry) in table.iter().enumerate() {
+        let colour = if highlight == Some(index) {
+            Colour::YELLOW
+        } else {
+            Colour::WHITE
+        };
+        let row_y = y + 30.0 + index as f32 * 25.0;
+        let (first, second) = if by_time {
+            (format_precise_time(entry.time), entry.score.to_string())
+        } else {
+            (entry.score.to_string(), format_time(entry.time))
+        };
+        let values = [
+            format!("{}", index + 1),
+            entry.name.clone(),
+            first,
+            entry.lines.to_string(),
+            entry.level.to_string(),
+            second,
+            entry.date.clone(),
+        ];
+        for (value, column) in values.iter().zip(columns) {
+            renderer.draw_text(value, x + column, row_y, font_size, colour);
+        }
+    }
+}
+pub fn draw_screen(renderer: &mut impl Renderer, screen: &Screen, context: &Context) {
+    let settings = &context.settings;
+    let theme = context.get_theme();
+    let (width, height) = renderer.get_size();
+    let layout = Layout::new(width, height, settings.visuals.draw_scale);
+    match screen {
+        Screen::Play(game) => draw_game(renderer, game, &context.effects, &layout, theme),
+        Screen::Replay(playback) => {
+            draw_playback(renderer, playback, &context.effects, &layout, theme);
+        }
+        Screen::Pause(menu) => {
+            draw_overlay(renderer);
+            draw_menu(&mut ScaledRenderer::new(renderer, &layout), "Paused", menu);
+        }
+        Screen::Options(menu) => {
+            draw_overlay(renderer);
+            draw_options_menu(&mut ScaledRenderer::new(renderer, &layout), menu, settings);
+        }
+        _ => draw_menu_screen(&mut ScaledRenderer::new(renderer, &layout), screen, context),
+    }
+}
+// the screens that are only text, laid out for an 800x600 window
+fn draw_menu_screen(renderer: &mut impl Renderer, screen: &Screen, context: &Context) {
+    match screen {
+        Screen::Title(menu) => {
+            renderer.draw_text("Rust Tetris", 40.0, 100.0, 60., Colour::WHITE);
+            draw_menu(renderer, "", menu);
+        }
+        Screen::ModeSelect(menu) => {
+            draw_menu(renderer, "Select mode", menu);
+            if let Some(mode) = menu.get_selected() {
+                renderer.draw_text(mode.description(), 500.0, 400.0, 20., Colour::WHITE);
+            }
+        }
+        Screen::PuzzleSelect(menu) => draw_puzzle_select(renderer, menu, &context.puzzles),
+        Screen::OpenerSelect(menu) => draw_opener_select(renderer, menu, &context.openers),
+        Screen::Replays { menu, .. } => {
+            draw_menu(renderer, "Replays", menu);
+            if menu.get_items().is_empty() {
+                renderer.draw_text("No replays yet", 500.0, 120.0, 30., Colour::WHITE);
+            }
+        }
+        Screen::NameEntry { summary, entry } => {
+            renderer.draw_text("New high score!", 40.0, 100.0, 60., Colour::WHITE);
+            renderer.draw_text(
+                &format_result(summary.mode, &summary.result),
+                40.0,
+                160.0,
+                30.,
+                Colour::WHITE,
+            );
+            renderer.draw_text("Enter your name:", 40.0, 220.0, 30., Colour::WHITE);
+            renderer.draw_text(&entry.get_text(), 40.0, 260.0, 30., Colour::YELLOW);
+            // underline the letter being edited
+            let cursor_x =
+                40.0 + renderer.measure_text(&entry.get_text()[..entry.get_cursor()], 30.);
+            renderer.draw_panel(cursor_x, 266.0, 15.0, 3.0, Colour::YELLOW);
+            renderer.draw_text("Enter to save", 40.0, 320.0, 20., Colour::WHITE);
+        }
+        Screen::Results {
+            summary,
+            rank,
+            menu,
+        } => {
+            let mode = summary.mode;
+            let title = match (summary.complete, &summary.puzzle) {
+                (true, _) => mode.complete_title(),
+                (false, Some(_)) => "Not solved.",
+                (false, None) => "Game Over.",
+            };
+            renderer.draw_text(title, 40.0, 100.0, 60., Colour::WHITE);
+            let result = match summary.puzzle.as_ref().or(summary.opener.as_ref()) {
+                Some(name) => format!("{} - {name}", mode.name()),
+                None => format!("{} - {}", mode.name(), format_result(mode, &summary.result)),
+            };
+            let result = match summary.grade {
+                Some(grade) => format!("{result}  Grade {grade}"),
+                None => result,
+            };
+            renderer.draw_text(&result, 40.0, 160.0, 30., Colour::WHITE);
+            renderer.draw_text(
+                &clear_breakdown(&summary.statistics),
+                40.0,
+                200.0,
+                20.,
+                Colour::GRAY,
+            );
+            draw_menu(renderer, "", menu);
+            if mode.has_high_scores() {
+                draw_high_score_table(
+                    renderer,
+                    context.high_scores.get_table(mode),
+                    mode,
+                    *rank,
+                    (40.0, 240.0),
+                );
+            }
+        }
+        Screen::HighScores { mode_index } => {
+            let mode = GameMode::with_high_scores()[*mode_index];
+            renderer.draw_text("High scores", 40.0, 60.0, 30., Colour::WHITE);
+            renderer.draw_text(
+                &format!("< {} >", mode.name()),
+                40.0,
+                100.0,
+                30.,
+                Colour::YELLOW,
+            );
+            draw_high_score_table(
+                renderer,
+                context.high_scores.get_table(mode),
+                mode,
+                None,
+                (40.0, 150.0),
+            );
+            renderer.draw_text("Esc to go back", 40.0, 460.0, 20., Colour::WHITE);
+        }
+        Screen::Play(_) | Screen::Replay(_) | Screen::Pause(_) | Screen::Options(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Ruleset;
+
+    // remembers what was drawn rather than drawing it
+    #[derive(Default)]
+    struct RecordingRenderer {
+        cells: Vec<(f32, f32, Colour)>,
+        text: Vec<String>,
+    }
+    impl Renderer for RecordingRenderer {
+        fn draw_cell(&mut self, x: f32, y: f32, _size: f32, colour: Colour) {
+            self.cells.push((x, y, colour));
+        }
+        fn draw_text(&mut self, text: &str, _x: f32, _y: f32, _font_size: f32, _colour: Colour) {
+            self.text.push(text.to_string());
+        }
+        fn draw_panel(&mut self, _x: f32, _y: f32, _width: f32, _height: f32, _colour: Colour) {}
+        fn measure_text(&self, text: &str, font_size: f32) -> f32 {
+            text.len() as f32 * font_size / 2.0
+        }
+        fn get_size(&self) -> (f32, f32) {
+            (DESIGN_WIDTH, DESIGN_HEIGHT)
+        }
+    }
+    #[test]
+    fn test_empty_board_draws_the_walls() {
+        let mut renderer = RecordingRenderer::default();
+        draw_board(
+            &mut renderer,
+            &Board::new(),
+            (0.0, 0.0),
+            10.0,
+            &Theme::classic(),
+        );
+        // both sides plus the floor, which shares its corners with the sides
+        let walls = (BOARD_HEIGHT * 2 + BOARD_WIDTH - 2) as usize;
+        assert_eq!(renderer.cells.len(), walls);
+        assert!(renderer.cells.contains(&(0.0, 0.0, Colour::WHITE)));
+    }
+    #[test]
+    fn test_game_draws_piece_and_score() {
+        let mut renderer = RecordingRenderer::default();
+        let game = Game::new(GameMode::Classic, Ruleset::default(), 3);
+        let theme = Theme::classic();
+        let layout = Layout::new(800.0, 600.0, 10.0);
+        draw_game(&mut renderer, &game, &Effects::new(), &layout, &theme);
+        let colour = theme.colour(game.get_current_tetromino().get_colour());
+        let piece_cells = renderer
+            .cells
+            .iter()
+            .filter(|(_, _, cell_colour)| *cell_colour == colour)
+            .count();
+        // the piece on the board plus it turning up in the next queue
+        assert!(piece_cells >= 4);
+        assert!(renderer.text.contains(&"Score: 0".to_string()));
+    }
+    #[test]
+    fn test_sprint_shows_lines_left_and_time() {
+        let game = Game::new(GameMode::Sprint, Ruleset::default(), 3);
+        assert_eq!(headline(&game), "Lines left: 40");
+        assert_eq!(format_precise_time(83.4567), "1:23.457");
+        assert_eq!(format_precise_time(59.9996), "1:00.000");
+        let mut renderer = RecordingRenderer::default();
+        let layout = Layout::new(800.0, 600.0, 10.0);
+        draw_game(
+            &mut renderer,
+            &game,
+            &Effects::new(),
+            &layout,
+            &Theme::classic(),
+        );
+        assert!(renderer.text.contains(&"Time: 0:00.000".to_string()));
+    }
+    #[test]
+    fn test_ultra_counts_down() {
+        let game = Game::new(GameMode::Ultra, Ruleset::default(), 3);
+        let mut renderer = RecordingRenderer::default();
+        let layout = Layout::new(800.0, 600.0, 10.0);
+        draw_game(
+            &mut renderer,
+            &game,
+            &Effects::new(),
+            &layout,
+            &Theme::classic(),
+        );
+        assert!(renderer.text.contains(&"Time left: 2:00.000".to_string()));
+        assert!(renderer.text.contains(&"Score: 0".to_string()));
+        assert_eq!(
+            clear_breakdown(game.get_statistics()),
+            "Singles 0  Doubles 0  Triples 0  Tetrises 0  T-spins 0"
+        );
+    }
+    #[test]
+    fn test_master_shows_the_grade_and_section() {
+        let game = Game::new(GameMode::Master, Ruleset::default(), 3);
+        let mut renderer = RecordingRenderer::default();
+        let layout = Layout::new(800.0, 600.0, 10.0);
+        draw_game(
+            &mut renderer,
+            &game,
+            &Effects::new(),
+            &layout,
+            &Theme::classic(),
+        );
+        assert!(renderer.text.contains(&"Grade: 9".to_string()));
+        assert!(renderer.text.contains(&"Level: 0/100".to_string()));
+    }
+}
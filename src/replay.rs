@@ -0,0 +1,717 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::{Game, GameMode, Ruleset, TickClock};
+use crate::highscores::timestamp;
+use crate::input::{Action, ActionState};
+use crate::opener::Opener;
+use crate::puzzle::Puzzle;
+use crate::settings::{HandlingSettings, RulesSettings};
+use crate::storage;
+
+// A replay is everything needed to play a game again exactly: the seed, the ruleset
+// and what was held down on each tick, plus the result it should end up with.
+//
+// The file layout, with every fixed size number little endian:
+//   "RTRP", format version (u8), game mode (u8), seed (u64)
+//   rules: speed, speed increase, show filled lines time (f64 each),
+//          score increment, completed lines increment (u32 each),
+//          time limit (f64, from version 2), start level (u32, from version 3),
+//          dig rows (u32) & rising garbage (f64, from version 4)
+//   handling: DAS, ARR, soft drop speed (f64 each)
+//   setup (from version 5): the length (varint) of the puzzle or opener the game was
+//          played on as TOML, in the same layout as their files, then the text. 0 for none
+//   the number of input changes, then for each change the ticks since the last one
+//   and the actions now down/pressed (varints, Undo from version 6)
+//   result: ticks (varint), score, lines, level (u32 each), board hash (u64)
+const REPLAY_MAGIC: &[u8; 4] = b"RTRP";
+pub const REPLAY_VERSION: u8 = 6;
+const REPLAY_DIRECTORY: &str = "replays";
+const REPLAY_EXTENSION: &str = "replay";
+
+// The actions a replay records, in the order of their bits. Only ever add to the end
+const RECORDED_ACTIONS: [Action; 8] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::SoftDrop,
+    Action::HardDrop,
+    Action::Rotate,
+    Action::RotateCounterClockwise,
+    Action::Hold,
+    Action::Undo,
+];
+// "down" uses the low bits of an action set, "pressed" the same bits shifted up by this
+const PRESSED_SHIFT: u32 = 16;
+
+pub const PLAYBACK_SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const NORMAL_SPEED_INDEX: usize = 2;
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    NotAReplay,
+    UnsupportedVersion(u8),
+    UnknownMode(u8),
+    Corrupt,
+}
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "could not access the replay: {error}"),
+            ReplayError::NotAReplay => write!(f, "this is not a replay file"),
+            ReplayError::UnsupportedVersion(version) => {
+                write!(f, "replay format version {version} is not supported")
+            }
+            ReplayError::UnknownMode(mode) => write!(f, "unknown game mode {mode}"),
+            ReplayError::Corrupt => write!(f, "the replay is damaged or cut short"),
+        }
+    }
+}
+impl std::error::Error for ReplayError {}
+impl From<io::Error> for ReplayError {
+    fn from(error: io::Error) -> Self {
+        if error.kind() == io::ErrorKind::UnexpectedEof {
+            ReplayError::Corrupt
+        } else {
+            ReplayError::Io(error)
+        }
+    }
+}
+
+// What the game said the result was when the replay was saved
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayResult {
+    pub ticks: u64,
+    pub score: u32,
+    pub lines: u32,
+    pub level: u32,
+    pub board_hash: u64,
+}
+
+impl ReplayResult {
+    // every field that doesn't match, as (name, this result's value, the other's value)
+    #[must_use]
+    pub fn differences(&self, other: &ReplayResult) -> Vec<(&'static str, String, String)> {
+        let fields = [
+            ("ticks", self.ticks.to_string(), other.ticks.to_string()),
+            ("score", self.score.to_string(), other.score.to_string()),
+            ("lines", self.lines.to_string(), other.lines.to_string()),
+            ("level", self.level.to_string(), other.level.to_string()),
+            (
+                "board hash",
+                format!("{:016x}", self.board_hash),
+                format!("{:016x}", other.board_hash),
+            ),
+        ];
+        fields
+            .into_iter()
+            .filter(|(_, mine, theirs)| mine != theirs)
+            .collect()
+    }
+}
+
+// What a game started from that the seed can't make again: the puzzle, with its board
+// and pieces, or the opener that deals the first bag
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplaySetup {
+    Puzzle(Box<Puzzle>), // a board is a lot bigger than an opener
+    Opener(Opener),
+}
+
+// The actions in use from this tick until the next input
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplayInput {
+    pub tick: u64,
+    pub actions: ActionState,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub mode: GameMode,
+    pub seed: u64,
+    pub ruleset: Ruleset,
+    pub setup: Option<ReplaySetup>, // only in Puzzle & Opener modes
+    pub inputs: Vec<ReplayInput>,
+    pub result: ReplayResult,
+}
+impl Replay {
+    #[must_use]
+    pub fn new(mode: GameMode, seed: u64, ruleset: Ruleset) -> Self {
+        Self {
+            mode,
+            seed,
+            ruleset,
+            setup: None,
+            inputs: Vec::new(),
+            result: ReplayResult::default(),
+        }
+    }
+    #[must_use]
+    pub fn default_directory() -> Option<PathBuf> {
+        storage::data_path(REPLAY_DIRECTORY)
+    }
+    // a new file name for a game of this mode that sorts after all the older ones
+    #[must_use]
+    pub fn file_name(mode: GameMode) -> String {
+        format!(
+            "{}_{}.{REPLAY_EXTENSION}",
+            timestamp(),
+            mode.name().to_lowercase()
+        )
+    }
+    // every replay in a directory, newest first
+    #[must_use]
+    pub fn list(directory: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(directory) else {
+            return Vec::new();
+        };
+        let mut ret_paths: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == REPLAY_EXTENSION))
+            .collect();
+        ret_paths.sort();
+        ret_paths.reverse();
+        ret_paths
+    }
+
+    // Note what was held on a tick. Only changes are kept, and only the gameplay actions
+    pub fn record(&mut self, tick: u64, actions: &ActionState) {
+        let actions = actions_from_bits(actions_to_bits(actions));
+        let previous = self
+            .inputs
+            .last()
+            .map_or(ActionState::new(), |input| input.actions);
+        if actions != previous {
+            self.inputs.push(ReplayInput { tick, actions });
+        }
+    }
+    #[must_use]
+    pub fn get_length(&self) -> u64 {
+        self.result.ticks
+    }
+    // Play the whole replay through with no window and report how it really ended,
+    // to check against the result it claims
+    #[must_use]
+    pub fn simulate(&self) -> ReplayResult {
+        let mut playback = Playback::new(self.clone());
+        playback.seek(self.get_length());
+        playback.get_game().get_replay().result
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, ReplayError> {
+        Self::read_from(&mut fs::File::open(path)?)
+    }
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Replay, ReplayError> {
+        Self::read_from(&mut bytes)
+    }
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut ret_bytes = Vec::new();
+        // writing into memory can't fail
+        let _ = self.write_to(&mut ret_bytes);
+        ret_bytes
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ReplayError> {
+        writer.write_all(REPLAY_MAGIC)?;
+        writer.write_all(&[REPLAY_VERSION, self.mode.id()])?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        let rules = &self.ruleset.rules;
+        for value in [
+            rules.speed,
+            rules.speed_increase,
+            rules.show_filled_lines_time,
+        ] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        for value in [rules.score_increment, rules.score_completed_lines_increment] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&rules.time_limit.to_le_bytes())?;
+        writer.write_all(&rules.start_level.to_le_bytes())?;
+        writer.write_all(&rules.dig_rows.to_le_bytes())?;
+        writer.write_all(&rules.rising_garbage.to_le_bytes())?;
+        let handling = &self.ruleset.handling;
+        for value in [
+            handling.delayed_auto_shift,
+            handling.auto_repeat_rate,
+            handling.soft_drop_speed,
+        ] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        // puzzles & openers are made of strings, so writing one out can't really fail
+        let setup = match &self.setup {
+            Some(setup) => storage::to_toml(setup).map_err(|_| ReplayError::Corrupt)?,
+            None => String::new(),
+        };
+        write_varint(writer, setup.len() as u64)?;
+        writer.write_all(setup.as_bytes())?;
+        write_varint(writer, self.inputs.len() as u64)?;
+        let mut previous_tick = 0;
+        for input in &self.inputs {
+            write_varint(writer, input.tick - previous_tick)?;
+            write_varint(writer, u64::from(actions_to_bits(&input.actions)))?;
+            previous_tick = input.tick;
+        }
+        write_varint(writer, self.result.ticks)?;
+        for value in [self.result.score, self.result.lines, self.result.level] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&self.result.board_hash.to_le_bytes())?;
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Replay, ReplayError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != REPLAY_MAGIC {
+            return Err(ReplayError::NotAReplay);
+        }
+        let [version, mode] = read_array(reader)?;
+        if version == 0 || version > REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        let mode = GameMode::from_id(mode).ok_or(ReplayError::UnknownMode(mode))?;
+        let seed = u64::from_le_bytes(read_array(reader)?);
+        let mut rules = RulesSettings {
+            speed: read_f64(reader)?,
+            speed_increase: read_f64(reader)?,
+            show_filled_lines_time: read_f64(reader)?,
+            score_increment: read_u32(reader)?,
+            score_completed_lines_increment: read_u32(reader)?,
+            ..RulesSettings::default()
+        };
+        // older replays are all of modes without these, so the defaults will do
+        if version >= 2 {
+            rules.time_limit = read_f64(reader)?;
+        }
+        if version >= 3 {
+            rules.start_level = read_u32(reader)?;
+        }
+        if version >= 4 {
+            rules.dig_rows = read_u32(reader)?;
+            rules.rising_garbage = read_f64(reader)?;
+        }
+        let handling = HandlingSettings {
+            delayed_auto_shift: read_f64(reader)?,
+            auto_repeat_rate: read_f64(reader)?,
+            soft_drop_speed: read_f64(reader)?,
+            ..HandlingSettings::default()
+        };
+        // older replays never had puzzles or openers, they weren't recorded
+        let setup = if version >= 5 {
+            read_setup(reader)?
+        } else {
+            None
+        };
+        let input_count = read_varint(reader)?;
+        let mut inputs = Vec::new();
+        let mut tick: u64 = 0;
+        for _ in 0..input_count {
+            tick = tick
+                .checked_add(read_varint(reader)?)
+                .ok_or(ReplayError::Corrupt)?;
+            let bits = u32::try_from(read_varint(reader)?).map_err(|_| ReplayError::Corrupt)?;
+            inputs.push(ReplayInput {
+                tick,
+                actions: actions_from_bits(bits),
+            });
+        }
+        let result = ReplayResult {
+            ticks: read_varint(reader)?,
+            score: read_u32(reader)?,
+            lines: read_u32(reader)?,
+            level: read_u32(reader)?,
+            board_hash: u64::from_le_bytes(read_array(reader)?),
+        };
+        Ok(Replay {
+            mode,
+            seed,
+            ruleset: Ruleset { rules, handling },
+            setup,
+            inputs,
+            result,
+        })
+    }
+}
+
+pub(crate) fn actions_to_bits(actions: &ActionState) -> u32 {
+    let mut ret_bits = 0;
+    for (bit, action) in RECORDED_ACTIONS.iter().enumerate() {
+        if actions.is_down(*action) {
+            ret_bits |= 1 << bit;
+        }
+        if actions.is_pressed(*action) {
+            ret_bits |= 1 << (bit as u32 + PRESSED_SHIFT);
+        }
+    }
+    ret_bits
+}
+
+pub(crate) fn actions_from_bits(bits: u32) -> ActionState {
+    let mut ret_actions = ActionState::new();
+    for (bit, action) in RECORDED_ACTIONS.iter().enumerate() {
+        if bits & (1 << (bit as u32 + PRESSED_SHIFT)) != 0 {
+            ret_actions.set_pressed(*action);
+        } else if bits & (1 << bit) != 0 {
+            ret_actions.set_down(*action);
+        }
+    }
+    ret_actions
+}
+
+// Numbers that are usually small are written 7 bits at a time (LEB128),
+// with the top bit of each byte saying whether there is more to come
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> Result<u64, ReplayError> {
+    let mut ret_value: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let [byte] = read_array(reader)?;
+        ret_value |= u64::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(ret_value);
+        }
+    }
+    Err(ReplayError::Corrupt)
+}
+
+fn read_setup<R: Read>(reader: &mut R) -> Result<Option<ReplaySetup>, ReplayError> {
+    let length = read_varint(reader)?;
+    if length == 0 {
+        return Ok(None);
+    }
+    // a damaged length could be anything, so only read what's really there
+    let mut bytes = Vec::new();
+    reader.take(length).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != length {
+        return Err(ReplayError::Corrupt);
+    }
+    let text = String::from_utf8(bytes).map_err(|_| ReplayError::Corrupt)?;
+    storage::from_toml(&text)
+        .map(Some)
+        .map_err(|_| ReplayError::Corrupt)
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> Result<[u8; N], ReplayError> {
+    let mut ret_bytes = [0; N];
+    reader.read_exact(&mut ret_bytes)?;
+    Ok(ret_bytes)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, ReplayError> {
+    Ok(u32::from_le_bytes(read_array(reader)?))
+}
+
+fn read_f64<R: Read>(reader: &mut R) -> Result<f64, ReplayError> {
+    Ok(f64::from_le_bytes(read_array(reader)?))
+}
+
+// Watching a replay. The game is rebuilt from the seed and fed the recorded inputs,
+// at anything from a quarter to eight times normal speed. Seeking backwards starts
+// again from the beginning and runs forward to the right tick, which is quick
+// because nothing needs drawing on the way
+pub struct Playback {
+    replay: Replay,
+    game: Game,
+    next_input: usize,
+    actions: ActionState,
+    clock: TickClock,
+    paused: bool,
+    speed_index: usize,
+}
+impl Playback {
+    #[must_use]
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            game: Self::start(&replay),
+            replay,
+            next_input: 0,
+            actions: ActionState::new(),
+            clock: TickClock::default(),
+            paused: false,
+            speed_index: NORMAL_SPEED_INDEX,
+        }
+    }
+    fn start(replay: &Replay) -> Game {
+        let ruleset = replay.ruleset.clone();
+        match &replay.setup {
+            Some(ReplaySetup::Puzzle(puzzle)) => Game::puzzle(puzzle, ruleset),
+            Some(ReplaySetup::Opener(opener)) => Game::opener(opener, ruleset, replay.seed),
+            None => Game::new(replay.mode, ruleset, replay.seed),
+        }
+    }
+    #[must_use]
+    pub fn get_game(&self) -> &Game {
+        &self.game
+    }
+    #[must_use]
+    pub fn get_replay(&self) -> &Replay {
+        &self.replay
+    }
+    #[must_use]
+    pub fn get_tick(&self) -> u64 {
+        self.game.get_ticks()
+    }
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.get_tick() >= self.replay.get_length() || self.game.is_game_over()
+    }
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+    #[must_use]
+    pub fn get_speed(&self) -> f64 {
+        PLAYBACK_SPEEDS[self.speed_index]
+    }
+    pub fn faster(&mut self) {
+        self.speed_index = (self.speed_index + 1).min(PLAYBACK_SPEEDS.len() - 1);
+    }
+    pub fn slower(&mut self) {
+        self.speed_index = self.speed_index.saturating_sub(1);
+    }
+
+    // Play one tick of the recording
+    pub fn step(&mut self) {
+        if self.is_finished() {
+            return;
+        }
+        while let Some(input) = self.replay.inputs.get(self.next_input)
+            && input.tick <= self.get_tick()
+        {
+            self.actions = input.actions;
+            self.next_input += 1;
+        }
+        self.game.tick(&self.actions);
+    }
+
+    pub fn update(&mut self, delta_time: f64) {
+        self.game.clear_events();
+        if self.paused {
+            return;
+        }
+        for _ in 0..self.clock.advance(delta_time * self.get_speed()) {
+            self.step();
+        }
+    }
+
+    pub fn seek(&mut self, tick: u64) {
+        if tick < self.get_tick() {
+            self.game = Self::start(&self.replay);
+            self.next_input = 0;
+            self.actions = ActionState::new();
+        }
+        while self.get_tick() < tick && !self.is_finished() {
+            self.step();
+            // nobody sees the events on the way through
+            self.game.clear_events();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::SavedGame;
+    // play a game with a made up pattern of inputs until it ends or runs out of time
+    fn play_game(seed: u64) -> Game {
+        let mut game = Game::new(GameMode::Classic, Ruleset::default(), seed);
+        let pattern = [
+            Action::MoveLeft,
+            Action::Rotate,
+            Action::MoveRight,
+            Action::HardDrop,
+            Action::Hold,
+            Action::RotateCounterClockwise,
+        ];
+        let mut tick = 0;
+        while !game.is_game_over() && tick < 5000 {
+            let mut actions = ActionState::new();
+            if tick % 7 == 0 {
+                actions.set_pressed(pattern[tick / 7 % pattern.len()]);
+            }
+            if tick % 50 < 20 {
+                actions.set_down(Action::MoveRight);
+            }
+            game.tick(&actions);
+            tick += 1;
+        }
+        game
+    }
+    #[test]
+    fn test_round_trip() {
+        let replay = play_game(1234).get_replay();
+        assert!(!replay.inputs.is_empty());
+        let bytes = replay.to_bytes();
+        assert_eq!(Replay::from_bytes(&bytes).unwrap(), replay);
+    }
+    #[test]
+    fn test_playback_matches_game() {
+        let game = play_game(5678);
+        let mut playback = Playback::new(game.get_replay());
+        while !playback.is_finished() {
+            playback.step();
+        }
+        let played = playback.get_game();
+        assert_eq!(played.get_ticks(), game.get_ticks());
+        assert_eq!(played.get_score(), game.get_score());
+        assert_eq!(played.get_lines(), game.get_lines());
+        assert_eq!(played.get_board().get_hash(), game.get_board().get_hash());
+    }
+    #[test]
+    fn test_simulate_catches_tampering() {
+        let mut replay = play_game(99).get_replay();
+        assert_eq!(replay.simulate(), replay.result);
+        assert!(replay.result.differences(&replay.simulate()).is_empty());
+        replay.result.score += 100;
+        let differences = replay.result.differences(&replay.simulate());
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].0, "score");
+    }
+    // hard drop every piece, moved along a bit so they don't all stack up in one place
+    fn drop_pieces(game: &mut Game, ticks: u64) {
+        for tick in 0..ticks {
+            let mut actions = ActionState::new();
+            match tick % 20 {
+                0 => actions.set_pressed(Action::MoveLeft),
+                5 if tick % 40 < 20 => actions.set_pressed(Action::Rotate),
+                10 => actions.set_pressed(Action::HardDrop),
+                _ => {}
+            }
+            game.tick(&actions);
+        }
+    }
+    fn assert_plays_back(game: &Game) {
+        let replay = Replay::from_bytes(&game.get_replay().to_bytes()).unwrap();
+        assert_eq!(replay, game.get_replay());
+        assert!(replay.result.differences(&replay.simulate()).is_empty());
+        let mut playback = Playback::new(replay);
+        playback.seek(u64::MAX);
+        assert_eq!(playback.get_game().get_board(), game.get_board());
+    }
+    #[test]
+    fn test_puzzle_plays_back() {
+        let puzzle = &Puzzle::built_in()[1];
+        let mut game = Game::puzzle(puzzle, Ruleset::default());
+        drop_pieces(&mut game, 200);
+        assert!(game.get_statistics().get_pieces() > 1);
+        assert_eq!(
+            game.get_replay().setup,
+            Some(ReplaySetup::Puzzle(Box::new(puzzle.clone())))
+        );
+        assert_plays_back(&game);
+        // a puzzle carried on from a save still has it
+        let mut game = Game::puzzle(puzzle, Ruleset::default());
+        drop_pieces(&mut game, 15);
+        let saved = SavedGame::from_toml(&game.to_saved().to_toml().unwrap()).unwrap();
+        let continued = Game::from_saved(&saved).unwrap();
+        assert_eq!(continued.get_replay(), game.get_replay());
+    }
+    #[test]
+    fn test_opener_plays_back() {
+        let opener = &Opener::built_in()[2];
+        let mut game = Game::opener(opener, Ruleset::default(), 8);
+        drop_pieces(&mut game, 100);
+        assert!(!game.is_game_over());
+        // taking pieces back has to be played back too
+        let placed = game.get_statistics().get_pieces();
+        let mut undo = ActionState::new();
+        undo.set_pressed(Action::Undo);
+        for _ in 0..3 {
+            game.tick(&undo);
+            game.tick(&ActionState::new());
+        }
+        assert_eq!(game.get_statistics().get_pieces(), placed - 3);
+        assert_plays_back(&game);
+    }
+    #[test]
+    fn test_seek_backwards() {
+        let game = play_game(42);
+        let halfway = game.get_ticks() / 2;
+        let mut playback = Playback::new(game.get_replay());
+        playback.seek(halfway);
+        let board_halfway = playback.get_game().get_board().clone();
+        playback.seek(u64::MAX);
+        assert!(playback.is_finished());
+        playback.seek(halfway);
+        assert_eq!(playback.get_tick(), halfway);
+        assert_eq!(*playback.get_game().get_board(), board_halfway);
+    }
+    #[test]
+    fn test_bad_files() {
+        assert!(matches!(
+            Replay::from_bytes(b"nope"),
+            Err(ReplayError::NotAReplay)
+        ));
+        let mut bytes = play_game(1).get_replay().to_bytes();
+        bytes[4] = 99;
+        assert!(matches!(
+            Replay::from_bytes(&bytes),
+            Err(ReplayError::UnsupportedVersion(99))
+        ));
+        let bytes = play_game(1).get_replay().to_bytes();
+        assert!(matches!(
+            Replay::from_bytes(&bytes[..bytes.len() - 3]),
+            Err(ReplayError::Corrupt)
+        ));
+    }
+    #[test]
+    fn test_older_versions_still_load() {
+        let replay = play_game(7).get_replay();
+        let bytes = replay.to_bytes();
+        // version 4 didn't have the setup, which comes after the handling
+        let mut version_four = bytes.clone();
+        version_four[4] = 4;
+        assert_eq!(version_four.remove(94), 0);
+        assert_eq!(Replay::from_bytes(&version_four).unwrap(), replay);
+        // version 3 didn't have the dig rules, which follow the other rules
+        let mut version_three = version_four;
+        version_three[4] = 3;
+        version_three.drain(58..70);
+        assert_eq!(Replay::from_bytes(&version_three).unwrap(), replay);
+        // version 2 didn't have the start level before them either
+        let mut version_two = version_three;
+        version_two[4] = 2;
+        version_two.drain(54..58);
+        assert_eq!(Replay::from_bytes(&version_two).unwrap(), replay);
+        // and version 1 didn't have the time limit before it either
+        let mut version_one = version_two;
+        version_one[4] = 1;
+        version_one.drain(46..54);
+        assert_eq!(Replay::from_bytes(&version_one).unwrap(), replay);
+    }
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value).unwrap();
+            assert_eq!(read_varint(&mut bytes.as_slice()).unwrap(), value);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::tetromino::TetrominoShape;
+
+// A small seeded random number generator (SplitMix64). The game has its own rather
+// than using the engine's so that a seed always gives the same pieces on every machine,
+// which is what lets replays play back exactly
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+impl Rng {
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    // a seed for a new game, different every time
+    #[must_use]
+    pub fn random_seed() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos() as u64)
+    }
+    #[must_use]
+    pub fn get_state(&self) -> u64 {
+        self.state
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^ (value >> 31)
+    }
+    // a number from 0 up to but not including max
+    pub fn gen_range(&mut self, max: u32) -> u32 {
+        if max == 0 {
+            return 0;
+        }
+        (self.next_u64() % u64::from(max)) as u32
+    }
+}
+
+// Hands out pieces in shuffled bags of all seven, so there is never a long wait
+// for any one piece. Enough bags are kept ready to show the next queue
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bag {
+    rng: Rng,
+    queue: VecDeque<TetrominoShape>,
+}
+impl Bag {
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        let mut ret_bag = Self {
+            rng: Rng::new(seed),
+            queue: VecDeque::new(),
+        };
+        ret_bag.fill();
+        ret_bag
+    }
+    // A first bag that starts with these pieces, in this order, and carries on with the
+    // rest of the seven shuffled. The bags after it are shuffled as usual
+    #[must_use]
+    pub fn starting_with(seed: u64, first: &[TetrominoShape]) -> Self {
+        let mut ret_bag = Self {
+            rng: Rng::new(seed),
+            queue: first.iter().copied().collect(),
+        };
+        let mut rest: Vec<TetrominoShape> = TetrominoShape::ALL
+            .into_iter()
+            .filter(|shape| !first.contains(shape))
+            .collect();
+        for index in (1..rest.len()).rev() {
+            let other = ret_bag.rng.gen_range(index as u32 + 1) as usize;
+            rest.swap(index, other);
+        }
+        ret_bag.queue.extend(rest);
+        ret_bag.fill();
+        ret_bag
+    }
+    // pick up a bag where a saved game left it
+    #[must_use]
+    pub fn from_state(rng_state: u64, queue: &[TetrominoShape]) -> Self {
+        let mut ret_bag = Self {
+            rng: Rng::new(rng_state),
+            queue: queue.iter().copied().collect(),
+        };
+        ret_bag.fill();
+        ret_bag
+    }
+    #[must_use]
+    pub fn get_rng_state(&self) -> u64 {
+        self.rng.get_state()
+    }
+    #[must_use]
+    pub fn get_queue(&self) -> Vec<TetrominoShape> {
+        self.queue.iter().copied().collect()
+    }
+    fn fill(&mut self) {
+        while self.queue.len() < TetrominoShape::ALL.len() {
+            let mut shapes = TetrominoShape::ALL;
+            // Fisher-Yates shuffle
+            for index in (1..shapes.len()).rev() {
+                let other = self.rng.gen_range(index as u32 + 1) as usize;
+                shapes.swap(index, other);
+            }
+            self.queue.extend(shapes);
+        }
+    }
+    pub fn next_shape(&mut self) -> TetrominoShape {
+        let ret_shape = self.queue.pop_front().unwrap_or(TetrominoShape::I);
+        self.fill();
+        ret_shape
+    }
+    // the upcoming pieces in order, at most a bag's worth
+    #[must_use]
+    pub fn peek(&self, count: usize) -> Vec<TetrominoShape> {
+        self.queue.iter().take(count).copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_same_seed_same_numbers() {
+        let mut first = Rng::new(42);
+        let mut second = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(first.next_u64(), second.next_u64());
+        }
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+    }
+    #[test]
+    fn test_every_bag_has_every_piece() {
+        let mut bag = Bag::new(7);
+        for _ in 0..10 {
+            let mut counts = [0; 7];
+            for _ in 0..7 {
+                counts[bag.next_shape().index()] += 1;
+            }
+            assert_eq!(counts, [1; 7]);
+        }
+    }
+    #[test]
+    fn test_bag_starting_with() {
+        let first = [TetrominoShape::I, TetrominoShape::O, TetrominoShape::S];
+        let mut bag = Bag::starting_with(5, &first);
+        assert_eq!(bag.peek(3), first);
+        // the rest of the first bag is still one of each, and so is the next
+        for _ in 0..2 {
+            let mut counts = [0; 7];
+            for _ in 0..7 {
+                counts[bag.next_shape().index()] += 1;
+            }
+            assert_eq!(counts, [1; 7]);
+        }
+    }
+    #[test]
+    fn test_peek_matches_next() {
+        let mut bag = Bag::new(99);
+        let upcoming = bag.peek(5);
+        let drawn: Vec<TetrominoShape> = (0..5).map(|_| bag.next_shape()).collect();
+        assert_eq!(upcoming, drawn);
+    }
+}
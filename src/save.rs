@@ -0,0 +1,261 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Rotation;
+use crate::finesse::FinesseState;
+use crate::game::GameMode;
+use crate::handling::PieceHandling;
+use crate::master::MasterState;
+use crate::opener::OpenerState;
+use crate::puzzle::PuzzleState;
+use crate::replay::ReplaySetup;
+use crate::settings::{HandlingSettings, RulesSettings};
+use crate::stats::Statistics;
+use crate::storage::{self, StorageError};
+use crate::tetromino::TetrominoShape;
+
+const SAVE_FILE: &str = "savegame.toml";
+// Bump this whenever the layout changes. Older saves can then be upgraded as they are
+// loaded, saves from a newer version of the game are refused rather than misread
+pub const SAVE_VERSION: u32 = 8;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Storage(StorageError),
+    NewerVersion(u32),
+    Invalid(&'static str),
+}
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Storage(error) => write!(f, "{error}"),
+            SaveError::NewerVersion(version) => write!(
+                f,
+                "the game was saved by a newer version (save version {version})"
+            ),
+            SaveError::Invalid(what) => write!(f, "the saved {what} doesn't make sense"),
+        }
+    }
+}
+impl std::error::Error for SaveError {}
+impl From<StorageError> for SaveError {
+    fn from(error: StorageError) -> Self {
+        SaveError::Storage(error)
+    }
+}
+
+// Seeds & random number states use all 64 bits but TOML integers stop at i64::MAX,
+// so they are written as hex strings instead
+mod hex_u64 {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    #[allow(clippy::trivially_copy_pass_by_ref)] // serde hands the value over by reference
+    pub fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{value:016x}"))
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        u64::from_str_radix(&text, 16).map_err(D::Error::custom)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedTetromino {
+    pub shape: TetrominoShape,
+    pub rotation: Rotation,
+    pub x: i32,
+    pub y: i32,
+}
+
+// A game in progress, written out when the window is closed so it can be carried on
+// next time. Everything that affects what happens next is here, so a continued game
+// plays out exactly as it would have, and its replay still verifies
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedGame {
+    pub version: u32,
+    pub mode: GameMode,
+    #[serde(with = "hex_u64")]
+    pub seed: u64,
+    pub score: u32,
+    pub lines: u32,
+    pub speed: f64,
+    pub ticks: u64,
+    pub last_update: f64,
+    pub filled_lines_shown_at: Option<f64>,
+    pub held: Option<TetrominoShape>,
+    pub hold_used: bool,
+    pub piece_inputs: u32,
+    #[serde(default)]
+    pub rotated_last: bool, // for spotting a T-spin when the piece locks
+    // how long each direction has been held, so DAS & ARR carry on where they were.
+    // The timings come from the handling settings
+    #[serde(default)]
+    pub piece_handling: PieceHandling,
+    pub queue: Vec<TetrominoShape>,
+    #[serde(with = "hex_u64")]
+    pub rng_state: u64,
+    // one string per row, top first, in the board's own characters
+    pub board: Vec<String>,
+    // the replay so far as (tick, recorded actions)
+    pub inputs: Vec<(u64, u32)>,
+    pub current: SavedTetromino,
+    pub rules: RulesSettings,
+    pub handling: HandlingSettings,
+    pub statistics: Statistics,
+    #[serde(default)]
+    pub master: Option<MasterState>, // only in Master mode
+    #[serde(default)]
+    pub puzzle: Option<PuzzleState>, // only in Puzzle mode
+    #[serde(default)]
+    pub finesse: Option<FinesseState>, // only in Finesse mode
+    #[serde(default)]
+    pub opener: Option<OpenerState>, // only in Opener mode
+    // the puzzle or opener the game started from, for its replay
+    #[serde(default)]
+    pub setup: Option<ReplaySetup>,
+}
+impl SavedGame {
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        storage::data_path(SAVE_FILE)
+    }
+
+    pub fn load_from(path: &Path) -> Result<SavedGame, SaveError> {
+        Self::from_toml(&fs::read_to_string(path).map_err(StorageError::from)?)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), SaveError> {
+        Ok(storage::save_toml(path, self)?)
+    }
+
+    pub fn from_toml(text: &str) -> Result<SavedGame, SaveError> {
+        // look at the version before anything else, a newer layout may not parse at all
+        let table: toml::Table = storage::from_toml(text)?;
+        let version = table
+            .get("version")
+            .and_then(toml::Value::as_integer)
+            .ok_or(SaveError::Invalid("version"))?;
+        let version = u32::try_from(version).map_err(|_| SaveError::Invalid("version"))?;
+        if version > SAVE_VERSION {
+            return Err(SaveError::NewerVersion(version));
+        }
+        Ok(storage::from_toml(text)?)
+    }
+
+    pub fn to_toml(&self) -> Result<String, SaveError> {
+        Ok(storage::to_toml(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Game, Ruleset};
+    use crate::input::{Action, ActionState};
+    use crate::opener::Opener;
+    fn play(game: &mut Game, from: u64, to: u64) {
+        for tick in from..to {
+            let mut actions = ActionState::new();
+            // spread the pieces out along the board so the game lasts a while
+            let side = if tick / 45 % 2 == 0 {
+                Action::MoveLeft
+            } else {
+                Action::MoveRight
+            };
+            match tick % 45 {
+                0 => actions.set_pressed(Action::Hold),
+                10 => actions.set_pressed(Action::Rotate),
+                15..=30 => actions.set_down(side),
+                35 => actions.set_pressed(Action::HardDrop),
+                _ => {}
+            }
+            game.tick(&actions);
+        }
+    }
+    #[test]
+    fn test_continued_game_plays_out_the_same() {
+        let mut game = Game::new(GameMode::Classic, Ruleset::default(), u64::MAX - 5);
+        // part way through holding a direction, so the auto repeat has to carry on too
+        play(&mut game, 0, 425);
+        assert!(!game.is_game_over());
+        let text = game.to_saved().to_toml().unwrap();
+        let saved = SavedGame::from_toml(&text).unwrap();
+        assert_eq!(saved, game.to_saved());
+        let mut continued = Game::from_saved(&saved).unwrap();
+        play(&mut game, 425, 900);
+        play(&mut continued, 425, 900);
+        assert_eq!(continued.get_board(), game.get_board());
+        assert_eq!(continued.get_score(), game.get_score());
+        assert_eq!(continued.get_next(5), game.get_next(5));
+        assert_eq!(continued.get_replay(), game.get_replay());
+    }
+    #[test]
+    fn test_newer_version_refused() {
+        let game = Game::new(GameMode::Classic, Ruleset::default(), 1);
+        let mut saved = game.to_saved();
+        saved.version = SAVE_VERSION + 1;
+        let text = saved.to_toml().unwrap();
+        assert!(matches!(
+            SavedGame::from_toml(&text),
+            Err(SaveError::NewerVersion(_))
+        ));
+    }
+    #[test]
+    fn test_version_4_has_no_opener() {
+        let opener = &Opener::built_in()[0];
+        let game = Game::opener(opener, Ruleset::default(), 1);
+        // version 4 came before openers
+        let mut table: toml::Table =
+            storage::from_toml(&game.to_saved().to_toml().unwrap()).unwrap();
+        table.remove("opener");
+        table.insert(String::from("version"), toml::Value::Integer(4));
+        let saved = SavedGame::from_toml(&storage::to_toml(&table).unwrap()).unwrap();
+        assert_eq!(saved.version, 4);
+        assert_eq!(saved.opener, None);
+        assert_eq!(saved.board, game.to_saved().board);
+    }
+    #[test]
+    fn test_version_7_stats_level_ignored() {
+        let game = Game::new(GameMode::Classic, Ruleset::default(), 1);
+        // version 7 kept a level in the stats as well, it was never read
+        let mut table: toml::Table =
+            storage::from_toml(&game.to_saved().to_toml().unwrap()).unwrap();
+        table["statistics"]
+            .as_table_mut()
+            .unwrap()
+            .insert(String::from("level"), toml::Value::Integer(1));
+        table.insert(String::from("version"), toml::Value::Integer(7));
+        let saved = SavedGame::from_toml(&storage::to_toml(&table).unwrap()).unwrap();
+        assert_eq!(saved.statistics, game.to_saved().statistics);
+    }
+    #[test]
+    fn test_bad_board_refused() {
+        let game = Game::new(GameMode::Classic, Ruleset::default(), 1);
+        let mut saved = game.to_saved();
+        saved.board.pop();
+        assert!(matches!(
+            Game::from_saved(&saved),
+            Err(SaveError::Invalid("board"))
+        ));
+    }
+    #[test]
+    fn test_gap_in_the_wall_refused() {
+        let game = Game::new(GameMode::Classic, Ruleset::default(), 1);
+        let mut saved = game.to_saved();
+        saved.board[5].replace_range(0..1, " ");
+        assert!(matches!(
+            Game::from_saved(&saved),
+            Err(SaveError::Invalid("board"))
+        ));
+        let mut saved = game.to_saved();
+        let floor = saved.board.len() - 1;
+        saved.board[floor].replace_range(3..4, "1");
+        assert!(matches!(
+            Game::from_saved(&saved),
+            Err(SaveError::Invalid("board"))
+        ));
+    }
+}
@@ -0,0 +1,817 @@
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::audio::Audio;
+use crate::constants::TICKS_PER_SECOND;
+use crate::effects::Effects;
+use crate::game::{Game, GameMode, Ranking, Ruleset};
+use crate::highscores::{HighScore, HighScores, NameEntry, today};
+use crate::input::{Action, ActionState};
+use crate::menu::Menu;
+use crate::opener::Opener;
+use crate::options::OptionsMenu;
+use crate::puzzle::Puzzle;
+use crate::replay::{Playback, Replay};
+use crate::rng::Rng;
+use crate::save::SavedGame;
+use crate::settings::Settings;
+use crate::stats::Statistics;
+use crate::theme::Theme;
+use crate::warning;
+
+const SEEK_SECONDS: u64 = 5; // how far left & right skip through a replay
+const REPLAYS_LISTED: usize = 10; // only the newest fit on the screen
+// used if the list of themes has been left empty
+static DEFAULT_THEME: LazyLock<Theme> = LazyLock::new(Theme::default);
+
+// Everything the screens share that lives longer than any one screen
+pub struct Context {
+    pub settings: Settings,
+    pub high_scores: HighScores,
+    // every theme that can be picked in the options, the built in ones first
+    pub themes: Vec<Theme>,
+    // every puzzle that can be played, the built in ones first
+    pub puzzles: Vec<Puzzle>,
+    // the openers that can be practised
+    pub openers: Vec<Opener>,
+    // animations for whichever game is being played or watched
+    pub effects: Effects,
+    // sounds for the same, and the music
+    pub audio: Audio,
+    // where finished games are saved as replays. Nothing is saved without one
+    pub replay_directory: Option<PathBuf>,
+    // where a game in progress is kept when the window is closed
+    pub save_path: Option<PathBuf>,
+}
+impl Default for Context {
+    fn default() -> Self {
+        Self {
+            settings: Settings::default(),
+            high_scores: HighScores::default(),
+            themes: Theme::built_in(),
+            puzzles: Puzzle::built_in(),
+            openers: Opener::built_in(),
+            effects: Effects::new(),
+            audio: Audio::default(),
+            replay_directory: None,
+            save_path: None,
+        }
+    }
+}
+impl Context {
+    // the theme picked in the settings
+    #[must_use]
+    pub fn get_theme(&self) -> &Theme {
+        Theme::find(&self.themes, &self.settings.visuals.theme).unwrap_or(&DEFAULT_THEME)
+    }
+    #[must_use]
+    pub fn theme_names(&self) -> Vec<String> {
+        self.themes.iter().map(|theme| theme.name.clone()).collect()
+    }
+    #[must_use]
+    pub fn has_saved_game(&self) -> bool {
+        self.save_path.as_deref().is_some_and(Path::exists)
+    }
+    pub fn save_game(&self, game: &Game) {
+        let Some(path) = &self.save_path else {
+            return;
+        };
+        if game.is_game_over() {
+            return;
+        }
+        if let Err(error) = game.to_saved().save_to(path) {
+            warning!("Could not save the game: {error}");
+        }
+    }
+    // Load the saved game and delete it, so it can only be carried on once
+    fn take_saved_game(&self) -> Option<Game> {
+        let path = self.save_path.as_deref()?;
+        let game = SavedGame::load_from(path).and_then(|saved| Game::from_saved(&saved));
+        match game {
+            Ok(game) => {
+                if let Err(error) = std::fs::remove_file(path) {
+                    warning!("Could not remove the saved game: {error}");
+                }
+                Some(game)
+            }
+            Err(error) => {
+                warning!("Could not continue the saved game: {error}");
+                None
+            }
+        }
+    }
+    fn save_replay(&self, game: &Game) {
+        let Some(directory) = &self.replay_directory else {
+            return;
+        };
+        // a game that was quit straight away isn't worth keeping
+        if game.get_statistics().get_pieces() == 0 {
+            return;
+        }
+        let path = directory.join(Replay::file_name(game.get_mode()));
+        if let Err(error) = game.get_replay().save(&path) {
+            warning!("Could not save replay: {error}");
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TitleChoice {
+    Continue,
+    Play,
+    HighScores,
+    Replays,
+    Options,
+    Quit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseChoice {
+    Resume,
+    Options,
+    QuitToTitle,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResultsChoice {
+    PlayAgain,
+    QuitToTitle,
+}
+
+// How a finished game went, for the high scores & the results screen
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameSummary {
+    pub mode: GameMode,
+    pub result: HighScore,
+    // the mode's goal was reached rather than the stack topping out
+    pub complete: bool,
+    pub statistics: Statistics,
+    pub grade: Option<&'static str>, // only Master mode is graded
+    pub puzzle: Option<String>,      // the name of the puzzle, to play it again
+    pub opener: Option<String>,      // the same for the opener
+}
+impl GameSummary {
+    #[must_use]
+    pub fn from_game(game: &Game) -> Self {
+        Self {
+            mode: game.get_mode(),
+            result: HighScore {
+                name: String::new(),
+                score: game.get_score(),
+                lines: game.get_lines(),
+                level: game.get_level(),
+                time: game.get_time(),
+                date: today(),
+            },
+            complete: game.is_complete(),
+            statistics: game.get_statistics().clone(),
+            grade: game
+                .get_master()
+                .map(|master| master.get_grade(game.get_score())),
+            puzzle: game
+                .get_puzzle()
+                .map(|puzzle| puzzle.get_name().to_string()),
+            opener: game
+                .get_opener()
+                .map(|opener| opener.get_name().to_string()),
+        }
+    }
+}
+
+// Every screen in the game. Screens are stacked so that pause & options
+// can sit on top of whatever was there before and go back to it when closed
+pub enum Screen {
+    Title(Menu<TitleChoice>),
+    ModeSelect(Menu<GameMode>),
+    // the menu values index into the context's puzzles
+    PuzzleSelect(Menu<usize>),
+    // the same for the context's openers
+    OpenerSelect(Menu<usize>),
+    Options(OptionsMenu),
+    Play(Box<Game>),
+    Pause(Menu<PauseChoice>),
+    NameEntry {
+        summary: GameSummary,
+        entry: NameEntry,
+    },
+    Results {
+        summary: GameSummary,
+        // where the score landed in the high score table, if it made it
+        rank: Option<usize>,
+        menu: Menu<ResultsChoice>,
+    },
+    HighScores {
+        mode_index: usize,
+    },
+    // the saved replays, newest first. The menu values index into the paths
+    Replays {
+        menu: Menu<usize>,
+        paths: Vec<PathBuf>,
+    },
+    Replay(Box<Playback>),
+}
+
+// What a screen wants to happen once it has been updated
+pub enum Transition {
+    None,
+    Push(Screen),
+    Pop,
+    Replace(Screen),
+    // throw the whole stack away and start again from this screen
+    Reset(Screen),
+    Quit,
+}
+
+impl Screen {
+    #[must_use]
+    pub fn title(context: &Context) -> Screen {
+        let mut items = vec![
+            (String::from("Play"), TitleChoice::Play),
+            (String::from("High scores"), TitleChoice::HighScores),
+            (String::from("Replays"), TitleChoice::Replays),
+            (String::from("Options"), TitleChoice::Options),
+            (String::from("Quit"), TitleChoice::Quit),
+        ];
+        if context.has_saved_game() {
+            items.insert(0, (String::from("Continue"), TitleChoice::Continue));
+        }
+        Screen::Title(Menu::new(items))
+    }
+    #[must_use]
+    pub fn mode_select() -> Screen {
+        Screen::ModeSelect(Menu::new(
+            GameMode::ALL
+                .iter()
+                .map(|mode| (String::from(mode.name()), *mode))
+                .collect(),
+        ))
+    }
+    #[must_use]
+    pub fn play(mode: GameMode, settings: &Settings) -> Screen {
+        Screen::Play(Box::new(Game::new(
+            mode,
+            Ruleset::from_settings(settings),
+            Rng::random_seed(),
+        )))
+    }
+    #[must_use]
+    pub fn puzzle_select(puzzles: &[Puzzle]) -> Screen {
+        Screen::PuzzleSelect(Menu::new(
+            puzzles
+                .iter()
+                .enumerate()
+                .map(|(index, puzzle)| (puzzle.name.clone(), index))
+                .collect(),
+        ))
+    }
+    #[must_use]
+    pub fn play_puzzle(puzzle: &Puzzle, settings: &Settings) -> Screen {
+        Screen::Play(Box::new(Game::puzzle(
+            puzzle,
+            Ruleset::from_settings(settings),
+        )))
+    }
+    #[must_use]
+    pub fn opener_select(openers: &[Opener]) -> Screen {
+        Screen::OpenerSelect(Menu::new(
+            openers
+                .iter()
+                .enumerate()
+                .map(|(index, opener)| (opener.name.clone(), index))
+                .collect(),
+        ))
+    }
+    #[must_use]
+    pub fn play_opener(opener: &Opener, settings: &Settings) -> Screen {
+        Screen::Play(Box::new(Game::opener(
+            opener,
+            Ruleset::from_settings(settings),
+            Rng::random_seed(),
+        )))
+    }
+    #[must_use]
+    pub fn replays(context: &Context) -> Screen {
+        let mut paths = context
+            .replay_directory
+            .as_deref()
+            .map(Replay::list)
+            .unwrap_or_default();
+        paths.truncate(REPLAYS_LISTED);
+        let items = paths
+            .iter()
+            .enumerate()
+            .map(|(index, path)| {
+                let name = path
+                    .file_stem()
+                    .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+                (name, index)
+            })
+            .collect();
+        Screen::Replays {
+            menu: Menu::new(items),
+            paths,
+        }
+    }
+    #[must_use]
+    pub fn pause() -> Screen {
+        Screen::Pause(Menu::new(vec![
+            (String::from("Resume"), PauseChoice::Resume),
+            (String::from("Options"), PauseChoice::Options),
+            (String::from("Quit to title"), PauseChoice::QuitToTitle),
+        ]))
+    }
+    #[must_use]
+    pub fn results(summary: GameSummary, rank: Option<usize>) -> Screen {
+        Screen::Results {
+            summary,
+            rank,
+            menu: Menu::new(vec![
+                (String::from("Play again"), ResultsChoice::PlayAgain),
+                (String::from("Main menu"), ResultsChoice::QuitToTitle),
+            ]),
+        }
+    }
+
+    // Once a game is over either ask for a name, if it got a high score, or go straight to the results
+    fn game_over(game: &Game, high_scores: &HighScores) -> Screen {
+        let summary = GameSummary::from_game(game);
+        // a race only counts if it was finished
+        let counts = summary.complete || summary.mode.ranking() == Ranking::Score;
+        if counts && high_scores.qualifies(summary.mode, &summary.result) {
+            Screen::NameEntry {
+                summary,
+                entry: NameEntry::new(),
+            }
+        } else {
+            Screen::results(summary, None)
+        }
+    }
+
+    // The same mode again, or the same puzzle or opener. A puzzle that has gone since
+    // goes back to the list of them
+    fn play_again(summary: &GameSummary, context: &Context) -> Screen {
+        let settings = &context.settings;
+        if let Some(name) = &summary.opener {
+            return match context.openers.iter().find(|opener| &opener.name == name) {
+                Some(opener) => Screen::play_opener(opener, settings),
+                None => Screen::opener_select(&context.openers),
+            };
+        }
+        let Some(name) = &summary.puzzle else {
+            return Screen::play(summary.mode, settings);
+        };
+        match context.puzzles.iter().find(|puzzle| &puzzle.name == name) {
+            Some(puzzle) => Screen::play_puzzle(puzzle, settings),
+            None => Screen::puzzle_select(&context.puzzles),
+        }
+    }
+
+    // Overlays are drawn on top of the screen underneath them rather than replacing it
+    #[must_use]
+    pub fn is_overlay(&self) -> bool {
+        matches!(self, Screen::Pause(_) | Screen::Options(_))
+    }
+
+    pub fn update(
+        &mut self,
+        actions: &ActionState,
+        typed: &[char],
+        context: &mut Context,
+        delta_time: f64,
+    ) -> Transition {
+        let settings = &mut context.settings;
+        match self {
+            Screen::Title(menu) => Self::update_title(menu, actions, context),
+            Screen::ModeSelect(menu) => Self::update_mode_select(menu, actions, context),
+            Screen::PuzzleSelect(menu) => Self::update_puzzle_select(menu, actions, context),
+            Screen::OpenerSelect(menu) => Self::update_opener_select(menu, actions, context),
+            Screen::Options(menu) => {
+                if actions.is_pressed(Action::Back) {
+                    // leaving the options writes them out
+                    if let Err(error) = settings.save() {
+                        warning!("Could not save settings: {error}");
+                    }
+                    return Transition::Pop;
+                }
+                menu.update(actions, settings);
+                Transition::None
+            }
+            Screen::Play(game) => {
+                if actions.is_pressed(Action::Pause) {
+                    return Transition::Push(Screen::pause());
+                }
+                game.update(actions, delta_time);
+                context.effects.handle_events(
+                    game.get_events(),
+                    &context.settings.visuals,
+                    game.get_ruleset().rules.show_filled_lines_time,
+                );
+                context.effects.update(delta_time);
+                context
+                    .audio
+                    .handle_events(game.get_events(), &context.settings.audio);
+                if game.is_game_over() {
+                    context.save_replay(game);
+                    return Transition::Replace(Screen::game_over(game, &context.high_scores));
+                }
+                Transition::None
+            }
+            Screen::Pause(menu) => {
+                if actions.is_pressed(Action::Back) {
+                    return Transition::Pop;
+                }
+                match menu.update(actions) {
+                    Some(PauseChoice::Resume) => Transition::Pop,
+                    Some(PauseChoice::Options) => Transition::Push(Screen::Options(
+                        OptionsMenu::new().with_themes(context.theme_names()),
+                    )),
+                    Some(PauseChoice::QuitToTitle) => Transition::Reset(Screen::title(context)),
+                    None => Transition::None,
+                }
+            }
+            Screen::NameEntry { summary, entry } => {
+                if !entry.update(actions, typed) {
+                    return Transition::None;
+                }
+                summary.result.name = entry.get_name();
+                let rank = context
+                    .high_scores
+                    .insert(summary.mode, summary.result.clone());
+                if let Err(error) = context.high_scores.save() {
+                    warning!("Could not save high scores: {error}");
+                }
+                Transition::Replace(Screen::results(summary.clone(), rank))
+            }
+            Screen::Results { summary, menu, .. } => {
+                if actions.is_pressed(Action::Back) {
+                    return Transition::Reset(Screen::title(context));
+                }
+                match menu.update(actions) {
+                    Some(ResultsChoice::PlayAgain) => {
+                        Transition::Replace(Self::play_again(summary, context))
+                    }
+                    Some(ResultsChoice::QuitToTitle) => Transition::Reset(Screen::title(context)),
+                    None => Transition::None,
+                }
+            }
+            Screen::HighScores { mode_index } => {
+                if actions.is_pressed(Action::Back) || actions.is_pressed(Action::Confirm) {
+                    return Transition::Pop;
+                }
+                // left & right flick between the tables for each mode
+                let mode_count = GameMode::with_high_scores().len();
+                if actions.is_pressed(Action::MoveLeft) {
+                    *mode_index = (*mode_index + mode_count - 1) % mode_count;
+                }
+                if actions.is_pressed(Action::MoveRight) {
+                    *mode_index = (*mode_index + 1) % mode_count;
+                }
+                Transition::None
+            }
+            Screen::Replays { menu, paths } => Self::update_replays(menu, paths, actions),
+            Screen::Replay(playback) => {
+                Self::update_playback(playback, actions, context, delta_time)
+            }
+        }
+    }
+
+    fn update_title(
+        menu: &mut Menu<TitleChoice>,
+        actions: &ActionState,
+        context: &Context,
+    ) -> Transition {
+        match menu.update(actions) {
+            Some(TitleChoice::Continue) => match context.take_saved_game() {
+                Some(game) => Transition::Push(Screen::Play(Box::new(game))),
+                None => Transition::Reset(Screen::title(context)),
+            },
+            Some(TitleChoice::Play) => Transition::Push(Screen::mode_select()),
+            Some(TitleChoice::HighScores) => Transition::Push(Screen::HighScores { mode_index: 0 }),
+            Some(TitleChoice::Replays) => Transition::Push(Screen::replays(context)),
+            Some(TitleChoice::Options) => Transition::Push(Screen::Options(
+                OptionsMenu::new().with_themes(context.theme_names()),
+            )),
+            Some(TitleChoice::Quit) => Transition::Quit,
+            None => Transition::None,
+        }
+    }
+
+    // Puzzle mode picks a puzzle before there's a game to play, Opener mode an opener
+    fn update_mode_select(
+        menu: &mut Menu<GameMode>,
+        actions: &ActionState,
+        context: &Context,
+    ) -> Transition {
+        if actions.is_pressed(Action::Back) {
+            return Transition::Pop;
+        }
+        match menu.update(actions) {
+            Some(GameMode::Puzzle) => Transition::Replace(Screen::puzzle_select(&context.puzzles)),
+            Some(GameMode::Opener) => Transition::Replace(Screen::opener_select(&context.openers)),
+            Some(mode) => Transition::Replace(Screen::play(mode, &context.settings)),
+            None => Transition::None,
+        }
+    }
+
+    fn update_puzzle_select(
+        menu: &mut Menu<usize>,
+        actions: &ActionState,
+        context: &Context,
+    ) -> Transition {
+        if actions.is_pressed(Action::Back) {
+            return Transition::Replace(Screen::mode_select());
+        }
+        match menu.update(actions) {
+            Some(index) => Transition::Replace(Screen::play_puzzle(
+                &context.puzzles[index],
+                &context.settings,
+            )),
+            None => Transition::None,
+        }
+    }
+
+    fn update_opener_select(
+        menu: &mut Menu<usize>,
+        actions: &ActionState,
+        context: &Context,
+    ) -> Transition {
+        if actions.is_pressed(Action::Back) {
+            return Transition::Replace(Screen::mode_select());
+        }
+        match menu.update(actions) {
+            Some(index) => Transition::Replace(Screen::play_opener(
+                &context.openers[index],
+                &context.settings,
+            )),
+            None => Transition::None,
+        }
+    }
+
+    fn update_replays(
+        menu: &mut Menu<usize>,
+        paths: &[PathBuf],
+        actions: &ActionState,
+    ) -> Transition {
+        if actions.is_pressed(Action::Back) {
+            return Transition::Pop;
+        }
+        let Some(index) = menu.update(actions) else {
+            return Transition::None;
+        };
+        match Replay::load(&paths[index]) {
+            Ok(replay) => Transition::Push(Screen::Replay(Box::new(Playback::new(replay)))),
+            Err(error) => {
+                warning!("Could not load replay: {error}");
+                Transition::None
+            }
+        }
+    }
+
+    // Watching a replay: confirm pauses, up & down change speed, left & right skip
+    fn update_playback(
+        playback: &mut Playback,
+        actions: &ActionState,
+        context: &mut Context,
+        delta_time: f64,
+    ) -> Transition {
+        if actions.is_pressed(Action::Back) {
+            return Transition::Pop;
+        }
+        if actions.is_pressed(Action::Confirm) {
+            playback.toggle_pause();
+        }
+        if actions.is_pressed(Action::MenuUp) {
+            playback.faster();
+        }
+        if actions.is_pressed(Action::MenuDown) {
+            playback.slower();
+        }
+        let seek_ticks = SEEK_SECONDS * u64::from(TICKS_PER_SECOND);
+        if actions.is_pressed(Action::MoveLeft) {
+            playback.seek(playback.get_tick().saturating_sub(seek_ticks));
+        }
+        if actions.is_pressed(Action::MoveRight) {
+            playback.seek(playback.get_tick() + seek_ticks);
+        }
+        playback.update(delta_time);
+        let game = playback.get_game();
+        context.effects.handle_events(
+            game.get_events(),
+            &context.settings.visuals,
+            game.get_ruleset().rules.show_filled_lines_time,
+        );
+        context.effects.update(delta_time);
+        context
+            .audio
+            .handle_events(game.get_events(), &context.settings.audio);
+        Transition::None
+    }
+}
+
+pub struct ScreenStack {
+    screens: Vec<Screen>,
+}
+impl ScreenStack {
+    #[must_use]
+    pub fn new(context: &Context) -> Self {
+        Self {
+            screens: vec![Screen::title(context)],
+        }
+    }
+    #[must_use]
+    pub fn get_screens(&self) -> &[Screen] {
+        &self.screens
+    }
+    #[must_use]
+    pub fn current(&self) -> Option<&Screen> {
+        self.screens.last()
+    }
+    // The screens that need drawing this frame, bottom first.
+    // That is the top screen plus anything it is overlaid on
+    #[must_use]
+    pub fn visible(&self) -> &[Screen] {
+        let first_visible = self
+            .screens
+            .iter()
+            .rposition(|screen| !screen.is_overlay())
+            .unwrap_or(0);
+        &self.screens[first_visible..]
+    }
+
+    // Only the top screen is updated, anything underneath is frozen until it comes back.
+    // Returns false once the game should quit
+    pub fn update(
+        &mut self,
+        actions: &ActionState,
+        typed: &[char],
+        context: &mut Context,
+        delta_time: f64,
+    ) -> bool {
+        let Some(screen) = self.screens.last_mut() else {
+            return false;
+        };
+        let transition = screen.update(actions, typed, context, delta_time);
+        // a new game or replay starts without anything left over from the last one
+        if let Transition::Push(next) | Transition::Replace(next) | Transition::Reset(next) =
+            &transition
+            && matches!(next, Screen::Play(_) | Screen::Replay(_))
+        {
+            context.effects.clear();
+        }
+        // a game thrown away part way through still gets its replay saved
+        if matches!(transition, Transition::Reset(_) | Transition::Quit) {
+            for screen in &self.screens {
+                if let Screen::Play(game) = screen {
+                    context.save_replay(game);
+                }
+            }
+        }
+        let ret_running = self.apply(transition);
+        // the music plays while a game is on top, so it stops for pauses & menus
+        let level = match self.current() {
+            Some(Screen::Play(game)) => Some(game.get_level()),
+            Some(Screen::Replay(playback)) => Some(playback.get_game().get_level()),
+            _ => None,
+        };
+        context.audio.update_music(level, &context.settings.audio);
+        ret_running
+    }
+
+    // The window is closing, keep any game in progress to carry on next time
+    pub fn suspend(&self, context: &Context) {
+        for screen in &self.screens {
+            if let Screen::Play(game) = screen {
+                context.save_game(game);
+            }
+        }
+    }
+
+    pub fn apply(&mut self, transition: Transition) -> bool {
+        match transition {
+            Transition::None => {}
+            Transition::Push(screen) => self.screens.push(screen),
+            Transition::Pop => {
+                self.screens.pop();
+            }
+            Transition::Replace(screen) => {
+                self.screens.pop();
+                self.screens.push(screen);
+            }
+            Transition::Reset(screen) => {
+                self.screens.clear();
+                self.screens.push(screen);
+            }
+            Transition::Quit => self.screens.clear(),
+        }
+        !self.screens.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn pressed(action: Action) -> ActionState {
+        let mut actions = ActionState::new();
+        actions.set_pressed(action);
+        actions
+    }
+    #[test]
+    fn test_title_to_play_and_pause() {
+        let mut context = Context::default();
+        let mut stack = ScreenStack::new(&context);
+        assert!(stack.update(&pressed(Action::Confirm), &[], &mut context, 0.0));
+        assert!(matches!(stack.current(), Some(Screen::ModeSelect(_))));
+        stack.update(&pressed(Action::Confirm), &[], &mut context, 0.0);
+        assert!(matches!(stack.current(), Some(Screen::Play(_))));
+        // mode select is replaced so backing out of a game goes to the title
+        assert_eq!(stack.get_screens().len(), 2);
+
+        stack.update(&pressed(Action::Pause), &[], &mut context, 0.0);
+        assert!(matches!(stack.current(), Some(Screen::Pause(_))));
+        // the paused game is still drawn underneath the pause menu
+        assert_eq!(stack.visible().len(), 2);
+        stack.update(&pressed(Action::Back), &[], &mut context, 0.0);
+        assert!(matches!(stack.current(), Some(Screen::Play(_))));
+        assert_eq!(stack.visible().len(), 1);
+    }
+    #[test]
+    fn test_quit_to_title_resets_stack() {
+        let context = Context::default();
+        let mut stack = ScreenStack::new(&context);
+        stack.apply(Transition::Push(Screen::play(
+            GameMode::Classic,
+            &Settings::default(),
+        )));
+        stack.apply(Transition::Push(Screen::pause()));
+        stack.apply(Transition::Reset(Screen::title(&context)));
+        assert_eq!(stack.get_screens().len(), 1);
+        assert!(matches!(stack.current(), Some(Screen::Title(_))));
+    }
+    #[test]
+    fn test_quit_from_title() {
+        let mut context = Context::default();
+        let mut stack = ScreenStack::new(&context);
+        for _ in 0..4 {
+            stack.update(&pressed(Action::MenuDown), &[], &mut context, 0.0);
+        }
+        assert!(!stack.update(&pressed(Action::Confirm), &[], &mut context, 0.0));
+        assert!(stack.current().is_none());
+    }
+    #[test]
+    fn test_high_scores_back_to_title() {
+        let mut context = Context::default();
+        let mut stack = ScreenStack::new(&context);
+        stack.update(&pressed(Action::MenuDown), &[], &mut context, 0.0);
+        stack.update(&pressed(Action::Confirm), &[], &mut context, 0.0);
+        assert!(matches!(stack.current(), Some(Screen::HighScores { .. })));
+        stack.update(&pressed(Action::Back), &[], &mut context, 0.0);
+        assert!(matches!(stack.current(), Some(Screen::Title(_))));
+    }
+    #[test]
+    fn test_replays_saved_and_listed() {
+        let directory =
+            std::env::temp_dir().join(format!("rust_tetris_replays_{}", std::process::id()));
+        let context = Context {
+            replay_directory: Some(directory.clone()),
+            ..Context::default()
+        };
+        let mut game = Game::new(GameMode::Classic, Ruleset::default(), 1);
+        let mut drop = ActionState::new();
+        drop.set_pressed(Action::HardDrop);
+        game.tick(&drop);
+        context.save_replay(&game);
+        let Screen::Replays { menu, paths } = Screen::replays(&context) else {
+            panic!("expected the replays screen");
+        };
+        assert_eq!(menu.get_items().len(), 1);
+        assert_eq!(Replay::load(&paths[0]).unwrap(), game.get_replay());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+    #[test]
+    fn test_continue_saved_game() {
+        let path =
+            std::env::temp_dir().join(format!("rust_tetris_savegame_{}.toml", std::process::id()));
+        let mut context = Context {
+            save_path: Some(path.clone()),
+            ..Context::default()
+        };
+        let mut stack = ScreenStack::new(&context);
+        stack.update(&pressed(Action::Confirm), &[], &mut context, 0.0);
+        stack.update(&pressed(Action::Confirm), &[], &mut context, 0.0);
+        stack.update(&ActionState::new(), &[], &mut context, 1.0);
+        let Some(Screen::Play(game)) = stack.current() else {
+            panic!("expected a game");
+        };
+        let ticks = game.get_ticks();
+        stack.suspend(&context);
+
+        // next time round the title offers to carry on
+        let mut stack = ScreenStack::new(&context);
+        stack.update(&pressed(Action::Confirm), &[], &mut context, 0.0);
+        let Some(Screen::Play(game)) = stack.current() else {
+            panic!("expected the saved game");
+        };
+        assert_eq!(game.get_ticks(), ticks);
+        assert!(!context.has_saved_game());
+    }
+}
@@ -0,0 +1,265 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{
+    AUTO_REPEAT_RATE, DELAYED_AUTO_SHIFT, DIG_ROWS, DRAW_SCALE, MAX_DIG_ROWS, MAX_START_LEVEL,
+    SCORE_COMPLETED_LINES_INCREMENT, SCORE_INCREMENT, SHOW_FILLED_LINES_TIME, SOFT_DROP_SPEED,
+    SPEED, SPEED_INCREASE, TIME_LIMIT,
+};
+use crate::effects::LineClearEffect;
+use crate::gamepad::DEFAULT_DEADZONE;
+use crate::storage::{self, StorageError};
+
+const SETTINGS_FILE: &str = "settings.toml";
+
+// Everything the player can tune. The defaults are the values in constants.rs
+// and any field missing from the file falls back to its default.
+// All times are in seconds
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub handling: HandlingSettings,
+    pub rules: RulesSettings,
+    pub visuals: VisualSettings,
+    pub audio: AudioSettings,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HandlingSettings {
+    pub delayed_auto_shift: f64,
+    pub auto_repeat_rate: f64,
+    pub soft_drop_speed: f64,
+    pub gamepad_deadzone: f32,
+}
+impl Default for HandlingSettings {
+    fn default() -> Self {
+        Self {
+            delayed_auto_shift: DELAYED_AUTO_SHIFT,
+            auto_repeat_rate: AUTO_REPEAT_RATE,
+            soft_drop_speed: SOFT_DROP_SPEED,
+            gamepad_deadzone: DEFAULT_DEADZONE,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RulesSettings {
+    pub speed: f64,
+    pub speed_increase: f64,
+    pub show_filled_lines_time: f64,
+    pub score_increment: u32,
+    pub score_completed_lines_increment: u32,
+    pub time_limit: f64,     // how long an Ultra game lasts
+    pub start_level: u32,    // where Marathon & Endless start from
+    pub dig_rows: u32,       // the garbage a Dig starts with
+    pub rising_garbage: f64, // seconds between new rows of garbage in a Dig, 0 for none
+}
+impl Default for RulesSettings {
+    fn default() -> Self {
+        Self {
+            speed: SPEED,
+            speed_increase: SPEED_INCREASE,
+            show_filled_lines_time: SHOW_FILLED_LINES_TIME,
+            score_increment: SCORE_INCREMENT,
+            score_completed_lines_increment: SCORE_COMPLETED_LINES_INCREMENT,
+            time_limit: TIME_LIMIT,
+            start_level: 1,
+            dig_rows: DIG_ROWS,
+            rising_garbage: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VisualSettings {
+    pub draw_scale: f32,
+    pub theme: String, // by name, the built in ones or any in the themes directory
+    pub line_clear_effect: LineClearEffect,
+    pub particles: bool,    // bursts of squares for a tetris or T-spin
+    pub lock_flash: bool,   // a piece lights up as it locks
+    pub screen_shake: bool, // the board jolts on hard drops & line clears
+}
+impl Default for VisualSettings {
+    fn default() -> Self {
+        Self {
+            draw_scale: DRAW_SCALE,
+            theme: "Classic".to_string(),
+            line_clear_effect: LineClearEffect::default(),
+            particles: true,
+            lock_flash: true,
+            screen_shake: false,
+        }
+    }
+}
+
+// Volumes run from 0.0 (silent) to 1.0 (full)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master_volume: f32,
+    pub effects_volume: f32,
+    pub music_volume: f32,
+}
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            effects_volume: 1.0,
+            music_volume: 0.7,
+        }
+    }
+}
+
+impl Settings {
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        storage::config_path(SETTINGS_FILE)
+    }
+
+    pub fn load() -> Result<Settings, StorageError> {
+        let path = Self::default_path().ok_or(StorageError::NoDirectory)?;
+        Self::load_from(&path)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Settings, StorageError> {
+        let settings: Settings = storage::load_toml(path)?;
+        Ok(settings.validated())
+    }
+
+    pub fn from_toml(text: &str) -> Result<Settings, StorageError> {
+        let settings: Settings = storage::from_toml(text)?;
+        Ok(settings.validated())
+    }
+
+    pub fn to_toml(&self) -> Result<String, StorageError> {
+        storage::to_toml(self)
+    }
+
+    pub fn save(&self) -> Result<(), StorageError> {
+        let path = Self::default_path().ok_or(StorageError::NoDirectory)?;
+        self.save_to(&path)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), StorageError> {
+        storage::save_toml(path, self)
+    }
+
+    // Pull anything silly back into a range the game can cope with.
+    // A hand edited file shouldn't be able to stop the game from starting
+    #[must_use]
+    pub fn validated(mut self) -> Settings {
+        let defaults = Settings::default();
+        let handling = &mut self.handling;
+        handling.delayed_auto_shift = clamp_or(
+            handling.delayed_auto_shift,
+            0.0,
+            1.0,
+            defaults.handling.delayed_auto_shift,
+        );
+        handling.auto_repeat_rate = clamp_or(
+            handling.auto_repeat_rate,
+            0.0,
+            0.5,
+            defaults.handling.auto_repeat_rate,
+        );
+        handling.soft_drop_speed = clamp_or(
+            handling.soft_drop_speed,
+            0.0,
+            1.0,
+            defaults.handling.soft_drop_speed,
+        );
+        handling.gamepad_deadzone = clamp_or(
+            handling.gamepad_deadzone.into(),
+            0.0,
+            0.95,
+            defaults.handling.gamepad_deadzone.into(),
+        ) as f32;
+
+        let rules = &mut self.rules;
+        rules.speed = clamp_or(rules.speed, 0.01, 5.0, defaults.rules.speed);
+        rules.speed_increase = clamp_or(
+            rules.speed_increase,
+            0.0,
+            0.5,
+            defaults.rules.speed_increase,
+        );
+        rules.show_filled_lines_time = clamp_or(
+            rules.show_filled_lines_time,
+            0.0,
+            2.0,
+            defaults.rules.show_filled_lines_time,
+        );
+        rules.time_limit = clamp_or(rules.time_limit, 10.0, 3600.0, defaults.rules.time_limit);
+        rules.start_level = rules.start_level.clamp(1, MAX_START_LEVEL);
+        rules.dig_rows = rules.dig_rows.clamp(1, MAX_DIG_ROWS);
+        rules.rising_garbage = clamp_or(rules.rising_garbage, 0.0, 60.0, 0.0);
+
+        let visuals = &mut self.visuals;
+        visuals.draw_scale = clamp_or(
+            visuals.draw_scale.into(),
+            5.0,
+            100.0,
+            defaults.visuals.draw_scale.into(),
+        ) as f32;
+
+        let audio = &mut self.audio;
+        for volume in [
+            &mut audio.master_volume,
+            &mut audio.effects_volume,
+            &mut audio.music_volume,
+        ] {
+            *volume = clamp_or((*volume).into(), 0.0, 1.0, 1.0) as f32;
+        }
+        self
+    }
+}
+
+fn clamp_or(value: f64, min: f64, max: f64, default: f64) -> f64 {
+    // NaN can't be clamped so it gets the default instead
+    if value.is_nan() {
+        default
+    } else {
+        value.clamp(min, max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_round_trip() {
+        let mut settings = Settings::default();
+        settings.handling.delayed_auto_shift = 0.1;
+        settings.audio.music_volume = 0.25;
+        let text = settings.to_toml().unwrap();
+        assert_eq!(Settings::from_toml(&text).unwrap(), settings);
+    }
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let settings = Settings::from_toml("[handling]\nauto_repeat_rate = 0.0\n").unwrap();
+        assert!(settings.handling.auto_repeat_rate.abs() < f64::EPSILON);
+        assert_eq!(settings.rules, RulesSettings::default());
+        assert_eq!(settings.visuals, VisualSettings::default());
+    }
+    #[test]
+    fn test_out_of_range_values_are_clamped() {
+        let settings = Settings::from_toml(
+            "[rules]\nspeed = -3.0\n[visuals]\ndraw_scale = 1000.0\n[audio]\nmaster_volume = 4.0\n",
+        )
+        .unwrap();
+        assert!((settings.rules.speed - 0.01).abs() < f64::EPSILON);
+        assert!((settings.visuals.draw_scale - 100.0).abs() < f32::EPSILON);
+        assert!((settings.audio.master_volume - 1.0).abs() < f32::EPSILON);
+    }
+    #[test]
+    fn test_bad_file_is_an_error() {
+        assert!(matches!(
+            Settings::from_toml("[rules]\nspeed = \"fast\"\n"),
+            Err(StorageError::Parse(_))
+        ));
+    }
+}
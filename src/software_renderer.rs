@@ -0,0 +1,258 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::font::{self, GLYPH_ADVANCE, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::png;
+use crate::render::{Colour, Renderer};
+use crate::theme::Skin;
+
+// Draws into an RGBA buffer in memory instead of a window, so screens can be drawn
+// with no GPU: snapshot tests in CI and thumbnails of replays. Text uses the little
+// built in pixel font so it won't look like the window, only be in the same places
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoftwareRenderer {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+impl SoftwareRenderer {
+    #[must_use]
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut ret_renderer = Self {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+        };
+        ret_renderer.clear(Colour::BLACK);
+        ret_renderer
+    }
+    #[must_use]
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+    #[must_use]
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+    #[must_use]
+    pub fn get_pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+    #[must_use]
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let index = (y as usize * self.width as usize + x as usize) * 4;
+        [
+            self.pixels[index],
+            self.pixels[index + 1],
+            self.pixels[index + 2],
+            self.pixels[index + 3],
+        ]
+    }
+    pub fn clear(&mut self, colour: Colour) {
+        let pixel = [
+            to_byte(colour.r),
+            to_byte(colour.g),
+            to_byte(colour.b),
+            to_byte(colour.a),
+        ];
+        for chunk in self.pixels.chunks_exact_mut(4) {
+            chunk.copy_from_slice(&pixel);
+        }
+    }
+    #[must_use]
+    pub fn to_png(&self) -> Vec<u8> {
+        png::encode_rgba(self.width, self.height, &self.pixels)
+    }
+    pub fn save_png(&self, path: &Path) -> io::Result<()> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, self.to_png())
+    }
+
+    // blends a colour over whatever is already there, clipped to the image
+    fn fill(&mut self, x: f32, y: f32, width: f32, height: f32, colour: Colour) {
+        let left = x.round().clamp(0.0, self.width as f32) as usize;
+        let right = (x + width).round().clamp(0.0, self.width as f32) as usize;
+        let top = y.round().clamp(0.0, self.height as f32) as usize;
+        let bottom = (y + height).round().clamp(0.0, self.height as f32) as usize;
+        let alpha = colour.a.clamp(0.0, 1.0);
+        let source = [colour.r, colour.g, colour.b];
+        for row in top..bottom {
+            let start = (row * self.width as usize + left) * 4;
+            let end = (row * self.width as usize + right) * 4;
+            for pixel in self.pixels[start..end].chunks_exact_mut(4) {
+                for (channel, value) in pixel.iter_mut().zip(source) {
+                    let blended = value * alpha + f32::from(*channel) / 255.0 * (1.0 - alpha);
+                    *channel = to_byte(blended);
+                }
+                let covered = alpha + f32::from(pixel[3]) / 255.0 * (1.0 - alpha);
+                pixel[3] = to_byte(covered);
+            }
+        }
+    }
+}
+
+fn to_byte(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// how big each pixel of the font is drawn, roughly matching the window's font sizes
+fn font_pixel_size(font_size: f32) -> f32 {
+    (font_size / 10.0).round().max(1.0)
+}
+
+impl Renderer for SoftwareRenderer {
+    fn draw_cell(&mut self, x: f32, y: f32, size: f32, colour: Colour) {
+        self.fill(x, y, size, size, colour);
+    }
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, font_size: f32, colour: Colour) {
+        let pixel_size = font_pixel_size(font_size);
+        // like the window, y is the baseline so the letters sit on top of it
+        let top = y - GLYPH_HEIGHT as f32 * pixel_size;
+        for (index, character) in text.chars().enumerate() {
+            let left = x + (index as u32 * GLYPH_ADVANCE) as f32 * pixel_size;
+            for (row, bits) in font::glyph(character).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        self.fill(
+                            left + column as f32 * pixel_size,
+                            top + row as f32 * pixel_size,
+                            pixel_size,
+                            pixel_size,
+                            colour,
+                        );
+                    }
+                }
+            }
+        }
+    }
+    fn draw_panel(&mut self, x: f32, y: f32, width: f32, height: f32, colour: Colour) {
+        self.fill(x, y, width, height, colour);
+    }
+    fn measure_text(&self, text: &str, font_size: f32) -> f32 {
+        (text.chars().count() as u32 * GLYPH_ADVANCE) as f32 * font_pixel_size(font_size)
+    }
+    fn get_size(&self) -> (f32, f32) {
+        (self.width as f32, self.height as f32)
+    }
+    fn draw_skin_cell(&mut self, skin: &Skin, tile: u32, x: f32, y: f32, size: f32) {
+        // nearest neighbour scaling, a pixel at a time
+        let pixels = size.round().max(1.0) as u32;
+        let tile_size = skin.get_tile_size();
+        for row in 0..pixels {
+            for column in 0..pixels {
+                let colour = Colour::from_bytes(skin.get_pixel(
+                    tile,
+                    column * tile_size / pixels,
+                    row * tile_size / pixels,
+                ));
+                self.fill(x + column as f32, y + row as f32, 1.0, 1.0, colour);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::Effects;
+    use crate::game::{Game, GameMode, Ruleset};
+    use crate::input::{Action, ActionState};
+    use crate::layout::Layout;
+    use crate::render::{draw_game, draw_playfield};
+    use crate::theme::{SKIN_TILES, Theme};
+    use std::path::PathBuf;
+
+    // Compares a rendered screen with the picture saved in snapshots/. Run the tests with
+    // UPDATE_SNAPSHOTS=1 set to save new pictures after changing how things are drawn
+    fn assert_matches_snapshot(renderer: &SoftwareRenderer, name: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("snapshots")
+            .join(format!("{name}.png"));
+        let png = renderer.to_png();
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            renderer.save_png(&path).unwrap();
+            return;
+        }
+        let expected = fs::read(&path).unwrap_or_default();
+        assert!(
+            png == expected,
+            "{name} doesn't match {}, run with UPDATE_SNAPSHOTS=1 if the change is intended",
+            path.display()
+        );
+    }
+    #[test]
+    fn test_blending_and_clipping() {
+        let mut renderer = SoftwareRenderer::new(4, 4);
+        renderer.draw_panel(-10.0, -10.0, 12.0, 12.0, Colour::WHITE);
+        assert_eq!(renderer.get_pixel(1, 1), [255, 255, 255, 255]);
+        assert_eq!(renderer.get_pixel(2, 2), [0, 0, 0, 255]);
+        renderer.draw_cell(2.0, 2.0, 2.0, Colour::new(1.0, 0.0, 0.0, 0.5));
+        assert_eq!(renderer.get_pixel(3, 3), [128, 0, 0, 255]);
+    }
+    #[test]
+    fn test_text_sits_on_the_baseline() {
+        let mut renderer = SoftwareRenderer::new(20, 20);
+        renderer.draw_text("I", 0.0, 10.0, 10.0, Colour::WHITE);
+        // the top of the I starts seven pixels above the baseline
+        assert_eq!(renderer.get_pixel(1, 3), [255, 255, 255, 255]);
+        assert_eq!(renderer.get_pixel(1, 10), [0, 0, 0, 255]);
+        assert!((renderer.measure_text("II", 10.0) - 12.0).abs() < f32::EPSILON);
+    }
+    #[test]
+    fn test_new_game_snapshot() {
+        let mut renderer = SoftwareRenderer::new(800, 600);
+        let game = Game::new(GameMode::Classic, Ruleset::default(), 1);
+        let layout = Layout::new(800.0, 600.0, 30.0);
+        draw_game(
+            &mut renderer,
+            &game,
+            &Effects::new(),
+            &layout,
+            &Theme::classic(),
+        );
+        assert_matches_snapshot(&renderer, "new_game");
+    }
+    #[test]
+    fn test_theme_snapshots() {
+        // a few pieces down so there's something to see
+        let mut game = Game::new(GameMode::Classic, Ruleset::default(), 2);
+        for tick in 0..200 {
+            let mut actions = ActionState::new();
+            match tick % 40 {
+                5 if tick % 80 == 5 => actions.set_pressed(Action::Rotate),
+                10..=14 if tick % 120 < 40 => actions.set_down(Action::MoveLeft),
+                10..=14 if tick % 120 >= 80 => actions.set_down(Action::MoveRight),
+                20 => actions.set_pressed(Action::HardDrop),
+                _ => {}
+            }
+            game.tick(&actions);
+        }
+        for theme in Theme::built_in() {
+            let mut renderer = SoftwareRenderer::new(360, 540);
+            draw_playfield(&mut renderer, &game, (0.0, 0.0), 30.0, &theme);
+            let name = theme
+                .name
+                .to_lowercase()
+                .replace(|c: char| !c.is_alphanumeric(), "_");
+            assert_matches_snapshot(&renderer, &format!("theme_{name}"));
+        }
+    }
+    #[test]
+    fn test_skin_cells_are_scaled() {
+        let mut pixels = Vec::new();
+        for _ in 0..2 {
+            for tile in 0..SKIN_TILES {
+                // the left column of each tile is white, the right is the tile number
+                pixels.extend([255, 255, 255, 255, tile as u8, 0, 0, 255]);
+            }
+        }
+        let skin = Skin::from_png(&crate::png::encode_rgba(2 * SKIN_TILES, 2, &pixels)).unwrap();
+        let mut renderer = SoftwareRenderer::new(8, 8);
+        renderer.draw_skin_cell(&skin, 3, 0.0, 0.0, 8.0);
+        assert_eq!(renderer.get_pixel(3, 7), [255, 255, 255, 255]);
+        assert_eq!(renderer.get_pixel(4, 0), [3, 0, 0, 255]);
+    }
+}
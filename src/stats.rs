@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+
+use crate::events::GameEvent;
+use crate::tetromino::TetrominoShape;
+
+// lines sent to an opponent by clearing 1 to 4 lines at once, guideline style
+const ATTACK: [u32; 4] = [0, 1, 2, 4];
+const T_SPIN_ATTACK: [u32; 4] = [2, 4, 6, 6];
+
+// Running totals for the stats panel, built up from the game's events
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Statistics {
+    pieces: u32,
+    lines: u32,
+    keys: u32,
+    finesse_faults: u32,
+    spawned: [u32; 7], // indexed by TetrominoShape::index
+    time: f64,
+    #[serde(default)]
+    clears: [u32; 4], // singles, doubles, triples & tetrises
+    #[serde(default)]
+    t_spins: u32,
+    #[serde(default)]
+    attack: u32,
+    #[serde(default)]
+    last_t_spin: bool, // whether the piece that just locked was a T-spin, for its clear
+}
+impl Default for Statistics {
+    fn default() -> Self {
+        Self {
+            pieces: 0,
+            lines: 0,
+            keys: 0,
+            finesse_faults: 0,
+            spawned: [0; 7],
+            time: 0.0,
+            clears: [0; 4],
+            t_spins: 0,
+            attack: 0,
+            last_t_spin: false,
+        }
+    }
+}
+impl Statistics {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn record(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::PieceSpawned(shape) => self.spawned[shape.index()] += 1,
+            GameEvent::KeyPressed(_) => self.keys += 1,
+            GameEvent::PieceLocked {
+                finesse_faults,
+                t_spin,
+                ..
+            } => {
+                self.pieces += 1;
+                self.finesse_faults += finesse_faults;
+                self.t_spins += u32::from(*t_spin);
+                self.last_t_spin = *t_spin;
+            }
+            GameEvent::FinesseFault { faults, .. } => self.finesse_faults += faults,
+            GameEvent::LinesCleared(rows) => {
+                self.lines += rows.len() as u32;
+                let index = rows.len().wrapping_sub(1);
+                if let Some(clears) = self.clears.get_mut(index) {
+                    *clears += 1;
+                }
+                let attack = if self.last_t_spin {
+                    T_SPIN_ATTACK
+                } else {
+                    ATTACK
+                };
+                self.attack += attack.get(index).copied().unwrap_or(0);
+            }
+            GameEvent::LevelUp(_)
+            | GameEvent::PieceMoved(_)
+            | GameEvent::PieceRotated
+            | GameEvent::PieceHeld(_)
+            | GameEvent::OffTemplate(_)
+            | GameEvent::PlacementUndone(_)
+            | GameEvent::GameOver
+            | GameEvent::Completed => {}
+        }
+    }
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+    #[must_use]
+    pub fn get_pieces(&self) -> u32 {
+        self.pieces
+    }
+    #[must_use]
+    pub fn get_lines(&self) -> u32 {
+        self.lines
+    }
+    #[must_use]
+    pub fn get_keys(&self) -> u32 {
+        self.keys
+    }
+    #[must_use]
+    pub fn get_finesse_faults(&self) -> u32 {
+        self.finesse_faults
+    }
+    #[must_use]
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+    // how many times that many lines were cleared at once, 1 to 4
+    #[must_use]
+    pub fn get_clears(&self, lines: usize) -> u32 {
+        self.clears.get(lines.wrapping_sub(1)).copied().unwrap_or(0)
+    }
+    #[must_use]
+    pub fn get_t_spins(&self) -> u32 {
+        self.t_spins
+    }
+    // lines that would have been sent to an opponent
+    #[must_use]
+    pub fn get_attack(&self) -> u32 {
+        self.attack
+    }
+    #[must_use]
+    pub fn get_spawned(&self, shape: TetrominoShape) -> u32 {
+        self.spawned[shape.index()]
+    }
+    #[must_use]
+    pub fn pieces_per_second(&self) -> f64 {
+        if self.time > 0.0 {
+            f64::from(self.pieces) / self.time
+        } else {
+            0.0
+        }
+    }
+    #[must_use]
+    pub fn attack_per_minute(&self) -> f64 {
+        if self.time > 0.0 {
+            f64::from(self.attack) * 60.0 / self.time
+        } else {
+            0.0
+        }
+    }
+    #[must_use]
+    pub fn keys_per_piece(&self) -> f64 {
+        if self.pieces > 0 {
+            f64::from(self.keys) / f64::from(self.pieces)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Action;
+    #[test]
+    fn test_record_events() {
+        let mut statistics = Statistics::new();
+        let events = [
+            GameEvent::PieceSpawned(TetrominoShape::T),
+            GameEvent::KeyPressed(Action::MoveLeft),
+            GameEvent::KeyPressed(Action::Rotate),
+            GameEvent::KeyPressed(Action::MoveLeft),
+            GameEvent::PieceLocked {
+                shape: TetrominoShape::T,
+                finesse_faults: 1,
+                cells: Vec::new(),
+                t_spin: false,
+            },
+            GameEvent::LinesCleared(vec![15, 16]),
+            GameEvent::PieceSpawned(TetrominoShape::I),
+        ];
+        for event in &events {
+            statistics.record(event);
+        }
+        assert_eq!(statistics.get_pieces(), 1);
+        assert_eq!(statistics.get_lines(), 2);
+        assert_eq!(statistics.get_finesse_faults(), 1);
+        assert_eq!(statistics.get_spawned(TetrominoShape::T), 1);
+        assert_eq!(statistics.get_spawned(TetrominoShape::I), 1);
+        assert_eq!(statistics.get_spawned(TetrominoShape::O), 0);
+        assert!((statistics.keys_per_piece() - 3.0).abs() < f64::EPSILON);
+        assert_eq!(statistics.get_clears(2), 1);
+        assert_eq!(statistics.get_clears(1), 0);
+        assert_eq!(statistics.get_clears(0), 0);
+    }
+    #[test]
+    fn test_clear_types() {
+        let mut statistics = Statistics::new();
+        statistics.record(&GameEvent::PieceLocked {
+            shape: TetrominoShape::T,
+            finesse_faults: 0,
+            cells: Vec::new(),
+            t_spin: true,
+        });
+        statistics.record(&GameEvent::LinesCleared(vec![14, 15]));
+        statistics.record(&GameEvent::LinesCleared(vec![12, 13, 14, 15]));
+        statistics.record(&GameEvent::LinesCleared(vec![15]));
+        assert_eq!(statistics.get_t_spins(), 1);
+        let clears: Vec<u32> = (1..=4).map(|lines| statistics.get_clears(lines)).collect();
+        assert_eq!(clears, vec![1, 1, 0, 1]);
+    }
+    #[test]
+    fn test_rates_before_anything_happens() {
+        let mut statistics = Statistics::new();
+        assert!(statistics.pieces_per_second().abs() < f64::EPSILON);
+        assert!(statistics.keys_per_piece().abs() < f64::EPSILON);
+        assert!(statistics.attack_per_minute().abs() < f64::EPSILON);
+        statistics.record(&GameEvent::PieceLocked {
+            shape: TetrominoShape::O,
+            finesse_faults: 0,
+            cells: Vec::new(),
+            t_spin: false,
+        });
+        statistics.set_time(2.0);
+        assert!((statistics.pieces_per_second() - 0.5).abs() < f64::EPSILON);
+    }
+    #[test]
+    fn test_attack_per_minute() {
+        let mut statistics = Statistics::new();
+        let lock = |t_spin| GameEvent::PieceLocked {
+            shape: TetrominoShape::T,
+            finesse_faults: 0,
+            cells: Vec::new(),
+            t_spin,
+        };
+        // a tetris, a T-spin double, a single, then a T-spin that clears nothing
+        let events = [
+            lock(false),
+            GameEvent::LinesCleared(vec![12, 13, 14, 15]),
+            lock(true),
+            GameEvent::LinesCleared(vec![14, 15]),
+            lock(false),
+            GameEvent::LinesCleared(vec![15]),
+            lock(true),
+        ];
+        for event in &events {
+            statistics.record(event);
+        }
+        assert_eq!(statistics.get_attack(), 8);
+        statistics.set_time(30.0);
+        assert!((statistics.attack_per_minute() - 16.0).abs() < f64::EPSILON);
+    }
+}
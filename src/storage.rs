@@ -0,0 +1,74 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+// Everything the game writes to disk lives under a "rust_tetris" directory.
+// Settings go in the config directory, anything the game records (scores etc) in the data directory
+const APP_DIRECTORY: &str = "rust_tetris";
+
+#[derive(Debug)]
+pub enum StorageError {
+    NoDirectory,
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Serialise(toml::ser::Error),
+}
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::NoDirectory => write!(f, "could not find a directory to store files in"),
+            StorageError::Io(error) => write!(f, "could not access the file: {error}"),
+            StorageError::Parse(error) => write!(f, "could not read the file: {error}"),
+            StorageError::Serialise(error) => write!(f, "could not write the file: {error}"),
+        }
+    }
+}
+impl std::error::Error for StorageError {}
+impl From<std::io::Error> for StorageError {
+    fn from(error: std::io::Error) -> Self {
+        StorageError::Io(error)
+    }
+}
+
+// $XDG_CONFIG_HOME/rust_tetris/<file> on Linux, the platform equivalent everywhere else
+#[must_use]
+pub fn config_path(file_name: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join(APP_DIRECTORY).join(file_name))
+}
+
+// $XDG_DATA_HOME/rust_tetris/<file> on Linux, the platform equivalent everywhere else
+#[must_use]
+pub fn data_path(file_name: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|directory| directory.join(APP_DIRECTORY).join(file_name))
+}
+
+pub fn from_toml<T: DeserializeOwned>(text: &str) -> Result<T, StorageError> {
+    toml::from_str(text).map_err(StorageError::Parse)
+}
+
+pub fn to_toml<T: Serialize>(value: &T) -> Result<String, StorageError> {
+    toml::to_string_pretty(value).map_err(StorageError::Serialise)
+}
+
+// A missing file isn't an error, it just means nothing has been saved yet
+pub fn load_toml<T: DeserializeOwned + Default>(path: &Path) -> Result<T, StorageError> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    from_toml(&fs::read_to_string(path)?)
+}
+
+pub fn save_toml<T: Serialize>(path: &Path, value: &T) -> Result<(), StorageError> {
+    write_file(path, to_toml(value)?.as_bytes())
+}
+
+pub fn write_file(path: &Path, contents: &[u8]) -> Result<(), StorageError> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use crate::audio::Sound;
+
+// Makes the game's sounds & music as WAV files in memory, so there are no sound files
+// to ship or lose. Everything is simple waveforms, like the old handhelds
+pub const SAMPLE_RATE: u32 = 22050;
+const EFFECT_LOUDNESS: f32 = 0.4;
+const MUSIC_LOUDNESS: f32 = 0.3;
+// how long an eighth note of the music lasts at normal speed, in seconds
+const MUSIC_EIGHTH: f32 = 0.15;
+
+#[derive(Clone, Copy)]
+enum Wave {
+    Square,
+    Triangle,
+}
+
+// A note as a MIDI number (69 is the A above middle C, 0 is a rest) & its length
+type Note = (u8, f32);
+
+// Korobeiniki, the tune everyone knows from the Game Boy version. The numbers are
+// lengths in eighth notes
+const MELODY: [(u8, u8); 38] = [
+    (76, 2),
+    (71, 1),
+    (72, 1),
+    (74, 2),
+    (72, 1),
+    (71, 1),
+    (69, 2),
+    (69, 1),
+    (72, 1),
+    (76, 2),
+    (74, 1),
+    (72, 1),
+    (71, 3),
+    (72, 1),
+    (74, 2),
+    (76, 2),
+    (72, 2),
+    (69, 2),
+    (69, 4),
+    (0, 1),
+    (74, 2),
+    (77, 1),
+    (81, 2),
+    (79, 1),
+    (77, 1),
+    (76, 3),
+    (72, 1),
+    (76, 2),
+    (74, 1),
+    (72, 1),
+    (71, 2),
+    (71, 1),
+    (72, 1),
+    (74, 2),
+    (76, 2),
+    (72, 2),
+    (69, 2),
+    (69, 3),
+];
+
+fn frequency(note: u8) -> f32 {
+    440.0 * 2.0_f32.powf((f32::from(note) - 69.0) / 12.0)
+}
+
+// the notes of each sound, quick rising ones for good things & falling ones for bad
+fn notes(sound: Sound) -> Vec<Note> {
+    match sound {
+        Sound::Move => vec![(93, 0.03)],
+        Sound::Rotate => vec![(88, 0.03), (95, 0.04)],
+        Sound::Lock => vec![(52, 0.07)],
+        Sound::Single => vec![(72, 0.12)],
+        Sound::Double => vec![(72, 0.08), (76, 0.12)],
+        Sound::Triple => vec![(72, 0.07), (76, 0.07), (79, 0.12)],
+        Sound::Tetris => vec![(72, 0.07), (76, 0.07), (79, 0.07), (84, 0.25)],
+        Sound::TSpin => vec![(79, 0.06), (74, 0.06), (79, 0.06), (86, 0.15)],
+        Sound::LevelUp => vec![(79, 0.08), (84, 0.08), (88, 0.08), (91, 0.2)],
+        Sound::Hold => vec![(81, 0.05), (76, 0.07)],
+        Sound::GameOver => vec![(67, 0.2), (64, 0.2), (60, 0.2), (55, 0.5)],
+    }
+}
+
+fn render(notes: &[Note], wave: Wave, loudness: f32) -> Vec<i16> {
+    let mut ret_samples = Vec::new();
+    for &(note, length) in notes {
+        let count = (length * SAMPLE_RATE as f32) as usize;
+        let step = frequency(note) / SAMPLE_RATE as f32;
+        for index in 0..count {
+            if note == 0 {
+                ret_samples.push(0);
+                continue;
+            }
+            let phase = (index as f32 * step).fract();
+            let value = match wave {
+                Wave::Square => {
+                    if phase < 0.5 {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+                Wave::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+            };
+            // each note dies away so they don't click into each other
+            let envelope = 1.0 - index as f32 / count as f32;
+            ret_samples.push((value * envelope * loudness * f32::from(i16::MAX)) as i16);
+        }
+    }
+    ret_samples
+}
+
+#[must_use]
+pub fn sound_wav(sound: Sound) -> Vec<u8> {
+    encode_wav(&render(&notes(sound), Wave::Square, EFFECT_LOUDNESS))
+}
+
+// One time through the tune, for looping. Tempo 2.0 is twice as fast
+#[must_use]
+pub fn music_wav(tempo: f32) -> Vec<u8> {
+    let notes: Vec<Note> = MELODY
+        .iter()
+        .map(|&(note, eighths)| (note, f32::from(eighths) * MUSIC_EIGHTH / tempo))
+        .collect();
+    encode_wav(&render(&notes, Wave::Triangle, MUSIC_LOUDNESS))
+}
+
+// 16 bit mono PCM, the plainest WAV there is
+#[must_use]
+pub fn encode_wav(samples: &[i16]) -> Vec<u8> {
+    let data_size = samples.len() as u32 * 2;
+    let mut ret_wav = Vec::with_capacity(44 + data_size as usize);
+    ret_wav.extend_from_slice(b"RIFF");
+    ret_wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    ret_wav.extend_from_slice(b"WAVE");
+    ret_wav.extend_from_slice(b"fmt ");
+    ret_wav.extend_from_slice(&16_u32.to_le_bytes());
+    ret_wav.extend_from_slice(&1_u16.to_le_bytes()); // PCM
+    ret_wav.extend_from_slice(&1_u16.to_le_bytes()); // channels
+    ret_wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    ret_wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // bytes a second
+    ret_wav.extend_from_slice(&2_u16.to_le_bytes()); // bytes a sample
+    ret_wav.extend_from_slice(&16_u16.to_le_bytes()); // bits a sample
+    ret_wav.extend_from_slice(b"data");
+    ret_wav.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        ret_wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    ret_wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_size(wav: &[u8]) -> u32 {
+        u32::from_le_bytes([wav[40], wav[41], wav[42], wav[43]])
+    }
+    #[test]
+    fn test_wav_header() {
+        let wav = encode_wav(&[0, 1, -1]);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(data_size(&wav), 6);
+        assert_eq!(wav.len(), 50);
+        assert_eq!(&wav[46..48], &1_i16.to_le_bytes());
+    }
+    #[test]
+    fn test_every_sound_is_short_and_audible() {
+        for sound in Sound::ALL {
+            let wav = sound_wav(sound);
+            let seconds = data_size(&wav) as f32 / 2.0 / SAMPLE_RATE as f32;
+            assert!(seconds > 0.0 && seconds < 1.5, "{sound:?} lasts {seconds}s");
+            assert!(wav[44..].iter().any(|byte| *byte != 0));
+        }
+    }
+    #[test]
+    fn test_faster_music_is_shorter() {
+        let normal = data_size(&music_wav(1.0)) as f32;
+        let fast = data_size(&music_wav(1.5)) as f32;
+        assert!((normal / fast - 1.5).abs() < 0.01);
+    }
+}
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 use crate::Rotation;
 use crate::constants::{TETROMINO_START_X, TETROMINO_START_Y};
 use crate::coordinate::{ICoordinate, UCoordinate};
@@ -9,7 +11,7 @@ const TETROMINO_L: &str = ".X...X...XX.....";
 const TETROMINO_S: &str = ".X...XX...X.....";
 const TETROMINO_Z: &str = "..X..XX..X......";
 const TETROMINO_SIZE: u32 = 4;
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TetrominoShape {
     I,
     O,
@@ -21,7 +23,49 @@ pub enum TetrominoShape {
 }
 
 impl TetrominoShape {
-    fn shape(&self) -> String {
+    // in colour order, so a shape's index is also its colour number on the board
+    pub const ALL: [TetrominoShape; 7] = [
+        TetrominoShape::I,
+        TetrominoShape::J,
+        TetrominoShape::L,
+        TetrominoShape::S,
+        TetrominoShape::Z,
+        TetrominoShape::O,
+        TetrominoShape::T,
+    ];
+
+    #[must_use]
+    pub fn index(self) -> usize {
+        match self {
+            TetrominoShape::I => 0,
+            TetrominoShape::J => 1,
+            TetrominoShape::L => 2,
+            TetrominoShape::S => 3,
+            TetrominoShape::Z => 4,
+            TetrominoShape::O => 5,
+            TetrominoShape::T => 6,
+        }
+    }
+    #[must_use]
+    pub fn letter(self) -> char {
+        match self {
+            TetrominoShape::I => 'I',
+            TetrominoShape::O => 'O',
+            TetrominoShape::T => 'T',
+            TetrominoShape::J => 'J',
+            TetrominoShape::L => 'L',
+            TetrominoShape::S => 'S',
+            TetrominoShape::Z => 'Z',
+        }
+    }
+    #[must_use]
+    pub fn from_letter(letter: char) -> Option<TetrominoShape> {
+        let letter = letter.to_ascii_uppercase();
+        TetrominoShape::ALL
+            .into_iter()
+            .find(|shape| shape.letter() == letter)
+    }
+    fn shape(self) -> String {
         // for a given shape name return the actual shape
         match self {
             TetrominoShape::I => String::from(TETROMINO_I),
@@ -35,7 +79,7 @@ impl TetrominoShape {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Tetromino {
     shape_name: TetrominoShape,
     rotation: Rotation,
@@ -44,26 +88,90 @@ pub struct Tetromino {
     coordinates: ICoordinate,
 }
 impl Tetromino {
+    #[must_use]
     pub fn new(shape_name: TetrominoShape) -> Self {
         Self {
             shape_name,
             rotation: Rotation::Zero,
-            colour: 0,
+            colour: shape_name.index() as u32,
             coordinates: ICoordinate {
                 x: TETROMINO_START_X,
                 y: TETROMINO_START_Y,
             },
         }
     }
+    #[must_use]
+    pub fn get_shape(&self) -> TetrominoShape {
+        self.shape_name
+    }
+    #[must_use]
+    pub fn get_rotation(&self) -> Rotation {
+        self.rotation
+    }
+    #[must_use]
     pub fn get_colour(&self) -> u32 {
         self.colour
     }
     pub fn set_colour(&mut self, colour: u32) {
         self.colour = colour;
     }
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+    pub fn set_coordinates(&mut self, coordinates: ICoordinate) {
+        self.coordinates = coordinates;
+    }
+    #[must_use]
     pub fn get_coordinates(&self) -> ICoordinate {
         self.coordinates
     }
+    // the board squares this piece covers
+    #[must_use]
+    pub fn get_cells(&self) -> Vec<ICoordinate> {
+        let mut tetromino = *self;
+        let mut ret_cells = Vec::new();
+        for y in 0..TETROMINO_SIZE {
+            for x in 0..TETROMINO_SIZE {
+                if tetromino.get_val_at_xy(&UCoordinate::new(x, y)) == 'X' {
+                    ret_cells.push(ICoordinate::new(
+                        x as i32 + self.coordinates.x,
+                        y as i32 + self.coordinates.y,
+                    ));
+                }
+            }
+        }
+        ret_cells
+    }
+    // The piece of this shape lying over exactly these board squares, in whichever of
+    // the rotations covers them, or None if they aren't that shape
+    #[must_use]
+    pub fn covering(shape: TetrominoShape, cells: &[ICoordinate]) -> Option<Tetromino> {
+        let mut target = cells.to_vec();
+        target.sort_unstable_by_key(|cell| (cell.x, cell.y));
+        let first = *target.first()?;
+        for rotation in [
+            Rotation::Zero,
+            Rotation::Ninety,
+            Rotation::OneEighty,
+            Rotation::TwoSeventy,
+        ] {
+            let mut ret_tetromino = Tetromino::new(shape);
+            ret_tetromino.set_rotation(rotation);
+            ret_tetromino.set_coordinates(ICoordinate::new(0, 0));
+            let mut cells = ret_tetromino.get_cells();
+            cells.sort_unstable_by_key(|cell| (cell.x, cell.y));
+            let offset = ICoordinate::new(first.x - cells[0].x, first.y - cells[0].y);
+            if cells.len() == target.len()
+                && cells.iter().zip(&target).all(|(cell, target)| {
+                    cell.x + offset.x == target.x && cell.y + offset.y == target.y
+                })
+            {
+                ret_tetromino.set_coordinates(offset);
+                return Some(ret_tetromino);
+            }
+        }
+        None
+    }
     pub fn move_left(&mut self) {
         self.coordinates.x -= 1;
     }
@@ -82,6 +190,15 @@ impl Tetromino {
             Rotation::TwoSeventy => self.rotation = Rotation::Zero,
         }
     }
+    pub fn rotate_counter_clockwise(&mut self) {
+        match self.rotation {
+            Rotation::Zero => self.rotation = Rotation::TwoSeventy,
+            Rotation::Ninety => self.rotation = Rotation::Zero,
+            Rotation::OneEighty => self.rotation = Rotation::Ninety,
+            Rotation::TwoSeventy => self.rotation = Rotation::OneEighty,
+        }
+    }
+    #[must_use]
     pub fn get_rotated_tetromino(&mut self) -> String {
         // We don't hold the rotated shape as part of this structure, we rotate on the fly.
         // We also hold the shape flattened rather than a 2D vector/array. This means we need to
@@ -100,6 +217,7 @@ impl Tetromino {
         }
         output
     }
+    #[must_use]
     pub fn rotate_square(&self, coordinate: &UCoordinate) -> usize {
         // simple maths to transpose a given X/Y co-ordinate to it's rotated value
         match self.rotation {
@@ -110,10 +228,12 @@ impl Tetromino {
         }
     }
     pub fn get_val_at_xy(&mut self, coordinate: &UCoordinate) -> char {
-        // this allows us to take an X,Y and return its value in flattened tetromino
-        self.get_rotated_tetromino()
+        // this allows us to take an X,Y and return its value in flattened tetromino. Only
+        // the one square is rotated, rotating the whole shape for it is slow
+        self.shape_name
+            .shape()
             .chars()
-            .nth((coordinate.x + coordinate.y * TETROMINO_SIZE) as usize)
+            .nth(self.rotate_square(coordinate))
             .unwrap()
     }
 }
@@ -150,6 +270,15 @@ mod tests {
         );
     }
     #[test]
+    fn test_rotate_both_ways() {
+        let mut tetromino = Tetromino::new(TetrominoShape::T);
+        tetromino.rotate_counter_clockwise();
+        assert_eq!(tetromino.get_rotation(), Rotation::TwoSeventy);
+        tetromino.rotate();
+        assert_eq!(tetromino.get_rotation(), Rotation::Zero);
+        assert_eq!(tetromino.get_colour(), TetrominoShape::T.index() as u32);
+    }
+    #[test]
     fn test_get_tetromino_shape() {
         let mut tetromino = Tetromino::new(TetrominoShape::I);
         assert_eq!(tetromino.get_rotated_tetromino(), TETROMINO_I);
@@ -0,0 +1,437 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::png::{self, PngError};
+use crate::render::Colour;
+use crate::storage::{self, StorageError};
+use crate::tetromino::TetrominoShape;
+use crate::warning;
+
+const THEME_DIRECTORY: &str = "themes";
+// the board's characters for the squares that aren't pieces
+pub const FLASH_SQUARE: u32 = 8;
+pub const WALL_SQUARE: u32 = 9;
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Storage(StorageError),
+    Skin(PngError),
+    SkinSize,
+}
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeError::Storage(error) => write!(f, "{error}"),
+            ThemeError::Skin(error) => write!(f, "could not read the skin: {error}"),
+            ThemeError::SkinSize => write!(
+                f,
+                "a skin needs {SKIN_TILES} square tiles side by side: I J L S Z O T, line clear, wall"
+            ),
+        }
+    }
+}
+impl std::error::Error for ThemeError {}
+impl From<StorageError> for ThemeError {
+    fn from(error: StorageError) -> Self {
+        ThemeError::Storage(error)
+    }
+}
+impl From<PngError> for ThemeError {
+    fn from(error: PngError) -> Self {
+        ThemeError::Skin(error)
+    }
+}
+
+// How each square of a piece is drawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockStyle {
+    Flat,
+    Bevelled, // lit from the top left
+    Outline,  // just the edges, faintly filled in
+    Skin,     // tiles from the theme's skin picture
+}
+
+// How the walls around the board are drawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BorderStyle {
+    Blocks, // as squares, like the pieces
+    Line,   // a thin line around the edge
+    Hidden,
+}
+
+// A colour for every piece
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Palette {
+    #[serde(rename = "I")]
+    pub i: Colour,
+    #[serde(rename = "J")]
+    pub j: Colour,
+    #[serde(rename = "L")]
+    pub l: Colour,
+    #[serde(rename = "S")]
+    pub s: Colour,
+    #[serde(rename = "Z")]
+    pub z: Colour,
+    #[serde(rename = "O")]
+    pub o: Colour,
+    #[serde(rename = "T")]
+    pub t: Colour,
+}
+impl Default for Palette {
+    fn default() -> Self {
+        Theme::classic().pieces
+    }
+}
+impl Palette {
+    #[must_use]
+    pub fn get(&self, shape: TetrominoShape) -> Colour {
+        match shape {
+            TetrominoShape::I => self.i,
+            TetrominoShape::J => self.j,
+            TetrominoShape::L => self.l,
+            TetrominoShape::S => self.s,
+            TetrominoShape::Z => self.z,
+            TetrominoShape::O => self.o,
+            TetrominoShape::T => self.t,
+        }
+    }
+}
+
+// Number of tiles in a skin: the seven pieces in colour order, then the line clear
+// flash and the wall
+pub const SKIN_TILES: u32 = 9;
+
+// A texture atlas of block pictures, read from a PNG with the tiles side by side.
+// The tiles are square, as big as the picture is high
+#[derive(Clone, Debug, PartialEq)]
+pub struct Skin {
+    tile_size: u32,
+    width: u32,
+    pixels: Vec<u8>,
+    id: u64,
+}
+impl Skin {
+    pub fn from_png(bytes: &[u8]) -> Result<Skin, ThemeError> {
+        let image = png::decode_rgba(bytes)?;
+        let tile_size = image.height;
+        if tile_size == 0 || image.width < tile_size * SKIN_TILES {
+            return Err(ThemeError::SkinSize);
+        }
+        // FNV-1a, so renderers can tell skins apart when caching them
+        let id = image
+            .pixels
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+            });
+        Ok(Skin {
+            tile_size,
+            width: image.width,
+            pixels: image.pixels,
+            id,
+        })
+    }
+    #[must_use]
+    pub fn get_tile_size(&self) -> u32 {
+        self.tile_size
+    }
+    #[must_use]
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+    #[must_use]
+    pub fn get_pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+    #[must_use]
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+    // which tile to use for one of the board's squares
+    #[must_use]
+    pub fn tile_for(square: u32) -> u32 {
+        match square {
+            0..=6 => square,
+            FLASH_SQUARE => 7,
+            _ => 8,
+        }
+    }
+    // a pixel of a tile, as RGBA bytes
+    #[must_use]
+    pub fn get_pixel(&self, tile: u32, x: u32, y: u32) -> [u8; 4] {
+        let column = (tile * self.tile_size + x.min(self.tile_size - 1)) as usize;
+        let index = (y.min(self.tile_size - 1) as usize * self.width as usize + column) * 4;
+        [
+            self.pixels[index],
+            self.pixels[index + 1],
+            self.pixels[index + 2],
+            self.pixels[index + 3],
+        ]
+    }
+    // for anything that can only draw flat colours
+    #[must_use]
+    pub fn average_colour(&self, tile: u32) -> Colour {
+        let mut totals = [0_u64; 4];
+        for y in 0..self.tile_size {
+            for x in 0..self.tile_size {
+                for (total, channel) in totals.iter_mut().zip(self.get_pixel(tile, x, y)) {
+                    *total += u64::from(channel);
+                }
+            }
+        }
+        let count = (u64::from(self.tile_size) * u64::from(self.tile_size) * 255) as f32;
+        Colour::new(
+            totals[0] as f32 / count,
+            totals[1] as f32 / count,
+            totals[2] as f32 / count,
+            totals[3] as f32 / count,
+        )
+    }
+}
+
+// Everything about how the board & pieces look. The built in themes are below, more
+// can be added as TOML files in the themes directory next to the settings
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub name: String,
+    pub block_style: BlockStyle,
+    pub border_style: BorderStyle,
+    pub background: Colour,
+    pub grid: Option<Colour>, // lines between the squares, if any
+    pub wall: Colour,
+    pub flash: Colour, // lines about to be cleared
+    pub pieces: Palette,
+    // a PNG of block pictures for the skin style, next to the theme's file
+    pub skin: Option<PathBuf>,
+    #[serde(skip)]
+    pub skin_image: Option<Skin>,
+}
+impl Default for Theme {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+impl Theme {
+    // the original look
+    #[must_use]
+    pub fn classic() -> Self {
+        Self {
+            name: "Classic".to_string(),
+            block_style: BlockStyle::Flat,
+            border_style: BorderStyle::Blocks,
+            background: Colour::BLACK,
+            grid: None,
+            wall: Colour::WHITE,
+            flash: Colour::VIOLET,
+            pieces: Palette {
+                i: Colour::SKYBLUE,
+                j: Colour::DARKBLUE,
+                l: Colour::ORANGE,
+                s: Colour::GREEN,
+                z: Colour::RED,
+                o: Colour::YELLOW,
+                t: Colour::PURPLE,
+            },
+            skin: None,
+            skin_image: None,
+        }
+    }
+    // the colours most modern versions use
+    #[must_use]
+    pub fn guideline() -> Self {
+        Self {
+            name: "Guideline".to_string(),
+            block_style: BlockStyle::Bevelled,
+            border_style: BorderStyle::Line,
+            background: Colour::new(0.06, 0.06, 0.09, 1.0),
+            grid: Some(Colour::new(0.16, 0.16, 0.21, 1.0)),
+            wall: Colour::new(0.6, 0.6, 0.65, 1.0),
+            flash: Colour::WHITE,
+            pieces: Palette {
+                i: Colour::new(0.0, 0.94, 0.94, 1.0),
+                j: Colour::new(0.0, 0.0, 0.94, 1.0),
+                l: Colour::new(0.94, 0.63, 0.0, 1.0),
+                s: Colour::new(0.0, 0.94, 0.0, 1.0),
+                z: Colour::new(0.94, 0.0, 0.0, 1.0),
+                o: Colour::new(0.94, 0.94, 0.0, 1.0),
+                t: Colour::new(0.63, 0.0, 0.94, 1.0),
+            },
+            skin: None,
+            skin_image: None,
+        }
+    }
+    // The Okabe-Ito palette, which stays distinct with the common kinds of colour
+    // blindness. Bevels give the pieces edges so neighbours don't run together
+    #[must_use]
+    pub fn colour_blind() -> Self {
+        Self {
+            name: "Colour-blind safe".to_string(),
+            block_style: BlockStyle::Bevelled,
+            border_style: BorderStyle::Blocks,
+            background: Colour::BLACK,
+            grid: Some(Colour::new(0.12, 0.12, 0.12, 1.0)),
+            wall: Colour::new(0.6, 0.6, 0.6, 1.0),
+            flash: Colour::WHITE,
+            pieces: Palette {
+                i: Colour::new(0.337, 0.706, 0.914, 1.0),
+                j: Colour::new(0.0, 0.447, 0.698, 1.0),
+                l: Colour::new(0.902, 0.624, 0.0, 1.0),
+                s: Colour::new(0.0, 0.620, 0.451, 1.0),
+                z: Colour::new(0.835, 0.369, 0.0, 1.0),
+                o: Colour::new(0.941, 0.894, 0.259, 1.0),
+                t: Colour::new(0.8, 0.475, 0.655, 1.0),
+            },
+            skin: None,
+            skin_image: None,
+        }
+    }
+    #[must_use]
+    pub fn outline() -> Self {
+        Self {
+            name: "Outline".to_string(),
+            block_style: BlockStyle::Outline,
+            border_style: BorderStyle::Line,
+            grid: None,
+            ..Self::guideline()
+        }
+    }
+    #[must_use]
+    pub fn built_in() -> Vec<Theme> {
+        vec![
+            Self::classic(),
+            Self::guideline(),
+            Self::colour_blind(),
+            Self::outline(),
+        ]
+    }
+
+    #[must_use]
+    pub fn default_directory() -> Option<PathBuf> {
+        storage::config_path(THEME_DIRECTORY)
+    }
+
+    // Reads a theme file, along with its skin if it has one
+    pub fn load_from(path: &Path) -> Result<Theme, ThemeError> {
+        let text = fs::read_to_string(path).map_err(StorageError::from)?;
+        let mut ret_theme: Theme = storage::from_toml(&text)?;
+        if let Some(skin) = &ret_theme.skin {
+            let skin_path = path.parent().unwrap_or(Path::new(".")).join(skin);
+            let bytes = fs::read(&skin_path).map_err(StorageError::from)?;
+            ret_theme.skin_image = Some(Skin::from_png(&bytes)?);
+        }
+        Ok(ret_theme)
+    }
+
+    // The built in themes followed by any in the directory, in name order.
+    // One that can't be read is skipped so it can't stop the game from starting
+    #[must_use]
+    pub fn load_all(directory: Option<&Path>) -> Vec<Theme> {
+        let mut ret_themes = Self::built_in();
+        let Some(entries) = directory.and_then(|directory| fs::read_dir(directory).ok()) else {
+            return ret_themes;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "toml")
+            })
+            .collect();
+        paths.sort();
+        for path in paths {
+            match Self::load_from(&path) {
+                Ok(theme) => ret_themes.push(theme),
+                Err(error) => warning!("Skipping theme {}: {error}", path.display()),
+            }
+        }
+        ret_themes
+    }
+
+    // the theme with this name, or the first one if it has gone
+    #[must_use]
+    pub fn find<'a>(themes: &'a [Theme], name: &str) -> Option<&'a Theme> {
+        themes
+            .iter()
+            .find(|theme| theme.name == name)
+            .or_else(|| themes.first())
+    }
+
+    // the colour for one of the board's squares
+    #[must_use]
+    pub fn colour(&self, square: u32) -> Colour {
+        match square {
+            FLASH_SQUARE => self.flash,
+            WALL_SQUARE => self.wall,
+            _ => TetrominoShape::ALL
+                .get(square as usize)
+                .map_or(self.wall, |shape| self.pieces.get(*shape)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_theme_file_round_trip() {
+        let theme = Theme::guideline();
+        let text = storage::to_toml(&theme).unwrap();
+        assert!(text.contains("block_style = \"bevelled\""));
+        let read: Theme = storage::from_toml(&text).unwrap();
+        assert_eq!(read.name, theme.name);
+        assert_eq!(read.block_style, theme.block_style);
+        assert_eq!(read.pieces.get(TetrominoShape::I).to_hex(), "#00f0f0");
+        // anything left out of a file comes from the classic theme
+        let partial: Theme = storage::from_toml("name = \"Mine\"\nwall = \"#ff000080\"").unwrap();
+        assert_eq!(partial.pieces, Theme::classic().pieces);
+        assert!((partial.wall.a - 128.0 / 255.0).abs() < 0.001);
+        let one_piece: Theme = storage::from_toml("[pieces]\nT = \"#ff00ff\"").unwrap();
+        assert_eq!(one_piece.pieces.t.to_hex(), "#ff00ff");
+        assert_eq!(one_piece.pieces.i, Theme::classic().pieces.i);
+        assert!(storage::from_toml::<Theme>("wall = \"red\"").is_err());
+    }
+    #[test]
+    fn test_skin_tiles() {
+        // nine 2x2 tiles, each a single shade of grey
+        let width = 2 * SKIN_TILES;
+        let pixels: Vec<u8> = (0..2 * width)
+            .flat_map(|index| {
+                let shade = ((index % width) / 2 * 20) as u8;
+                [shade, shade, shade, 255]
+            })
+            .collect();
+        let skin = Skin::from_png(&png::encode_rgba(width, 2, &pixels)).unwrap();
+        assert_eq!(skin.get_tile_size(), 2);
+        assert_eq!(
+            skin.get_pixel(Skin::tile_for(WALL_SQUARE), 1, 1),
+            [160, 160, 160, 255]
+        );
+        assert!((skin.average_colour(1).r - 20.0 / 255.0).abs() < 0.001);
+        let too_narrow = png::encode_rgba(4, 2, &pixels[..32]);
+        assert!(matches!(
+            Skin::from_png(&too_narrow),
+            Err(ThemeError::SkinSize)
+        ));
+    }
+    #[test]
+    fn test_missing_theme_falls_back() {
+        let themes = Theme::built_in();
+        assert_eq!(Theme::find(&themes, "Outline").unwrap().name, "Outline");
+        assert_eq!(Theme::find(&themes, "Deleted").unwrap().name, "Classic");
+        let directory = std::env::temp_dir().join("rust_tetris_theme_test");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("broken.toml"), "block_style = 3").unwrap();
+        fs::write(directory.join("mine.toml"), "name = \"Mine\"").unwrap();
+        let themes = Theme::load_all(Some(&directory));
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(themes.len(), Theme::built_in().len() + 1);
+        assert_eq!(themes.last().unwrap().name, "Mine");
+    }
+}